@@ -1,7 +1,9 @@
 // build task tray resource
 
-use windres::Build;
-
 fn main() {
-    Build::new().compile("assets/tray.rc").unwrap();
+    // the icon resource only exists for windows, `windres::Build` needs a windows host
+    #[cfg(windows)]
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+        windres::Build::new().compile("assets/tray.rc").unwrap();
+    }
 }
@@ -0,0 +1,160 @@
+// A `.taiwubak` file: one backup as a zip, the save `local.sav` with `taiwubak.json` describing
+// it, to give a save to a friend or attach it to a bug report. Only with the `export` feature.
+
+#[cfg(feature = "export")]
+use std::fs;
+use std::io;
+#[cfg(feature = "export")]
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "export")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "export")]
+use crate::game::game;
+use crate::game_root::GameSetup;
+#[cfg(feature = "export")]
+use crate::store;
+use crate::store::BackupEntry;
+
+const ARCHIVE_EXTENSION: &str = "taiwubak";
+#[cfg(feature = "export")]
+const INFO_FILE_NAME: &str = "taiwubak.json";
+// of `taiwubak.json`, newer ones are not read
+const ARCHIVE_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveInfo {
+    pub format: u32,
+    // of this program
+    pub app_version: String,
+    pub world: usize,
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    // when it was backed up
+    pub time: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+    // installed when it was backed up, see `GameSetup`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mods: Vec<String>,
+    pub size: u64,
+    pub sha256: String,
+    pub exported: DateTime<Utc>,
+}
+
+pub fn describe(world: usize, entry: &BackupEntry, sha256: String, setup: GameSetup) -> ArchiveInfo {
+    ArchiveInfo {
+        format: ARCHIVE_FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_owned(),
+        world,
+        id: entry.id.clone(),
+        seq: entry.seq,
+        time: entry.utc,
+        label: entry.label.clone(),
+        game_version: setup.game_version,
+        mods: setup.mods,
+        size: entry.size,
+        sha256,
+        exported: Utc::now(),
+    }
+}
+
+// `world_1-143.taiwubak`, by the number of the backup if it has one
+fn file_name(info: &ArchiveInfo) -> String {
+    let id = info.seq.map(|seq| seq.to_string()).unwrap_or_else(|| info.id.clone());
+    format!("{}-{}.{}", crate::world_folder_name(info.world), id, ARCHIVE_EXTENSION)
+}
+
+// only replaced once complete
+#[cfg(feature = "export")]
+pub fn write(dst: &Path, info: &ArchiveInfo, save: &[u8]) -> io::Result<()> {
+    let tmp = store::temp_path(dst);
+    let result = (|| {
+        let mut zip = zip::ZipWriter::new(fs::File::create(&tmp)?);
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file(INFO_FILE_NAME, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(info)?)?;
+        zip.start_file(game().save_file_name(), options)?;
+        zip.write_all(save)?;
+        zip.finish()?.sync_all()
+    })();
+    match result {
+        Ok(()) => fs::rename(&tmp, dst),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        },
+    }
+}
+
+#[cfg(not(feature = "export"))]
+pub fn write(dst: &Path, _info: &ArchiveInfo, _save: &[u8]) -> io::Result<()> {
+    Err(unsupported(dst))
+}
+
+// an archive read back by `read`, and the backup it became
+#[derive(Debug, Clone, Serialize)]
+pub struct Imported {
+    pub info: ArchiveInfo,
+    pub world: usize,
+    // of the new backup, what it would have been in a dry run
+    pub id: String,
+}
+
+// the save checked against what the archive says about it, `InvalidData` if it is not one this
+// program can import
+#[cfg(feature = "export")]
+pub fn read(path: &Path) -> io::Result<(ArchiveInfo, Vec<u8>)> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(path)?).map_err(|e| invalid(format!("it is not a zip file ({})", e)))?;
+    let mut read_file = |name: &str| -> io::Result<Vec<u8>> {
+        let mut file = match zip.by_name(name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Err(invalid(format!("it has no `{}`", name))),
+            Err(e) => return Err(e.into()),
+        };
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    };
+    let info: ArchiveInfo = serde_json::from_slice(&read_file(INFO_FILE_NAME)?)
+        .map_err(|e| invalid(format!("`{}` is invalid: {}", INFO_FILE_NAME, e)))?;
+    if info.format > ARCHIVE_FORMAT {
+        return Err(invalid(format!("it was exported by a newer version ({}) of this program", info.app_version)));
+    }
+    let save = read_file(game().save_file_name())?;
+    if save.len() as u64 != info.size || format!("{:x}", Sha256::digest(&save)) != info.sha256 {
+        return Err(invalid("the save in it differs from what it was exported with".to_owned()));
+    }
+    Ok((info, save))
+}
+
+#[cfg(not(feature = "export"))]
+pub fn read(path: &Path) -> io::Result<(ArchiveInfo, Vec<u8>)> {
+    Err(unsupported(path))
+}
+
+#[cfg(not(feature = "export"))]
+fn unsupported(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("`{}` can only be written and read with the `export` feature", path.display()))
+}
+
+#[cfg(feature = "export")]
+pub fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// a folder gets the archive named by `file_name`
+pub fn target_path(dst: &Path, info: &ArchiveInfo) -> PathBuf {
+    if dst.is_dir() {
+        dst.join(file_name(info))
+    } else {
+        dst.to_owned()
+    }
+}
@@ -0,0 +1,135 @@
+// Start the program on user login: the `Run` registry key on windows,
+// an XDG autostart entry on linux and a launch agent on macos.
+
+use std::env;
+
+use log::info;
+
+use crate::Result;
+
+const AUTOSTART_NAME: &str = "TaiwuBackup";
+
+pub fn is_enabled() -> Result<bool> {
+    imp::is_enabled()
+}
+
+pub fn enable() -> Result<()> {
+    let exe = env::current_exe()?;
+    imp::enable(&exe)?;
+    info!("[Autostart] enabled for `{}`", exe.display());
+    Ok(())
+}
+
+pub fn disable() -> Result<()> {
+    imp::disable()?;
+    info!("[Autostart] disabled");
+    Ok(())
+}
+
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    if enabled {
+        enable()
+    } else {
+        disable()
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE};
+    use winreg::RegKey;
+
+    use super::AUTOSTART_NAME;
+    use crate::Result;
+
+    const RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+    fn run_key(flags: u32) -> io::Result<RegKey> {
+        RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(RUN_KEY, flags)
+    }
+
+    pub fn is_enabled() -> Result<bool> {
+        Ok(run_key(KEY_READ)?.get_value::<String, _>(AUTOSTART_NAME).is_ok())
+    }
+
+    pub fn enable(exe: &Path) -> Result<()> {
+        let command = format!("\"{}\"", exe.display());
+        run_key(KEY_WRITE)?.set_value(AUTOSTART_NAME, &command)?;
+        Ok(())
+    }
+
+    pub fn disable() -> Result<()> {
+        match run_key(KEY_WRITE)?.delete_value(AUTOSTART_NAME) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    use crate::Result;
+
+    pub fn is_enabled() -> Result<bool> {
+        Ok(super::entry_file()?.is_file())
+    }
+
+    pub fn enable(exe: &Path) -> Result<()> {
+        let entry = super::entry_file()?;
+        fs::create_dir_all(entry.parent().unwrap())?;
+        fs::write(entry, entry_content(exe))?;
+        Ok(())
+    }
+
+    pub fn disable() -> Result<()> {
+        match fs::remove_file(super::entry_file()?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn entry_content(exe: &Path) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n\
+             \t<key>Label</key>\n\t<string>{}</string>\n\
+             \t<key>ProgramArguments</key>\n\t<array>\n\t\t<string>{}</string>\n\t</array>\n\
+             \t<key>RunAtLoad</key>\n\t<true/>\n\
+             </dict>\n</plist>\n",
+            super::AUTOSTART_NAME,
+            exe.display()
+        )
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn entry_content(exe: &Path) -> String {
+        format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\n",
+            super::AUTOSTART_NAME,
+            exe.display()
+        )
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn entry_file() -> Result<std::path::PathBuf> {
+    let base_dirs = directories::BaseDirs::new().ok_or(crate::TaiwuError::HomeDirNotAvailable)?;
+    let name = format!("com.github.{}.plist", AUTOSTART_NAME);
+    Ok(base_dirs.home_dir().join("Library").join("LaunchAgents").join(name))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn entry_file() -> Result<std::path::PathBuf> {
+    let base_dirs = directories::BaseDirs::new().ok_or(crate::TaiwuError::HomeDirNotAvailable)?;
+    let name = format!("{}.desktop", AUTOSTART_NAME);
+    Ok(base_dirs.config_dir().join("autostart").join(name))
+}
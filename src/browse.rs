@@ -0,0 +1,315 @@
+// `taiwu browse`, look through the backups of every world in the terminal.
+//
+// Works on the backup store directly, the watcher does not need to be running. If it is, the
+// saves it is copying are shown with how far it got.
+
+use std::io;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use taiwu::ipc::{self, Request};
+use taiwu::{BackupEntry, BackupQuery, CopyProgress, Taiwu};
+
+use crate::cli::{format_game, format_size, format_utc};
+use crate::i18n::tr;
+
+// asking the running instance what it is copying
+const COPY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn help() -> String {
+    tr!(
+        "←→ 存档位  ↑↓ 选择  r 恢复  l 备注  x 删除  m 标记  d 与标记对比  g 按游戏内日期排序  q 退出",
+        "←→ world  ↑↓ select  r restore  l label  x delete  m mark  d diff with mark  g sort by in-game date  q quit",
+    )
+}
+
+enum Mode {
+    Normal,
+    // editing the label of the selected backup
+    Label(String),
+    ConfirmRestore,
+    ConfirmDelete,
+}
+
+struct App {
+    tw: Taiwu,
+    worlds: Vec<usize>,
+    world: ListState,
+    backups: Vec<BackupEntry>,
+    backup: TableState,
+    // id of the backup to diff against
+    marked: Option<String>,
+    // by the in-game date of the saves instead of when they were backed up
+    by_game_date: bool,
+    mode: Mode,
+    message: String,
+    // by the running instance
+    copies: Vec<CopyProgress>,
+}
+
+pub fn run(tw: Taiwu) -> io::Result<()> {
+    let mut app = App::new(tw);
+    app.reload();
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(tw: Taiwu) -> App {
+        let worlds = tw.worlds();
+        App {
+            tw,
+            worlds,
+            world: ListState::default().with_selected(Some(0)),
+            backups: Vec::new(),
+            backup: TableState::default(),
+            marked: None,
+            by_game_date: false,
+            mode: Mode::Normal,
+            message: String::new(),
+            copies: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(COPY_POLL_INTERVAL)? {
+                self.poll_copies();
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key.code,
+                _ => continue,
+            };
+            // the last message is shown until the next key, then the help again
+            if matches!(self.mode, Mode::Normal) {
+                self.message.clear();
+            }
+
+            match &mut self.mode {
+                Mode::Normal => match key {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Left => self.select_world(-1),
+                    KeyCode::Right | KeyCode::Tab => self.select_world(1),
+                    KeyCode::Up => self.backup.select_previous(),
+                    KeyCode::Down => self.backup.select_next(),
+                    KeyCode::Char('r') if self.selected().is_some() => self.mode = Mode::ConfirmRestore,
+                    KeyCode::Char('x') | KeyCode::Delete if self.selected().is_some() => self.mode = Mode::ConfirmDelete,
+                    KeyCode::Char('l') => {
+                        if let Some(entry) = self.selected() {
+                            self.mode = Mode::Label(entry.label.clone().unwrap_or_default());
+                        }
+                    },
+                    KeyCode::Char('m') => self.mark(),
+                    KeyCode::Char('d') => self.diff(),
+                    KeyCode::Char('g') => {
+                        self.by_game_date = !self.by_game_date;
+                        self.reload();
+                    },
+                    _ => {},
+                },
+                Mode::Label(text) => match key {
+                    KeyCode::Char(c) => text.push(c),
+                    KeyCode::Backspace => {
+                        text.pop();
+                    },
+                    KeyCode::Enter => {
+                        let text = std::mem::take(text);
+                        self.mode = Mode::Normal;
+                        self.label(&text);
+                    },
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    _ => {},
+                },
+                Mode::ConfirmRestore | Mode::ConfirmDelete => {
+                    let restore = matches!(self.mode, Mode::ConfirmRestore);
+                    self.mode = Mode::Normal;
+                    match key {
+                        KeyCode::Char('y') if restore => self.restore(),
+                        KeyCode::Char('y') => self.delete(),
+                        _ => self.message = tr!("已取消", "Cancelled"),
+                    }
+                },
+            }
+        }
+    }
+
+    // the list gets the new backups once they are done
+    fn poll_copies(&mut self) {
+        let copies = ipc::send(Request::Status).ok()
+            .and_then(|response| response.status)
+            .map(|status| status.state.copying)
+            .unwrap_or_default();
+        if copies.is_empty() && !self.copies.is_empty() {
+            self.reload();
+        }
+        self.copies = copies;
+    }
+
+    fn current_world(&self) -> usize {
+        self.worlds[self.world.selected().unwrap_or(0)]
+    }
+
+    fn selected(&self) -> Option<&BackupEntry> {
+        self.backup.selected().and_then(|i| self.backups.get(i))
+    }
+
+    fn select_world(&mut self, step: isize) {
+        let len = self.worlds.len() as isize;
+        let i = self.world.selected().unwrap_or(0) as isize;
+        self.world.select(Some((i + step).rem_euclid(len) as usize));
+        self.marked = None;
+        self.reload();
+    }
+
+    // newest first, keeps the selection on the same backup if it still exists
+    fn reload(&mut self) {
+        let selected = self.selected().map(|entry| entry.id.clone());
+        let query = BackupQuery { newest_first: true, by_game_date: self.by_game_date, ..BackupQuery::default() };
+        self.backups = match self.tw.query_backups(self.current_world(), &query) {
+            Ok(backups) => backups,
+            Err(e) => {
+                self.message = tr!("读取备份失败: {}", "Could not read the backups: {}", e);
+                Vec::new()
+            },
+        };
+        let i = selected.and_then(|id| self.backups.iter().position(|entry| entry.id == id));
+        self.backup.select(if self.backups.is_empty() { None } else { Some(i.unwrap_or(0)) });
+    }
+
+    fn restore(&mut self) {
+        let Some(entry) = self.selected() else { return };
+        let time = format_time(entry);
+        self.message = match self.tw.restore(self.current_world(), &entry.id.clone()) {
+            Ok(()) => tr!("已恢复 {} 的备份", "Restored the backup of {}", time),
+            Err(e) => tr!("恢复失败: {}", "Restore failed: {}", e),
+        };
+    }
+
+    fn delete(&mut self) {
+        let Some(entry) = self.selected() else { return };
+        let (id, time) = (entry.id.clone(), format_time(entry));
+        self.message = match self.tw.delete_backup(self.current_world(), &id) {
+            Ok(()) => tr!("已删除 {} 的备份", "Deleted the backup of {}", time),
+            Err(e) => tr!("删除失败: {}", "Delete failed: {}", e),
+        };
+        if self.marked.as_ref() == Some(&id) {
+            self.marked = None;
+        }
+        self.reload();
+    }
+
+    fn label(&mut self, text: &str) {
+        let Some(entry) = self.selected() else { return };
+        self.message = match self.tw.set_label(self.current_world(), &entry.id.clone(), text) {
+            Ok(()) => tr!("备注已保存", "Label saved"),
+            Err(e) => tr!("备注失败: {}", "Label failed: {}", e),
+        };
+        self.reload();
+    }
+
+    fn mark(&mut self) {
+        let Some(entry) = self.selected() else { return };
+        let message = tr!("已标记 {}，选择另一个备份按 d 对比", "Marked {}, select another backup and press d to diff", format_time(entry));
+        self.marked = Some(entry.id.clone());
+        self.message = message;
+    }
+
+    fn diff(&mut self) {
+        let marked = self.marked.as_ref().and_then(|id| self.backups.iter().find(|entry| &entry.id == id));
+        let (Some(a), Some(b)) = (marked, self.selected()) else {
+            self.message = tr!("请先用 m 标记一个备份", "Mark a backup with m first");
+            return;
+        };
+        let message = match self.tw.diff_backups(self.current_world(), &a.id, &b.id) {
+            Ok(diff) if diff.identical => tr!("{} → {}: 内容相同", "{} → {}: identical", format_time(a), format_time(b)),
+            Ok(diff) => tr!(
+                "{} → {}: 大小 {:+} 字节，{} 个字节不同",
+                "{} → {}: size {:+} bytes, {} bytes differ",
+                format_time(a),
+                format_time(b),
+                diff.size_delta,
+                diff.changed_bytes,
+            ),
+            Err(e) => tr!("对比失败: {}", "Diff failed: {}", e),
+        };
+        self.message = message;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let copying = Constraint::Length(self.copies.len().min(1) as u16);
+        let [main, progress, status] = Layout::vertical([Constraint::Min(0), copying, Constraint::Length(3)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Length(16), Constraint::Min(0)]).areas(main);
+
+        let worlds: Vec<ListItem> = self.worlds.iter().map(|world| ListItem::new(tr!("存档位 {}", "World {}", world))).collect();
+        let worlds = List::new(worlds)
+            .block(Block::bordered().title(tr!(" 太吾绘卷 ", " The Scroll Of Taiwu ")))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(worlds, left, &mut self.world);
+
+        let rows = self.backups.iter().map(|entry| {
+            let mark = if self.marked.as_ref() == Some(&entry.id) { "*" } else { "" };
+            Row::new(vec![
+                mark.to_owned(),
+                entry.seq.map(|seq| format!("#{}", seq)).unwrap_or_default(),
+                format_time(entry),
+                format_size(entry.size),
+                format_game(entry).unwrap_or_default(),
+                entry.label.clone().unwrap_or_default(),
+            ])
+        });
+        let widths = [
+            Constraint::Length(1),
+            Constraint::Length(6),
+            Constraint::Length(19),
+            Constraint::Length(10),
+            Constraint::Length(20),
+            Constraint::Min(0),
+        ];
+        let title = tr!(" 存档位 {} 的备份 ({}) ", " Backups of world {} ({}) ", self.current_world(), self.backups.len());
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec![String::new(), tr!("编号", "No."), tr!("时间", "Time"), tr!("大小", "Size"), tr!("游戏内", "In game"), tr!("备注", "Label")]).bold())
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, right, &mut self.backup);
+
+        let text = match &self.mode {
+            Mode::Normal if self.message.is_empty() => help(),
+            Mode::Normal => self.message.clone(),
+            Mode::Label(text) => tr!(
+                "备注 (回车保存，Esc取消，留空删除): {}_",
+                "Label (Enter saves, Esc cancels, empty removes it): {}_",
+                text,
+            ),
+            Mode::ConfirmRestore => tr!("覆盖当前存档? 请先退出游戏 (y/n)", "Overwrite the current save? Quit the game first (y/n)"),
+            Mode::ConfirmDelete => tr!("删除这个备份? (y/n)", "Delete this backup? (y/n)"),
+        };
+        frame.render_widget(Paragraph::new(text).block(Block::bordered()), status);
+
+        if let Some(copy) = self.copies.first() {
+            let label = tr!(
+                "正在备份 {} {} / {}",
+                "Backing up {} {} / {}",
+                copy.src.display(),
+                format_size(copy.copied),
+                format_size(copy.total),
+            );
+            let ratio = copy.copied as f64 / copy.total.max(1) as f64;
+            frame.render_widget(Gauge::default().ratio(ratio.min(1.0)).label(label), progress);
+        }
+    }
+}
+
+fn format_time(entry: &BackupEntry) -> String {
+    format_utc(entry.utc)
+}
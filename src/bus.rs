@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone)]
+pub enum TaiwuEvent {
+    BackupDone {
+        src: PathBuf,
+        stores: Vec<StoreStatus>,
+        // of the save, copied to every store in `duration`
+        bytes: u64,
+        duration: Duration,
+    },
+    BackupFailed {
+        src: PathBuf,
+        error: String,
+    },
+    // of a save taking a while to copy, every `COPY_PROGRESS_INTERVAL` until it is done
+    CopyProgress(CopyProgress),
+    // the save got older than its latest backup, e.g. Steam Cloud restored an old one
+    CloudConflict {
+        src: PathBuf,
+        src_modified: SystemTime,
+        latest_backup: PathBuf,
+        backup_modified: SystemTime,
+    },
+    // the save is (almost) empty and was not backed up, once until a good one is seen again
+    SaveTruncated {
+        world: usize,
+        src: PathBuf,
+        size: u64,
+    },
+    // the save is much smaller than the recent backups of its world, it is backed up anyway
+    SaveShrank {
+        world: usize,
+        src: PathBuf,
+        size: u64,
+        // of the recent backups
+        average: u64,
+    },
+    // a store was skipped, its volume has too little free space left
+    InsufficientSpace {
+        root: PathBuf,
+        available: u64,
+        required: u64,
+    },
+    // the oldest backups were removed to keep the store under its quota
+    Pruned {
+        removed: Vec<PathBuf>,
+        freed: u64,
+    },
+    GameStarted,
+    // all worlds are backed up right after this
+    GameStopped,
+    // it failed, missed changes or was not running, `running` is false if it could not start again
+    WatcherRestarted {
+        reason: String,
+        polling: bool,
+        running: bool,
+    },
+    // of the integrity scan, `problems` describes each backup that failed it
+    VerifyDone {
+        checked: usize,
+        problems: Vec<String>,
+    },
+    // a queued backup finally reached its store
+    RetryDone {
+        root: PathBuf,
+        dst: PathBuf,
+    },
+}
+
+// result of one backup copy on one store (primary or mirror)
+#[derive(Debug, Clone)]
+pub struct StoreStatus {
+    pub root: PathBuf,
+    pub result: std::result::Result<PathBuf, String>,
+}
+
+// a save being copied to a store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CopyProgress {
+    pub src: PathBuf,
+    pub root: PathBuf,
+    // bytes
+    pub copied: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<TaiwuEvent>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Receiver<TaiwuEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn emit(&self, event: TaiwuEvent) {
+        // subscribers whose receiver was dropped are forgotten
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
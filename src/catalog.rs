@@ -0,0 +1,66 @@
+// All backups of the primary store kept in memory, so the quota can be checked after every
+// backup without scanning the store again.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::store::{self, BackupEntry};
+
+#[derive(Debug, Default)]
+pub struct Catalog {
+    // world folder -> its backups, oldest first
+    folders: BTreeMap<PathBuf, Vec<BackupEntry>>,
+    total_size: u64,
+}
+
+impl Catalog {
+    pub fn scan(root: &Path) -> io::Result<Catalog> {
+        let mut catalog = Catalog::default();
+        if !root.is_dir() {
+            return Ok(catalog);
+        }
+        for entry in fs::read_dir(root)? {
+            let folder = entry?.path();
+            if folder.is_dir() {
+                // packs are rewritten only by `Taiwu::pack` and `Taiwu::prune`, not for the quota,
+                // and chunked backups mostly share their space
+                let loose = store::list_backups(&folder)?.into_iter().filter(|backup| backup.pack.is_none() && backup.chunks.is_none());
+                for backup in loose {
+                    catalog.add(backup);
+                }
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn add(&mut self, entry: BackupEntry) {
+        let folder = entry.path.parent().unwrap().to_owned();
+        self.total_size += entry.size;
+        let backups = self.folders.entry(folder).or_default();
+        let i = backups.partition_point(|backup| backup.order() < entry.order());
+        backups.insert(i, entry);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<BackupEntry> {
+        let backups = self.folders.get_mut(path.parent()?)?;
+        let i = backups.iter().position(|backup| backup.path == path)?;
+        let entry = backups.remove(i);
+        self.total_size -= entry.size;
+        Some(entry)
+    }
+
+    // oldest first over all worlds, the latest backup of a world is never one of them
+    pub fn prune_candidates(&self) -> Vec<&BackupEntry> {
+        let mut candidates: Vec<_> = self.folders.values()
+            .flat_map(|backups| backups.split_last().map(|(_, older)| older).unwrap_or_default())
+            .collect();
+        candidates.sort_by_key(|backup| (backup.utc, backup.timestamp));
+        candidates
+    }
+}
@@ -0,0 +1,193 @@
+// The chunked store format (`store_format = "chunked"`): a backup is split at points found from
+// its content (FastCDC), so an insertion only changes the chunks around it, and every chunk is
+// kept once under `chunks/` of the store root by its SHA-256. The backups of a save that only
+// changed in a few places, and of other worlds, then share most of their space. The backup itself
+// is `<name>.chunks` in its folder, the list of its chunks, with the modified time of the save.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::store;
+
+const CHUNKS_FOLDER_NAME: &str = "chunks";
+const CHUNK_LIST_EXTENSION: &str = "chunks";
+#[cfg(feature = "chunked")]
+const MIN_CHUNK_SIZE: u32 = 16 * 1024;
+#[cfg(feature = "chunked")]
+const AVG_CHUNK_SIZE: u32 = 64 * 1024;
+#[cfg(feature = "chunked")]
+const MAX_CHUNK_SIZE: u32 = 256 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkList {
+    pub size: u64,
+    // of the whole backup
+    pub sha256: String,
+    // in order
+    pub chunks: Vec<String>,
+}
+
+// the chunk list standing for the backup `backup`
+pub fn list_path(backup: &Path) -> PathBuf {
+    let mut name = backup.as_os_str().to_owned();
+    name.push(".");
+    name.push(CHUNK_LIST_EXTENSION);
+    PathBuf::from(name)
+}
+
+pub fn backup_path(list: &Path) -> Option<PathBuf> {
+    is_chunk_list(list).then(|| list.with_extension(""))
+}
+
+pub fn is_chunk_list(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == CHUNK_LIST_EXTENSION)
+}
+
+pub fn is_chunk_folder(path: &Path) -> bool {
+    path.file_name() == Some(CHUNKS_FOLDER_NAME.as_ref())
+}
+
+pub fn read_list(list: &Path) -> io::Result<ChunkList> {
+    Ok(serde_json::from_slice(&fs::read(list)?)?)
+}
+
+// the nearest above the list, so a store still reads once moved
+fn chunk_folder(list: &Path) -> io::Result<PathBuf> {
+    list.ancestors()
+        .skip(1)
+        .map(|folder| folder.join(CHUNKS_FOLDER_NAME))
+        .find(|folder| folder.is_dir())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no chunks for `{}`", list.display())))
+}
+
+fn chunk_path(folder: &Path, hash: &str) -> PathBuf {
+    folder.join(&hash[..2]).join(hash)
+}
+
+// `src` (a complete copy of the save) into the store `root` as the chunk list `list`, only the
+// chunks it does not have yet are written
+#[cfg(feature = "chunked")]
+pub fn write(root: &Path, src: &Path, list: &Path, sha256: String, durable: bool) -> io::Result<()> {
+    let bytes = fs::read(src)?;
+    let folder = root.join(CHUNKS_FOLDER_NAME);
+    let mut chunks = Vec::new();
+    for chunk in fastcdc::v2020::FastCDC::new(&bytes, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE) {
+        let data = &bytes[chunk.offset..chunk.offset + chunk.length];
+        let hash = format!("{:x}", Sha256::digest(data));
+        let path = chunk_path(&folder, &hash);
+        if !path.is_file() {
+            fs::create_dir_all(path.parent().unwrap())?;
+            write_complete(&path, data, durable)?;
+        }
+        chunks.push(hash);
+    }
+
+    let chunk_list = ChunkList { size: bytes.len() as u64, sha256, chunks };
+    write_complete(list, &serde_json::to_vec(&chunk_list)?, durable)?;
+    // what `is_same_file` and the cloud conflict check compare the save with
+    let modified = fs::metadata(src)?.modified()?;
+    fs::File::options().write(true).open(list)?.set_modified(modified)
+}
+
+#[cfg(not(feature = "chunked"))]
+pub fn write(_root: &Path, _src: &Path, list: &Path, _sha256: String, _durable: bool) -> io::Result<()> {
+    let msg = format!("`{}` can only be written with the `chunked` feature", list.display());
+    Err(io::Error::new(io::ErrorKind::Unsupported, msg))
+}
+
+#[cfg(feature = "chunked")]
+fn write_complete(path: &Path, data: &[u8], durable: bool) -> io::Result<()> {
+    let tmp = store::temp_path(path);
+    fs::write(&tmp, data)?;
+    if durable {
+        fs::File::options().write(true).open(&tmp)?.sync_all()?;
+    }
+    fs::rename(tmp, path)
+}
+
+// as it was backed up, an error if a chunk is missing or it does not add up any more
+pub fn read(list: &Path) -> io::Result<Vec<u8>> {
+    let chunk_list = read_list(list)?;
+    let bytes = assemble(list, &chunk_list)?;
+    if format!("{:x}", Sha256::digest(&bytes)) != chunk_list.sha256 {
+        let msg = format!("the chunks of `{}` differ from what was backed up", list.display());
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+    Ok(bytes)
+}
+
+// of the chunks put together, for the integrity scan to compare with the recorded one
+pub fn sha256(list: &Path) -> io::Result<String> {
+    let bytes = assemble(list, &read_list(list)?)?;
+    Ok(format!("{:x}", Sha256::digest(bytes)))
+}
+
+fn assemble(list: &Path, chunk_list: &ChunkList) -> io::Result<Vec<u8>> {
+    let folder = chunk_folder(list)?;
+    let mut bytes = Vec::with_capacity(chunk_list.size as usize);
+    for hash in &chunk_list.chunks {
+        bytes.extend(fs::read(chunk_path(&folder, hash))?);
+    }
+    Ok(bytes)
+}
+
+// the chunks of the list that do not have their content anymore, so the next backup writes
+// them again instead of using them, with their hashes
+pub fn remove_damaged(list: &Path) -> io::Result<Vec<String>> {
+    let folder = chunk_folder(list)?;
+    let mut removed = Vec::new();
+    for hash in read_list(list)?.chunks {
+        let path = chunk_path(&folder, &hash);
+        let Ok(data) = fs::read(&path) else { continue };
+        if format!("{:x}", Sha256::digest(data)) != hash {
+            fs::remove_file(&path)?;
+            removed.push(hash);
+        }
+    }
+    Ok(removed)
+}
+
+// the chunks no list under `root` uses anymore (quarantined ones included), with their size; a
+// backup being written must not be, its chunks are there before its list
+pub fn collect_garbage(root: &Path) -> io::Result<(usize, u64)> {
+    let folder = root.join(CHUNKS_FOLDER_NAME);
+    if !folder.is_dir() {
+        return Ok((0, 0));
+    }
+    let mut used = BTreeSet::new();
+    find_used(root, &mut used)?;
+
+    let (mut removed, mut freed) = (0, 0);
+    for prefix in fs::read_dir(&folder)? {
+        for chunk in fs::read_dir(prefix?.path())? {
+            let path = chunk?.path();
+            let hash = path.file_name().unwrap().to_string_lossy();
+            if used.contains(hash.as_ref()) || store::is_temp_file(&path) {
+                continue;
+            }
+            freed += path.metadata()?.len();
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok((removed, freed))
+}
+
+fn find_used(folder: &Path, used: &mut BTreeSet<String>) -> io::Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if !is_chunk_folder(&path) {
+                find_used(&path, used)?;
+            }
+        } else if is_chunk_list(&path) {
+            used.extend(read_list(&path)?.chunks);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,1005 @@
+use std::error::Error;
+
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::json;
+use simplelog::{LevelFilter, WriteLogger};
+
+use taiwu::{autostart, BackupDiff, BackupEntry, BackupQuery, BackupSelector, Config, HistoryFilter, HistoryKind, InstanceGuard, LogLevel, PrunePolicy, PruneReport, Stats, Taiwu};
+use taiwu::heartbeat::Heartbeat;
+use taiwu::ipc::{self, Request, Response};
+
+use crate::i18n::tr;
+
+// without a subcommand the tray app is started as usual
+#[derive(Debug, Parser)]
+#[command(version, about = "太吾绘卷存档自动备份 (The Scroll Of Taiwu save backup)")]
+pub struct Cli {
+    /// Run as the windows service (started by the service manager)
+    #[cfg(windows)]
+    #[arg(long, hide = true)]
+    pub service: bool,
+
+    /// Run in the foreground without tray, logging to stdout
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Print the result as JSON, with the same fields whatever the language
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Only log what would be copied, pruned or restored
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Keep the config, logs and backups next to this program (as if `portable.flag` was there)
+    #[arg(long, global = true)]
+    pub portable: bool,
+
+    /// Break the lock of the backup store, when a program that is not running anymore still holds it
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Read this config file instead of `config.toml` in the appdata folder
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<std::path::PathBuf>,
+
+    /// Use this profile of the config instead of the active one
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// The game folder, over the config file and the detection
+    #[arg(long, global = true, value_name = "DIR")]
+    pub game_root: Option<std::path::PathBuf>,
+
+    /// The folder of the backups, over the config file
+    #[arg(long, global = true, value_name = "DIR")]
+    pub backup_root: Option<std::path::PathBuf>,
+
+    /// Log at this level (error, warn, info, debug or trace), over the config file
+    #[arg(long, global = true, value_name = "LEVEL")]
+    pub log_level: Option<LogLevel>,
+
+    /// Started again by the tray, wait for the old instance to quit
+    #[arg(long, hide = true)]
+    pub restarted: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start this program on user login or not
+    Autostart {
+        #[command(subcommand)]
+        action: AutostartAction,
+    },
+    /// Browse, restore, label or delete backups in the terminal
+    #[cfg(feature = "tui")]
+    Browse,
+    /// List the backups of a world, or of every world
+    List {
+        #[arg(value_parser = parse_world)]
+        world: Option<usize>,
+        /// Only backups made at or after, e.g. `2022-10-01` or `2022-10-01 12:00:00`
+        #[arg(long, value_parser = parse_time)]
+        since: Option<chrono::NaiveDateTime>,
+        /// Only backups made before
+        #[arg(long, value_parser = parse_time)]
+        until: Option<chrono::NaiveDateTime>,
+        /// Newest first instead of oldest first
+        #[arg(long)]
+        newest_first: bool,
+        /// At most this many backups per world
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Also show the SHA-256 of the content (reads the backups made before it was recorded)
+        #[arg(long)]
+        hash: bool,
+        /// Only backups of saves in this in-game year
+        #[arg(long)]
+        game_year: Option<u32>,
+        /// Only backups of saves of a character whose name contains this
+        #[arg(long)]
+        character: Option<String>,
+        /// By the in-game date of the saves instead of when they were backed up
+        #[arg(long)]
+        by_game_date: bool,
+    },
+    /// Remove old backups of every world, the latest and labeled ones are always kept (see `--dry-run`)
+    Prune {
+        /// Keep the newest N backups of each world
+        #[arg(long)]
+        keep_last: Option<usize>,
+        /// Only remove backups older than this, e.g. `30d`, `12h` or `2w`
+        #[arg(long, value_parser = parse_age)]
+        older_than: Option<std::time::Duration>,
+        /// Then remove the oldest until all backups take at most this, e.g. `2GB` or `500MB`
+        #[arg(long, value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Show how many backups there are and how much space they take
+    Stats,
+    /// Show what was backed up, skipped, restored or pruned and what failed, e.g. `taiwu history --since yesterday`
+    History {
+        #[arg(long, value_parser = parse_world)]
+        world: Option<usize>,
+        /// Only entries at or after, e.g. `yesterday 21:00`, `2h ago` or `2022-10-01`
+        #[arg(long, value_parser = parse_at)]
+        since: Option<chrono::NaiveDateTime>,
+        /// Only entries before
+        #[arg(long, value_parser = parse_at)]
+        until: Option<chrono::NaiveDateTime>,
+        /// Only these kinds, any of backup, skip, error, restore and prune (e.g. `backup,error`)
+        #[arg(long, value_delimiter = ',')]
+        kind: Vec<HistoryKind>,
+        /// Newest first instead of oldest first
+        #[arg(long)]
+        newest_first: bool,
+        /// At most this many, the newest ones
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Read every backup and compare the copies on the mirrors, to find damaged ones
+    Verify,
+    /// Bundle old backups into one compressed pack per world and month, they stay restorable
+    Pack {
+        /// Pack backups older than this, e.g. `30d`; by default `pack_after_days` of the config
+        #[arg(long, value_parser = parse_age)]
+        older_than: Option<std::time::Duration>,
+    },
+    /// Restore a backup of a world, into its own slot or with `--to` into another one
+    #[command(group = ArgGroup::new("backup").args(["id", "at", "before_backup"]))]
+    Restore {
+        #[arg(value_parser = parse_world)]
+        world: usize,
+        /// The backup id, or its number like `#143`, by default the latest good backup (for a save the game left truncated)
+        id: Option<String>,
+        /// The backup nearest to a time, e.g. `yesterday 21:00`, `21:00`, `2h ago` or `2022-10-01 21:00`
+        #[arg(long, value_parser = parse_at)]
+        at: Option<chrono::NaiveDateTime>,
+        /// The backup right before the newest one with this label
+        #[arg(long, value_name = "LABEL")]
+        before_backup: Option<String>,
+        /// The slot to restore into, it must be empty unless `--overwrite` is given
+        #[arg(long, requires = "backup", value_parser = parse_world)]
+        to: Option<usize>,
+        #[arg(long, requires = "to")]
+        overwrite: bool,
+    },
+    /// Put a backup (by default the latest labeled one) back whenever the game writes the save of a world
+    Freeze {
+        #[arg(value_parser = parse_world)]
+        world: usize,
+        /// The backup id, or its number like `#143`
+        id: Option<String>,
+    },
+    /// Save a backup (by default the latest) with its time, label and game version as one `.taiwubak` file to share
+    Export {
+        #[arg(value_parser = parse_world)]
+        world: usize,
+        /// The backup id, or its number like `#143`
+        id: Option<String>,
+        /// The file, or the folder to put it in (by default the current one)
+        #[arg(short, long, default_value = ".")]
+        output: std::path::PathBuf,
+    },
+    /// Add a `.taiwubak` file as a new backup, and restore it into an empty slot if asked to
+    Import {
+        file: std::path::PathBuf,
+        /// The world to add it to, by default the one it was exported from
+        #[arg(long, value_parser = parse_world)]
+        world: Option<usize>,
+        /// Then restore it into this slot, which must be empty
+        #[arg(long, value_parser = parse_world)]
+        restore_to: Option<usize>,
+    },
+    /// Stop putting the frozen backup back
+    Unfreeze {
+        #[arg(value_parser = parse_world)]
+        world: usize,
+    },
+    /// Put back the save that the last restore into a world replaced
+    UndoRestore {
+        #[arg(value_parser = parse_world)]
+        world: usize,
+    },
+    /// Compare two backups of a world, ids (or numbers like `#143`) as listed by `taiwu list`
+    Diff {
+        #[arg(value_parser = parse_world)]
+        world: usize,
+        a: String,
+        b: String,
+    },
+    /// List the profiles of the config, or switch to one (a running instance uses it once restarted)
+    Profile {
+        name: Option<String>,
+        /// Go back to `profile` of the config file
+        #[arg(long, conflicts_with = "name")]
+        default: bool,
+    },
+    /// Move the whole store (with the folders of every profile) to another folder and set it as `backup_root`
+    MigrateStore {
+        /// A folder that is empty or not there yet
+        new_root: std::path::PathBuf,
+        /// Copy only, the old files stay where they are
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Look for a newer release on GitHub
+    #[cfg(feature = "update")]
+    CheckUpdate,
+    /// Download the latest release from GitHub and replace this program with it
+    #[cfg(feature = "update")]
+    SelfUpdate,
+    /// Change the log level of the running instance (error, warn, info, debug or trace)
+    LogLevel {
+        level: LogLevel,
+    },
+    /// Send a command to the running instance
+    Ctl {
+        #[arg(value_enum)]
+        command: CtlCommand,
+    },
+    /// Back up the saves now, in the running instance if there is one
+    BackupNow,
+    /// Pause the backups of the running instance, as `taiwu ctl pause`
+    Pause,
+    /// Resume the backups of the running instance, as `taiwu ctl resume`
+    Resume,
+    /// Show the status of the running instance, as `taiwu ctl status`
+    Status,
+    /// Quit the running instance, as `taiwu ctl quit`
+    Quit,
+    /// Print the completion script of a shell (bash, zsh, fish, powershell or elvish)
+    Completions {
+        shell: clap_complete::Shell,
+    },
+    /// Print the manual page, or write one per command into a folder
+    Man {
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Install or control the windows service (needs administrator)
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AutostartAction {
+    Enable,
+    Disable,
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CtlCommand {
+    BackupNow,
+    Pause,
+    Resume,
+    Status,
+    Quit,
+}
+
+impl From<CtlCommand> for Request {
+    fn from(command: CtlCommand) -> Request {
+        match command {
+            CtlCommand::BackupNow => Request::BackupNow,
+            CtlCommand::Pause => Request::Pause,
+            CtlCommand::Resume => Request::Resume,
+            CtlCommand::Status => Request::Status,
+            CtlCommand::Quit => Request::Quit,
+        }
+    }
+}
+
+#[cfg(windows)]
+#[derive(Debug, Subcommand)]
+pub enum ServiceAction {
+    Install,
+    Uninstall,
+    Start,
+    Stop,
+}
+
+pub fn run(command: Command, dry_run: bool, json: bool) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Autostart { action } => {
+            match action {
+                AutostartAction::Enable => autostart::enable()?,
+                AutostartAction::Disable => autostart::disable()?,
+                AutostartAction::Status => {},
+            }
+            let enabled = autostart::is_enabled()?;
+            if json {
+                print_json(&json!({ "autostart": enabled }))?;
+            } else {
+                let status = match (action, enabled) {
+                    (AutostartAction::Status, true) => tr!("开机自启: 已开启", "autostart enabled"),
+                    (AutostartAction::Status, false) => tr!("开机自启: 已关闭", "autostart disabled"),
+                    (_, true) => tr!("已开启开机自启", "autostart enabled"),
+                    (_, false) => tr!("已关闭开机自启", "autostart disabled"),
+                };
+                println!("{}", status);
+            }
+        },
+        #[cfg(feature = "tui")]
+        Command::Browse => {
+            let tw = load(dry_run)?;
+            crate::browse::run(tw)?;
+        },
+        Command::List { world, since, until, newest_first, limit, hash, game_year, character, by_game_date } => {
+            let tw = load(dry_run)?;
+            let query = BackupQuery { since, until, newest_first, limit, hash, game_year, character, by_game_date };
+            let worlds = world.map(|world| vec![world]).unwrap_or_else(|| tw.worlds());
+            let mut list = Vec::new();
+            for world in worlds {
+                list.push((world, tw.query_backups(world, &query)?));
+            }
+            if json {
+                let list: Vec<_> = list.iter().map(|(world, backups)| json!({ "world": world, "backups": backups })).collect();
+                print_json(&list)?;
+            } else {
+                print_backups(&list);
+            }
+        },
+        Command::Prune { keep_last, older_than, max_size } => {
+            let policy = PrunePolicy { keep_last, older_than, max_size };
+            if policy.is_empty() {
+                return Err(tr!(
+                    "请至少指定 --keep-last、--older-than 或 --max-size 中的一个",
+                    "give at least one of --keep-last, --older-than or --max-size",
+                ).into());
+            }
+            let tw = load(dry_run)?;
+            let report = tw.prune(&policy)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                print_prune_report(&report);
+            }
+        },
+        Command::Stats => {
+            let tw = load(dry_run)?;
+            let stats = tw.stats()?;
+            if json {
+                print_json(&stats)?;
+            } else {
+                print_stats(&stats);
+            }
+        },
+        Command::History { world, since, until, kind, newest_first, limit } => {
+            let tw = load(dry_run)?;
+            let filter = HistoryFilter { since, until, kinds: kind, world, newest_first, limit };
+            let entries = tw.history(&filter)?;
+            if json {
+                print_json(&entries)?;
+            } else if entries.is_empty() {
+                println!("{}", tr!("没有记录", "nothing recorded"));
+            } else {
+                for entry in &entries {
+                    let world = entry.world.map_or_else(|| "-".to_owned(), |world| world.to_string());
+                    println!("{}  {:<7}  {:>2}  {}", format_unix_time(entry.time), entry.kind, world, entry.message);
+                }
+            }
+        },
+        Command::Verify => {
+            let tw = load(dry_run)?;
+            let report = tw.verify()?;
+            if json {
+                print_json(&report)?;
+            } else {
+                for problem in &report.problems {
+                    println!("! {}  {}", problem.path.display(), problem.problem);
+                    if let Some(moved) = &problem.quarantined {
+                        println!("{}", tr!("  已移至 {}", "  moved to {}", moved.display()));
+                    }
+                }
+                println!("{}", tr!(
+                    "检查了 {} 个备份({})，发现 {} 个问题",
+                    "checked {} backups ({}), {} problems",
+                    report.checked,
+                    format_size(report.bytes),
+                    report.problems.len(),
+                ));
+            }
+            if !report.problems.is_empty() {
+                return Err(tr!("有备份未通过检查", "some backups failed the check").into());
+            }
+        },
+        Command::Pack { older_than } => {
+            let tw = load(dry_run)?;
+            let report = tw.pack(older_than)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                for pack in &report.packs {
+                    println!("+ {}", pack.display());
+                }
+                let summary = if report.dry_run {
+                    tr!("将打包 {} 个备份({})", "would pack {} backups ({})", report.packed, format_size(report.bytes))
+                } else {
+                    tr!("已打包 {} 个备份({})", "packed {} backups ({})", report.packed, format_size(report.bytes))
+                };
+                println!("{}", summary);
+            }
+        },
+        Command::Restore { world, id, at, before_backup, to, overwrite } => {
+            let running = !dry_run && ipc::send(Request::Status).is_ok();
+            let tw = load(dry_run)?;
+            let selector = match (id, at, before_backup) {
+                (Some(id), _, _) => Some(BackupSelector::Id(id)),
+                (_, Some(at), _) => Some(BackupSelector::At(at)),
+                (_, _, Some(label)) => Some(BackupSelector::BeforeLabel(label)),
+                _ => None,
+            };
+            // the id of what was picked, so it is the one checked and restored
+            let id = match selector {
+                Some(BackupSelector::Id(id)) => Some(id),
+                Some(selector) => {
+                    let entry = tw.select_backup(world, &selector)?;
+                    if !json {
+                        println!("{}", tr!("选中的备份: {} {}  {}", "picked backup: {} {}  {}", format_seq(&entry), entry.id, format_utc(entry.utc)));
+                    }
+                    Some(entry.id)
+                },
+                None => None,
+            };
+            if let Some(id) = &id {
+                for difference in tw.compatibility(world, id)? {
+                    eprintln!("{}", tr!("注意: 此备份可能无法读取，{}", "note: this backup may not load, {}", difference));
+                }
+            }
+            if running {
+                // by the running instance, which would back up the save written by another process
+                return print_response(ipc::send(Request::Restore { world, id, to, overwrite })?, json);
+            }
+            let id = match (id, to) {
+                (Some(id), Some(to)) => {
+                    tw.restore_to(world, &id, to, overwrite)?;
+                    id
+                },
+                (Some(id), None) => {
+                    tw.restore(world, &id)?;
+                    id
+                },
+                (None, _) => {
+                    let entry = tw.restore_latest_good(world)?;
+                    if !json {
+                        println!("{}", tr!("已恢复到最新的完好备份 {}", "restored the latest good backup {}", entry.id));
+                        return Ok(());
+                    }
+                    entry.id
+                },
+            };
+            if json {
+                print_json(&json!({ "world": world, "id": id, "restored_to": to.unwrap_or(world), "dry_run": dry_run }))?;
+            } else {
+                println!("{}", tr!("已恢复", "restored"));
+            }
+        },
+        Command::Export { world, id, output } => {
+            let tw = load(dry_run)?;
+            let path = tw.export(world, id.as_deref(), &output)?;
+            if json {
+                print_json(&json!({ "world": world, "path": path }))?;
+            } else {
+                println!("{}", tr!("已导出到 {}", "exported to {}", path.display()));
+            }
+        },
+        Command::Import { file, world, restore_to } => {
+            let tw = load(dry_run)?;
+            let imported = tw.import(&file, world)?;
+            let restored_to = restore_to.filter(|_| !dry_run);
+            if let Some(slot) = restored_to {
+                if ipc::send(Request::Status).is_ok() {
+                    let request = Request::Restore { world: imported.world, id: Some(imported.id.clone()), to: Some(slot), overwrite: false };
+                    let response = ipc::send(request)?;
+                    if !response.ok {
+                        return Err(response.message.into());
+                    }
+                } else {
+                    tw.restore_to(imported.world, &imported.id, slot, false)?;
+                }
+            }
+            if json {
+                print_json(&json!({ "imported": imported, "restored_to": restored_to }))?;
+                return Ok(());
+            }
+            println!("{}", tr!(
+                "已导入为存档位 {} 的备份 {} (原为存档位 {} 于 {} 的备份)",
+                "imported into world {} as backup {} (of world {} from {})",
+                imported.world,
+                imported.id,
+                imported.info.world,
+                format_utc(imported.info.time),
+            ));
+            if let Some(slot) = restored_to {
+                println!("{}", tr!("已恢复到存档位 {}", "restored into world {}", slot));
+            }
+        },
+        Command::Freeze { world, id } => {
+            let tw = load(dry_run)?;
+            let entry = tw.freeze(world, id.as_deref())?;
+            if json {
+                print_json(&json!({ "world": world, "frozen": entry }))?;
+                return Ok(());
+            }
+            println!(
+                "{}",
+                tr!(
+                    "存档位 {} 已冻结为 {} 的备份，游戏写入的存档会被替换(仍会备份)，游戏内需重新读档",
+                    "world {} is frozen to the backup of {}, saves the game writes are replaced (and still backed up), load it again in the game",
+                    world,
+                    format_utc(entry.utc),
+                ),
+            );
+        },
+        Command::Unfreeze { world } => {
+            let tw = load(dry_run)?;
+            tw.unfreeze(world)?;
+            if json {
+                print_json(&json!({ "world": world, "frozen": null }))?;
+            } else {
+                println!("{}", tr!("存档位 {} 已解冻", "world {} is unfrozen", world));
+            }
+        },
+        Command::UndoRestore { world } if dry_run || ipc::send(Request::Status).is_err() => {
+            let tw = load(dry_run)?;
+            let record = tw.undo_restore(world)?;
+            if json {
+                print_json(&json!({ "world": world, "undone": record }))?;
+                return Ok(());
+            }
+            println!(
+                "{}",
+                tr!(
+                    "已撤销 {} 的恢复 (存档位 {} 的备份 {})",
+                    "undid the restore of {} (backup {} of world {})",
+                    format_timestamp(record.time),
+                    record.backup,
+                    record.from_world,
+                ),
+            );
+        },
+        Command::UndoRestore { world } => print_response(ipc::send(Request::UndoRestore { world })?, json)?,
+        Command::Diff { world, a, b } => {
+            let tw = load(dry_run)?;
+            let diff = tw.diff_backups(world, &a, &b)?;
+            if json {
+                print_json(&diff)?;
+            } else {
+                print_diff(&diff);
+            }
+        },
+        #[cfg(feature = "update")]
+        Command::CheckUpdate => match crate::update::check()? {
+            release if json => print_json(&json!({
+                "version": crate::APP_VERSION,
+                "latest": release.as_ref().map(|release| &release.tag_name),
+                "url": release.as_ref().map(|release| &release.html_url),
+            }))?,
+            Some(release) => println!("{}", tr!("新版本 {}: {}", "{} is available: {}", release.tag_name, release.html_url)),
+            None => println!("{}", tr!("已是最新版本 v{}", "v{} is the latest version", crate::APP_VERSION)),
+        },
+        #[cfg(feature = "update")]
+        Command::SelfUpdate => {
+            // the executable of the running instance is the one to replace
+            if ipc::send(Request::Status).is_ok() {
+                return Err(tr!("请先退出正在运行的程序 (`taiwu ctl quit`)", "quit the running instance first (`taiwu ctl quit`)").into());
+            }
+            match crate::update::check()? {
+                Some(release) => {
+                    let exe = crate::update::install(&release)?;
+                    if json {
+                        print_json(&json!({ "version": crate::APP_VERSION, "updated_to": release.tag_name, "exe": exe }))?;
+                    } else {
+                        println!("{}", tr!("已更新到 {}: {}", "updated to {}: {}", release.tag_name, exe.display()));
+                    }
+                },
+                None if json => print_json(&json!({ "version": crate::APP_VERSION, "updated_to": null }))?,
+                None => println!("{}", tr!("已是最新版本 v{}", "v{} is the latest version", crate::APP_VERSION)),
+            }
+        },
+        Command::Profile { name, default } => {
+            let config = Config::load()?;
+            if default {
+                taiwu::save_active_profile(None)?;
+                if json {
+                    print_json(&json!({ "active": null }))?;
+                } else {
+                    println!("{}", tr!("已改回配置文件中的配置档", "back to the profile of the config file"));
+                }
+            } else if let Some(name) = name {
+                if !config.profiles.contains_key(&name) {
+                    return Err(tr!("没有配置档 {}", "there is no profile {}", name).into());
+                }
+                taiwu::save_active_profile(Some(&name))?;
+                if json {
+                    print_json(&json!({ "active": name }))?;
+                } else {
+                    println!("{}", tr!("已切换到配置档 {}，正在运行的程序重新启动后生效", "switched to profile {}, a running instance uses it once restarted", name));
+                }
+            } else if json {
+                let profiles: Vec<_> = config.profiles.iter()
+                    .map(|(name, profile)| json!({ "name": name, "backup_folder": profile.backup_folder.as_deref().unwrap_or(name) }))
+                    .collect();
+                print_json(&json!({ "active": config.profile, "profiles": profiles }))?;
+            } else if config.profiles.is_empty() {
+                println!("{}", tr!("配置文件中没有配置档", "the config has no profiles"));
+            } else {
+                for (name, profile) in &config.profiles {
+                    let active = if config.profile.as_ref() == Some(name) { "*" } else { " " };
+                    let folder = profile.backup_folder.as_deref().unwrap_or(name);
+                    println!("{} {}  {}", active, name, folder);
+                }
+            }
+        },
+        Command::MigrateStore { new_root, keep } => {
+            let mut percent = None;
+            let report = taiwu::migrate_store(&new_root, keep, dry_run, |progress| {
+                let now = progress.copied * 100 / progress.total.max(1);
+                if !json && percent != Some(now) {
+                    percent = Some(now);
+                    eprint!("\r{}", tr!("已复制 {}% ({} / {})", "copied {}% ({} / {})", now, format_size(progress.copied), format_size(progress.total)));
+                }
+            })?;
+            if json {
+                print_json(&report)?;
+                return Ok(());
+            }
+            if percent.is_some() {
+                eprintln!();
+            }
+            if dry_run {
+                println!("{}", tr!(
+                    "将把 {} 个文件({})从 {} 移至 {}",
+                    "would move {} files ({}) from {} to {}",
+                    report.files,
+                    format_size(report.bytes),
+                    report.from.display(),
+                    report.to.display(),
+                ));
+                return Ok(());
+            }
+            println!("{}", tr!(
+                "已校验并复制 {} 个文件({})到 {}，backup_root 已更新",
+                "copied and verified {} files ({}) to {}, backup_root is set to it",
+                report.files,
+                format_size(report.bytes),
+                report.to.display(),
+            ));
+            if report.kept {
+                println!("{}", tr!("旧的备份仍在 {}", "the old backups are still in {}", report.from.display()));
+            }
+            if report.retries_moved > 0 {
+                println!("{}", tr!("{} 个待重试的备份将写入新位置", "{} pending retries go to the new folder", report.retries_moved));
+            }
+        },
+        Command::LogLevel { level } => {
+            let response = ipc::send(Request::SetLogLevel { level })?;
+            if json {
+                print_json(&response)?;
+            } else {
+                println!("{}", response.message);
+            }
+            if !response.ok {
+                return Err(response.message.into());
+            }
+        },
+        Command::Ctl { command } => forward(command, json)?,
+        Command::BackupNow if ipc::send(Request::Status).is_err() => {
+            // nothing else may watch or back up meanwhile
+            let _guard = InstanceGuard::acquire()?;
+            let tw = load(dry_run)?;
+            let report = tw.backup_once();
+            if json {
+                let failed: Vec<_> = report.failed.iter().map(|(path, e)| json!({ "path": path, "error": e.to_string() })).collect();
+                print_json(&json!({ "done": report.done, "failed": failed }))?;
+            } else {
+                println!("{}", tr!("已备份 {} 个文件", "backup done, {} files", report.done.len()));
+            }
+            report.into_result()?;
+        },
+        Command::BackupNow => forward(CtlCommand::BackupNow, json)?,
+        Command::Pause => forward(CtlCommand::Pause, json)?,
+        Command::Resume => forward(CtlCommand::Resume, json)?,
+        Command::Status => forward(CtlCommand::Status, json)?,
+        Command::Quit => forward(CtlCommand::Quit, json)?,
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        },
+        Command::Man { output } => match output {
+            Some(folder) => {
+                std::fs::create_dir_all(&folder)?;
+                clap_mangen::generate_to(Cli::command(), &folder)?;
+                println!("{}", tr!("已写入 {}", "written to {}", folder.display()));
+            },
+            None => clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?,
+        },
+        #[cfg(windows)]
+        Command::Service { action } => {
+            use crate::service;
+
+            match action {
+                ServiceAction::Install => service::install()?,
+                ServiceAction::Uninstall => service::uninstall()?,
+                ServiceAction::Start => service::start()?,
+                ServiceAction::Stop => service::stop()?,
+            }
+            if json {
+                print_json(&json!({ "service": format!("{:?}", action).to_lowercase() }))?;
+            } else {
+                println!("service {:?} done", action);
+            }
+        },
+    }
+    Ok(())
+}
+
+// to the running instance, and print what it answers
+fn forward(command: CtlCommand, json: bool) -> Result<(), Box<dyn Error>> {
+    let response = match ipc::send(command.into()) {
+        Ok(response) => response,
+        Err(e) if matches!(command, CtlCommand::Status) => return print_heartbeat(e.into(), json),
+        Err(e) if ipc::is_not_running(&e) => return Err(tr!("没有正在运行的程序", "no running instance").into()),
+        Err(e) => return Err(e.into()),
+    };
+    print_response(response, json)
+}
+
+fn print_response(response: Response, json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        print_json(&response)?;
+    } else {
+        println!("{}", response.message);
+    }
+    if let Some(status) = response.status.filter(|_| !json) {
+        let state = &status.state;
+        println!("{}", tr!("版本: {}", "version: {}", status.version));
+        println!("{}", tr!("监控中: {}", "watching: {}", state.watching));
+        println!("{}", tr!("轮询检查: {}", "polling: {}", state.polling));
+        println!("{}", tr!("已暂停: {}", "paused: {}", state.paused));
+        println!("{}", tr!("演练模式: {}", "dry run: {}", status.dry_run));
+        println!("{}", tr!("日志级别: {}", "log level: {}", status.log_level));
+        println!("{}", tr!("便携模式: {}", "portable: {}", status.portable));
+        println!("{}", tr!("游戏运行中: {}", "game running: {}", state.game_running));
+        if let Some(profile) = &status.profile {
+            println!("{}", tr!("配置档: {}", "profile: {}", profile));
+        }
+        println!("{}", tr!("游戏目录: {}", "game root: {}", status.game_root.display()));
+        println!("{}", tr!("备份目录: {}", "backup root: {}", status.backup_root.display()));
+        if let Some(time) = state.last_event {
+            println!("{}", tr!("最近的存档变化: {}", "last change: {}", format_unix_time(time)));
+        }
+        for last in &state.last_backups {
+            println!("{}", tr!("存档位 {} 最新备份: {}", "world {} last backup: {}", last.world, format_unix_time(last.time)));
+        }
+        println!("{}", tr!("排队中: {}", "queued: {}", state.queued_jobs));
+        println!("{}", tr!("待重试: {}", "pending retries: {}", state.pending_retries));
+        for copy in &state.copying {
+            let percent = copy.copied * 100 / copy.total.max(1);
+            println!("{}", tr!("正在备份: {} {}%", "copying: {} {}%", copy.src.display(), percent));
+        }
+        if let Some(error) = &state.last_error {
+            println!("{}", tr!("上次错误 ({}): {}", "last error ({}): {}", format_unix_time(error.time), error.message));
+        }
+    }
+    if !response.ok {
+        return Err(response.message.into());
+    }
+    Ok(())
+}
+
+// the config file, with the command line on top
+pub fn load_config(dry_run: bool) -> taiwu::Result<Config> {
+    let mut config = Config::load()?;
+    config.dry_run |= dry_run;
+    Ok(config)
+}
+
+fn load(dry_run: bool) -> taiwu::Result<Taiwu> {
+    // what would be done is only logged, so show the log
+    if dry_run {
+        let _ = WriteLogger::init(LevelFilter::Info, simplelog::Config::default(), std::io::stderr());
+    }
+    Taiwu::with_config(&load_config(dry_run)?)
+}
+
+fn parse_time(text: &str) -> Result<chrono::NaiveDateTime, String> {
+    BackupQuery::parse_time(text).ok_or_else(|| format!("invalid time `{}`, e.g. `2022-10-01 12:00:00`", text))
+}
+
+fn parse_at(text: &str) -> Result<chrono::NaiveDateTime, String> {
+    BackupSelector::parse_at(text).ok_or_else(|| format!("invalid time `{}`, e.g. `yesterday 21:00`, `21:00` or `2h ago`", text))
+}
+
+fn parse_world(text: &str) -> Result<usize, String> {
+    taiwu::parse_world(text).ok_or_else(|| format!("invalid world `{}`, e.g. `2` or `world_2`", text))
+}
+
+fn parse_age(text: &str) -> Result<std::time::Duration, String> {
+    taiwu::parse_age(text).ok_or_else(|| format!("invalid age `{}`, e.g. `30d` or `12h`", text))
+}
+
+fn parse_size(text: &str) -> Result<u64, String> {
+    taiwu::parse_size(text).ok_or_else(|| format!("invalid size `{}`, e.g. `2GB` or `500MB`", text))
+}
+
+fn print_prune_report(report: &PruneReport) {
+    for entry in &report.deleted {
+        println!("- {}  {}  {}", entry.path.display(), format_utc(entry.utc), format_size(entry.size));
+    }
+    let summary = if report.dry_run {
+        tr!("将删除 {} 个备份，释放 {}，保留 {} 个", "would remove {} backups, freeing {}, {} kept", report.deleted.len(), format_size(report.freed), report.retained.len())
+    } else {
+        tr!("已删除 {} 个备份，释放 {}，保留 {} 个", "removed {} backups, freed {}, {} kept", report.deleted.len(), format_size(report.freed), report.retained.len())
+    };
+    println!("{}", summary);
+}
+
+fn print_backups(list: &[(usize, Vec<BackupEntry>)]) {
+    for (world, backups) in list {
+        if backups.is_empty() {
+            continue;
+        }
+        println!("{}", tr!("存档位 {}:", "world {}:", world));
+        for entry in backups {
+            print!("  {:>5}  {}  {}  {:>10}", format_seq(entry), entry.id, format_utc(entry.utc), format_size(entry.size));
+            if let Some(hash) = &entry.hash {
+                print!("  {}", hash);
+            }
+            if let Some(game) = format_game(entry) {
+                print!("  [{}]", game);
+            }
+            if let Some(label) = &entry.label {
+                print!("  {}", label);
+            }
+            println!();
+        }
+    }
+}
+
+// the in-game date and the character of the save, `3年7月 太吾`, none if the game profile read neither
+pub fn format_game(entry: &BackupEntry) -> Option<String> {
+    let date = entry.game_date().map(|(year, month)| match month {
+        0 => tr!("{}年", "year {}", year),
+        month => tr!("{}年{}月", "year {} month {}", year, month),
+    });
+    let text: Vec<_> = date.into_iter().chain(entry.character().map(str::to_owned)).collect();
+    (!text.is_empty()).then(|| text.join(" "))
+}
+
+fn print_stats(stats: &Stats) {
+    for world in &stats.worlds {
+        print!("{}", tr!("存档位 {}: {} 个备份, {}", "world {}: {} backups, {}", world.world, world.count, format_size(world.size)));
+        if let (Some(oldest), Some(newest)) = (world.oldest, world.newest) {
+            print!(", {} ~ {}", format_utc(oldest), format_utc(newest));
+        }
+        println!();
+    }
+    println!("{}", tr!("总计: {} 个备份, {}", "total: {} backups, {}", stats.count, format_size(stats.size)));
+    println!("{}", tr!("重复: {}", "duplicates: {}", format_size(stats.duplicate_size)));
+    if let Some(times) = &stats.copy_times {
+        println!("{}", tr!(
+            "复制耗时({} 个备份): 中位 {} ms, p90 {} ms, p99 {} ms, 最长 {} ms",
+            "copy time ({} backups): median {} ms, p90 {} ms, p99 {} ms, max {} ms",
+            times.count,
+            times.p50_ms,
+            times.p90_ms,
+            times.p99_ms,
+            times.max_ms,
+        ));
+        println!("{}", tr!(
+            "复制速度: 中位 {}/s, 最慢的 10% 不超过 {}/s",
+            "copy speed: median {}/s, the slowest 10% at most {}/s",
+            format_size(times.p50_bytes_per_sec),
+            format_size(times.p10_bytes_per_sec),
+        ));
+    }
+}
+
+fn print_diff(diff: &BackupDiff) {
+    for (name, entry, hash) in [("a", &diff.a, &diff.a_hash), ("b", &diff.b, &diff.b_hash)] {
+        println!("{}: {}  {}  {}  {}", name, entry.id, format_utc(entry.utc), format_size(entry.size), hash);
+    }
+    if diff.identical {
+        println!("{}", tr!("内容相同", "identical"));
+        return;
+    }
+    println!("{}", tr!("大小: {:+} 字节", "size: {:+} bytes", diff.size_delta));
+    println!("{}", tr!("不同的字节: {}", "changed bytes: {}", diff.changed_bytes));
+    if let Some(offset) = diff.first_difference {
+        println!("{}", tr!("首个不同处: {:#x}", "first difference at: {:#x}", offset));
+    }
+}
+
+// pretty printed, for `--json`
+fn print_json(value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+pub fn format_size(size: u64) -> String {
+    match size {
+        0..=1023 => format!("{} B", size),
+        1024..=1048575 => format!("{:.1} KB", size as f64 / 1024.0),
+        _ => format!("{:.1} MB", size as f64 / 1048576.0),
+    }
+}
+
+// the control channel did not answer, the last `status.json` tells whether it still runs
+fn print_heartbeat(error: Box<dyn Error>, json: bool) -> Result<(), Box<dyn Error>> {
+    let Some(heartbeat) = Heartbeat::read()? else {
+        return Err(error);
+    };
+    if json {
+        print_json(&json!({ "error": error.to_string(), "age": heartbeat.age(), "alive": heartbeat.is_alive(), "heartbeat": heartbeat }))?;
+        return if heartbeat.is_alive() { Ok(()) } else { Err(tr!("程序没有在运行", "it is not running").into()) };
+    }
+    println!("{}", tr!("无法连接到运行中的程序: {}", "could not reach the running instance: {}", error));
+    println!("{}", tr!("以下来自 {} 秒前写入的 status.json", "from status.json written {} seconds ago", heartbeat.age()));
+    println!("{}", tr!("版本: {}", "version: {}", heartbeat.version));
+    println!("{}", tr!("进程: {}", "pid: {}", heartbeat.pid));
+    println!("{}", tr!("已退出: {}", "stopped: {}", heartbeat.stopped));
+    println!("{}", tr!("监控中: {}", "watching: {}", heartbeat.watching));
+    println!("{}", tr!("轮询检查: {}", "polling: {}", heartbeat.polling));
+    println!("{}", tr!("已暂停: {}", "paused: {}", heartbeat.paused));
+    println!("{}", tr!("游戏运行中: {}", "game running: {}", heartbeat.game_running));
+    if let Some(time) = heartbeat.last_event {
+        println!("{}", tr!("最近的存档变化: {}", "last change: {}", format_unix_time(time)));
+    }
+    for world in &heartbeat.worlds {
+        println!("{}", tr!("存档位 {} 最新备份: {}", "world {} last backup: {}", world.world, format_unix_time(world.last_backup)));
+    }
+    if let Some(error) = &heartbeat.last_error {
+        println!("{}", tr!("上次错误 ({}): {}", "last error ({}): {}", format_unix_time(error.time), error.message));
+    }
+    if !heartbeat.is_alive() {
+        return Err(tr!("程序没有在运行", "it is not running").into());
+    }
+    Ok(())
+}
+
+fn format_unix_time(secs: i64) -> String {
+    use chrono::TimeZone;
+
+    match chrono::offset::Local.timestamp_opt(secs, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => secs.to_string(),
+    }
+}
+
+// nanoseconds since the unix epoch, like restore records
+pub fn format_timestamp(timestamp: i64) -> String {
+    format_unix_time(timestamp.div_euclid(1_000_000_000))
+}
+
+// `#143`, blank for backups not numbered yet
+fn format_seq(entry: &BackupEntry) -> String {
+    entry.seq.map(|seq| format!("#{}", seq)).unwrap_or_default()
+}
+
+// shown in local time
+pub fn format_utc(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.with_timezone(&chrono::offset::Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// the exe has no console of its own (windows subsystem), print into the one it was started from
+#[cfg(windows)]
+pub fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn attach_console() {}
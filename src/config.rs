@@ -0,0 +1,672 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use log::{debug, info, LevelFilter};
+use serde::{Deserialize, Serialize};
+
+use crate::notifier::NotificationKind;
+use crate::prune::{parse_age, parse_size, PrunePolicy};
+use crate::{Result, TaiwuError};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+// `TAIWU_BACKUP_<KEY>` for any top level key of the config file, and `TAIWU_BACKUP_CONFIG` for
+// the file itself
+const ENV_PREFIX: &str = "TAIWU_BACKUP_";
+// the profile switched to last (tray or `taiwu profile`), over `profile` of the config file
+const ACTIVE_PROFILE_FILE_NAME: &str = "profile.txt";
+
+static OVERRIDES: Mutex<ConfigOverrides> = Mutex::new(ConfigOverrides {
+    config_file: None,
+    profile: None,
+    game_root: None,
+    backup_root: None,
+    log_level: None,
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // detected automatically (steam) if not set
+    pub game_root: Option<PathBuf>,
+    // other save folders, besides the one of the game and those found in Steam userdata
+    pub save_roots: Vec<PathBuf>,
+    // the id (folder name) in Steam userdata whose saves are used, of every account if not set
+    pub steam_user: Option<String>,
+    // `%LOCALAPPDATA%\TaiwuBackup\BackupData` if not set
+    pub backup_root: Option<PathBuf>,
+    // extra stores (e.g. a NAS share), failures on them are only warnings
+    pub mirror_roots: Vec<PathBuf>,
+    // retries when the save file is still locked by the game, the delay doubles every time
+    pub lock_retry_attempts: u32,
+    pub lock_retry_delay_ms: u64,
+    // copy a still locked save from a volume shadow copy (`vss` feature, administrator)
+    pub vss_fallback: bool,
+    // serve the HTTP API on `127.0.0.1:<port>` (`http` feature), disabled if not set
+    pub http_port: Option<u16>,
+    // e.g. `Ctrl+Alt+B`, backs up all worlds at once from anywhere (tray app only)
+    pub hotkey: Option<String>,
+    // only watch the saves while the game is running, e.g. restores made meanwhile are not backed up
+    pub only_while_game_running: bool,
+    // MB, the oldest backups without a label are removed when the backup root grows over it
+    pub store_quota_mb: Option<u64>,
+    // MB to keep free on the volume of a store, backups to it are skipped otherwise (0 disables)
+    pub min_free_space_mb: u64,
+    // only log what would be copied, pruned or restored, nothing is written
+    pub dry_run: bool,
+    // flush every backup to the disk before it counts as done, so it survives a power loss
+    pub durable: bool,
+    // copy the backups (and compress packs) at the lowest CPU and disk priority, so the game does not stutter
+    pub background_priority: bool,
+    // `files`, each backup a copy of the save, or `chunked`, split into chunks kept once in the
+    // store, see `chunks.rs`; backups of the other format are still read
+    pub store_format: StoreFormat,
+    // ask GitHub for a newer release on start and then daily (`update` feature, tray app only)
+    pub check_updates: bool,
+    // `zh-CN` or `en-US` for the tray, notifications and command line, the system locale if not set
+    pub language: Option<String>,
+    // `error`, `warn`, `info`, `debug` or `trace`, can be changed while running (`taiwu log-level`)
+    pub log_level: LogLevel,
+    // `text`, or `json` for one object per line with fields like world, bytes and duration
+    pub log_format: LogFormat,
+    // days the log files of the tray app and the service are kept, 0 keeps them forever
+    pub log_retention_days: u32,
+    // copies of the save replaced by a restore kept per world, in `PreRestore` of the backup root
+    pub pre_restore_keep: usize,
+    // move the backups of a world deleted in the game to `Archive` in the stores
+    pub archive_removed_worlds: bool,
+    // globs relative to the save root of the files watched and backed up there,
+    // a file is backed up if it matches any include but no exclude
+    pub save_include: Vec<String>,
+    pub save_exclude: Vec<String>,
+    // globs relative to the game root of more files to back up, e.g. `Mods/**/*.json`
+    pub extra_files: Vec<String>,
+    // `auto`, `native` or `poll`, auto polls once the native watcher is seen missing changes
+    pub watcher: WatcherBackend,
+    // seconds between two polls
+    pub poll_interval_secs: u64,
+    // minutes between two backups of the changed saves while watching, even without events (0 disables)
+    pub snapshot_interval_mins: u64,
+    // POST a JSON message there when a backup is done, skipped or failed (`webhook` feature),
+    // the same as a `webhook` in `notifiers`
+    pub webhook_url: Option<String>,
+    // `auto` (from the url), `discord`, `slack` or `generic`
+    pub webhook_format: WebhookFormat,
+    // where notifications go and which of them, only toasts (tray app only) if empty
+    pub notifiers: Vec<NotifierConfig>,
+    // `off`, `errors-only` or `all` of the toasts when `notifiers` is empty, none shows the game
+    // starting and stopping, warnings and errors
+    pub notifications: Option<NotificationMode>,
+    // seconds the toasts of more backups are held back after one, then shown as one summary (0 shows every one)
+    pub notification_cooldown_secs: u64,
+    // percent a save may shrink compared to the recent backups of its world before it is alerted,
+    // e.g. a new game started over the slot (0 disables)
+    pub shrink_alert_percent: u64,
+    // seconds between two writes of `status.json` in the appdata folder, 0 disables it
+    pub heartbeat_interval_secs: u64,
+    // cron expressions (e.g. `0 3 * * *`) of the integrity scan and of pruning while watching,
+    // put off while the game is running
+    pub verify: Option<String>,
+    pub prune: Option<String>,
+    // what the scheduled pruning removes, like the options of `taiwu prune`
+    pub prune_keep_last: Option<usize>,
+    // e.g. `30d`
+    pub prune_older_than: Option<String>,
+    // e.g. `2GB`
+    pub prune_max_size: Option<String>,
+    // cron expression of packing the backups older than `pack_after_days` (`pack` feature)
+    pub pack: Option<String>,
+    pub pack_after_days: u64,
+    // one of `profiles`, none uses the settings above as they are
+    pub profile: Option<String>,
+    // for each user of a shared computer, their backups never mix
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    // by world number (`[worlds.1]`), the settings of a world that differ
+    pub worlds: BTreeMap<String, WorldConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    // not backed up at all when off, while watching or not, its backups are kept
+    pub enabled: bool,
+    // over `prune_keep_last`, `prune_older_than` and `prune_max_size` (and the options of
+    // `taiwu prune`) for this world, `max_size` is then for it alone
+    pub keep_last: Option<usize>,
+    pub older_than: Option<String>,
+    pub max_size: Option<String>,
+    // more stores for its backups only, like `mirror_roots`
+    pub mirror_roots: Vec<PathBuf>,
+}
+
+impl Default for WorldConfig {
+    fn default() -> WorldConfig {
+        WorldConfig { enabled: true, keep_last: None, older_than: None, max_size: None, mirror_roots: Vec::new() }
+    }
+}
+
+// `[worlds.N]` checked and parsed
+#[derive(Debug, Clone)]
+pub struct WorldSettings {
+    pub enabled: bool,
+    // only what is set for the world
+    pub prune: PrunePolicy,
+    pub mirror_roots: Vec<PathBuf>,
+}
+
+// given on the command line (or to `TaiwuBuilder`), over the config file
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    // instead of `config.toml` in the appdata folder
+    pub config_file: Option<PathBuf>,
+    // also over the one switched to last
+    pub profile: Option<String>,
+    pub game_root: Option<PathBuf>,
+    pub backup_root: Option<PathBuf>,
+    pub log_level: Option<LogLevel>,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(profile) = &self.profile {
+            config.profile = Some(profile.clone());
+        }
+        if let Some(game_root) = &self.game_root {
+            config.game_root = Some(game_root.clone());
+            // not the one of the profile either
+            if let Some(profile) = config.profile.as_ref().and_then(|name| config.profiles.get_mut(name)) {
+                profile.game_root = None;
+            }
+        }
+        if let Some(backup_root) = &self.backup_root {
+            config.backup_root = Some(backup_root.clone());
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+    }
+
+    fn config_file(&self) -> Result<PathBuf> {
+        if let Some(path) = std::env::var_os(format!("{}CONFIG", ENV_PREFIX)) {
+            return Ok(PathBuf::from(path));
+        }
+        match &self.config_file {
+            Some(path) => Ok(path.clone()),
+            None => config_file_default(),
+        }
+    }
+}
+
+// what differs for a profile, the rest is shared
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub game_root: Option<PathBuf>,
+    pub save_roots: Vec<PathBuf>,
+    pub steam_user: Option<String>,
+    // in the backup root and each mirror, the name of the profile if not set
+    pub backup_folder: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifierConfig {
+    #[serde(rename = "type")]
+    pub backend: NotifierBackend,
+    // of a webhook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    // e.g. `backup-complete`, `backup-skipped` or `error`, all of them if empty
+    #[serde(default)]
+    pub events: Vec<NotificationKind>,
+    // of an `email` notifier
+    #[serde(flatten)]
+    pub email: EmailConfig,
+}
+
+// where an `email` notifier sends, and when (`email` feature, see `email.rs`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_host: Option<String>,
+    // 465 with `tls`, 587 with `starttls` and 25 with `none` if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_port: Option<u16>,
+    pub smtp_security: SmtpSecurity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_password: Option<String>,
+    // the environment variable with the password, to keep it out of the config file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_password_env: Option<String>,
+    // `smtp_user` if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub to: Vec<String>,
+    // backups failed in a row before mailing, 3 if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failures: Option<u32>,
+    // hours the game ran without a backup before mailing, 6 if not set (0 never mails for it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_hours: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,
+    // implicit TLS from the start, usually port 465
+    Tls,
+    // plain text, only for a relay on the same machine or network
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationMode {
+    Off,
+    // errors and skipped backups
+    ErrorsOnly,
+    // every backup too
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifierBackend {
+    Toast,
+    Webhook,
+    Log,
+    Email,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatcherBackend {
+    #[default]
+    Auto,
+    Native,
+    Poll,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreFormat {
+    #[default]
+    Files,
+    Chunked,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    #[default]
+    Auto,
+    Generic,
+    Discord,
+    Slack,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace];
+
+    // the loggers let everything through, only the max level of `log` filters
+    pub fn apply(self) {
+        log::set_max_level(self.into());
+        info!("Log level is {}", self);
+    }
+
+    pub fn current() -> LogLevel {
+        match log::max_level() {
+            LevelFilter::Off | LevelFilter::Error => LogLevel::Error,
+            LevelFilter::Warn => LogLevel::Warn,
+            LevelFilter::Info => LogLevel::Info,
+            LevelFilter::Debug => LogLevel::Debug,
+            LevelFilter::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> LevelFilter {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(LevelFilter::from(*self).as_str().to_lowercase().as_str())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<LogLevel, String> {
+        LogLevel::ALL.into_iter()
+            .find(|level| level.to_string().eq_ignore_ascii_case(text))
+            .ok_or_else(|| format!("unknown log level `{}`", text))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            game_root: None,
+            save_roots: Vec::new(),
+            steam_user: None,
+            backup_root: None,
+            mirror_roots: Vec::new(),
+            lock_retry_attempts: 5,
+            lock_retry_delay_ms: 200,
+            vss_fallback: false,
+            http_port: None,
+            hotkey: None,
+            only_while_game_running: false,
+            store_quota_mb: None,
+            min_free_space_mb: 100,
+            dry_run: false,
+            durable: false,
+            background_priority: true,
+            store_format: StoreFormat::Files,
+            check_updates: false,
+            language: None,
+            log_level: LogLevel::Info,
+            log_format: LogFormat::Text,
+            log_retention_days: 14,
+            pre_restore_keep: 10,
+            archive_removed_worlds: false,
+            save_include: vec![crate::game().save_include()],
+            save_exclude: Vec::new(),
+            extra_files: Vec::new(),
+            watcher: WatcherBackend::Auto,
+            poll_interval_secs: 5,
+            snapshot_interval_mins: 0,
+            webhook_url: None,
+            webhook_format: WebhookFormat::Auto,
+            notifiers: Vec::new(),
+            notifications: None,
+            notification_cooldown_secs: 60,
+            shrink_alert_percent: 50,
+            heartbeat_interval_secs: 30,
+            verify: None,
+            prune: None,
+            prune_keep_last: None,
+            prune_older_than: None,
+            prune_max_size: None,
+            pack: None,
+            pack_after_days: 30,
+            profile: None,
+            profiles: BTreeMap::new(),
+            worlds: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        Config::load_with(&OVERRIDES.lock().unwrap())
+    }
+
+    pub fn load_with(overrides: &ConfigOverrides) -> Result<Config> {
+        let mut config = Config::load_from(overrides.config_file()?)?;
+        if let Some(profile) = active_profile() {
+            config.profile = Some(profile);
+        }
+        overrides.apply(&mut config);
+        apply_env(config)
+    }
+
+    // with the settings of the profile in use, the stores are its folders in them
+    pub fn profile_applied(&self) -> Result<Config> {
+        let Some(name) = &self.profile else {
+            return Ok(self.clone());
+        };
+        let profile = self.profiles.get(name).ok_or_else(|| TaiwuError::ConfigValueInvalid {
+            key: "profile".to_owned(),
+            message: format!("there is no profile `{}`", name),
+        })?;
+        let folder = profile.backup_folder.as_deref().unwrap_or(name);
+        let backup_root = match &self.backup_root {
+            Some(root) => root.clone(),
+            None => crate::get_backup_root_default()?,
+        };
+
+        let mut config = self.clone();
+        if profile.game_root.is_some() {
+            config.game_root = profile.game_root.clone();
+        }
+        config.save_roots.extend(profile.save_roots.iter().cloned());
+        if profile.steam_user.is_some() {
+            config.steam_user = profile.steam_user.clone();
+        }
+        config.backup_root = Some(backup_root.join(folder));
+        config.mirror_roots = self.mirror_roots.iter().map(|root| root.join(folder)).collect();
+        Ok(config)
+    }
+
+    // the configured ones, or the toasts the tray always showed, and `webhook_url`
+    pub fn notifiers(&self) -> Vec<NotifierConfig> {
+        let mut notifiers = self.notifiers.clone();
+        let events = match self.notifications {
+            None => Some(vec![
+                NotificationKind::GameStarted,
+                NotificationKind::GameStopped,
+                NotificationKind::WatcherRestarted,
+                NotificationKind::BackupSkipped,
+                NotificationKind::SaveShrank,
+                NotificationKind::Error,
+            ]),
+            Some(NotificationMode::Off) => None,
+            Some(NotificationMode::ErrorsOnly) => Some(vec![NotificationKind::BackupSkipped, NotificationKind::Error]),
+            Some(NotificationMode::All) => Some(Vec::new()),
+        };
+        if let Some(events) = events.filter(|_| notifiers.is_empty()) {
+            notifiers.push(NotifierConfig {
+                backend: NotifierBackend::Toast,
+                url: None,
+                format: WebhookFormat::Auto,
+                events,
+                email: EmailConfig::default(),
+            });
+        }
+        if let Some(url) = &self.webhook_url {
+            notifiers.push(NotifierConfig {
+                backend: NotifierBackend::Webhook,
+                url: Some(url.clone()),
+                format: self.webhook_format,
+                events: vec![NotificationKind::BackupComplete, NotificationKind::BackupSkipped, NotificationKind::Error],
+                email: EmailConfig::default(),
+            });
+        }
+        notifiers
+    }
+
+    // of the scheduled pruning
+    pub fn prune_policy(&self) -> Result<PrunePolicy> {
+        let (older_than, max_size) = (self.prune_older_than.as_deref(), self.prune_max_size.as_deref());
+        prune_policy("prune_", self.prune_keep_last, older_than, max_size)
+    }
+
+    // by world number, `[worlds.1]` or `[worlds.world_1]`
+    pub fn world_settings(&self) -> Result<BTreeMap<usize, WorldSettings>> {
+        let mut settings = BTreeMap::new();
+        for (key, world) in &self.worlds {
+            let number = key.strip_prefix(crate::game().world_prefix()).unwrap_or(key).parse().map_err(|_| TaiwuError::ConfigValueInvalid {
+                key: format!("worlds.{}", key),
+                message: "not a world number".to_owned(),
+            })?;
+            let prefix = format!("worlds.{}.", key);
+            let prune = prune_policy(&prefix, world.keep_last, world.older_than.as_deref(), world.max_size.as_deref())?;
+            settings.insert(number, WorldSettings { enabled: world.enabled, prune, mirror_roots: world.mirror_roots.clone() });
+        }
+        Ok(settings)
+    }
+
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            debug!("config file `{}` not found, use default", path.display());
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|source| TaiwuError::ConfigReadFailed { path: path.to_owned(), source })?;
+        let config = toml::from_str(&text).map_err(TaiwuError::ConfigInvalid)?;
+        info!("Loaded config `{}`", path.display());
+        Ok(config)
+    }
+}
+
+// `prefix` of the keys, for the error
+fn prune_policy(prefix: &str, keep_last: Option<usize>, older_than: Option<&str>, max_size: Option<&str>) -> Result<PrunePolicy> {
+    let invalid = |key: &str, text: &str| TaiwuError::ConfigValueInvalid {
+        key: format!("{}{}", prefix, key),
+        message: format!("`{}` is not valid", text),
+    };
+    let older_than = match older_than {
+        Some(text) => Some(parse_age(text).ok_or_else(|| invalid("older_than", text))?),
+        None => None,
+    };
+    let max_size = match max_size {
+        Some(text) => Some(parse_size(text).ok_or_else(|| invalid("max_size", text))?),
+        None => None,
+    };
+    Ok(PrunePolicy { keep_last, older_than, max_size })
+}
+
+// the environment goes over the command line, e.g. for a service set up with both
+fn apply_env(config: Config) -> Result<Config> {
+    let vars: Vec<_> = std::env::vars()
+        .filter_map(|(name, value)| Some((name.strip_prefix(ENV_PREFIX)?.to_lowercase(), value)))
+        .filter(|(key, _)| key != "config")
+        .collect();
+    if vars.is_empty() {
+        return Ok(config);
+    }
+    let mut table = match toml::Value::try_from(&config).map_err(|e| TaiwuError::EnvInvalid(e.to_string()))? {
+        toml::Value::Table(table) => table,
+        _ => unreachable!(),
+    };
+    for (key, value) in vars {
+        debug!("`{}` set by the environment", key);
+        table.insert(key, env_value(&value));
+    }
+    toml::Value::Table(table).try_into().map_err(|e: toml::de::Error| TaiwuError::EnvInvalid(e.to_string()))
+}
+
+// as it would be written in the config file (`30`, `true`, `['a', 'b']`), or the text as it is,
+// so strings need no quotes
+fn env_value(text: &str) -> toml::Value {
+    toml::from_str::<toml::value::Table>(&format!("value = {}", text))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(text.to_owned()))
+}
+
+// `key = value` at the top of the config file, which is created if there is none, the comments
+// and the other settings in it are kept
+#[cfg(feature = "config_edit")]
+pub fn set_config_value(key: &str, value: &str) -> Result<()> {
+    let path = OVERRIDES.lock().unwrap().config_file()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(source) => return Err(TaiwuError::ConfigReadFailed { path, source }),
+    };
+    let mut document: toml_edit::DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| TaiwuError::ConfigValueInvalid {
+        key: key.to_owned(),
+        message: e.to_string(),
+    })?;
+    document[key] = toml_edit::value(value);
+    fs::create_dir_all(path.parent().unwrap())?;
+    // all of it or nothing, e.g. when `backup_root` is set after the store was moved
+    let temp = crate::store::temp_path(&path);
+    fs::write(&temp, document.to_string())?;
+    fs::rename(&temp, &path)?;
+    info!("Set `{}` in `{}`", key, path.display());
+    Ok(())
+}
+
+// what sets `key` over the config file, an environment variable or `--backup-root`, so that
+// `set_config_value` alone would not change it
+#[cfg(feature = "config_edit")]
+pub(crate) fn config_override(key: &str) -> Option<String> {
+    let env = std::env::vars().map(|(name, _)| name).find(|name| {
+        name.strip_prefix(ENV_PREFIX).is_some_and(|rest| rest.to_lowercase() == key)
+    });
+    if let Some(name) = env {
+        return Some(format!("`{}`", name));
+    }
+    let overrides = OVERRIDES.lock().unwrap();
+    let overridden = match key {
+        "profile" => overrides.profile.is_some(),
+        "game_root" => overrides.game_root.is_some(),
+        "backup_root" => overrides.backup_root.is_some(),
+        "log_level" => overrides.log_level.is_some(),
+        _ => false,
+    };
+    overridden.then(|| format!("`--{}`", key.replace('_', "-")))
+}
+
+// for every `Config::load` from now on
+pub fn set_config_overrides(overrides: ConfigOverrides) {
+    *OVERRIDES.lock().unwrap() = overrides;
+}
+
+fn active_profile() -> Option<String> {
+    let path = crate::get_appdata_root_default().ok()?.join(ACTIVE_PROFILE_FILE_NAME);
+    let name = fs::read_to_string(path).ok()?.trim().to_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+// used from the next start on, none goes back to `profile` of the config file
+pub fn save_active_profile(profile: Option<&str>) -> Result<()> {
+    let path = crate::get_appdata_root_default()?.join(ACTIVE_PROFILE_FILE_NAME);
+    match profile {
+        Some(name) => fs::write(path, name)?,
+        None => match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {},
+        },
+    }
+    info!("Switched to profile {}", profile.unwrap_or("(none)"));
+    Ok(())
+}
+
+pub fn config_file_default() -> Result<PathBuf> {
+    let appdata = crate::get_appdata_root_default()?;
+    Ok(appdata.join(CONFIG_FILE_NAME))
+}
@@ -0,0 +1,110 @@
+// Cron expressions of the maintenance schedules, e.g. `verify = "0 3 * * *"`: minute, hour,
+// day of month, month and day of week (0 or 7 is Sunday), each `*`, a number, a range `1-5`,
+// a step `*/15` or `1-30/2`, or a list of those `0,30`.
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // like cron, when both days are restricted either of them matching is enough; a field
+    // starting with `*` (`*/2` too) is not restricted
+    any_day: bool,
+}
+
+impl Schedule {
+    pub fn parse(text: &str) -> Result<Schedule, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("`{}` needs 5 fields: minute hour day month weekday", text));
+        };
+        let mut weekdays_bits = parse_field(weekdays, 0, 7)?;
+        // 7 is the other Sunday
+        if weekdays_bits & (1 << 7) != 0 {
+            weekdays_bits |= 1;
+        }
+        Ok(Schedule {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekdays_bits,
+            any_day: !days.starts_with('*') && !weekdays.starts_with('*'),
+        })
+    }
+
+    // at the minute of `time`
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        let has = |bits: u64, value: u32| bits & (1 << value) != 0;
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        let day = if self.any_day { day || weekday } else { day && weekday };
+        has(self.minutes, time.minute()) && has(self.hours, time.hour()) && has(self.months, time.month()) && day
+    }
+}
+
+// the values as bits
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&step| step > 0).ok_or_else(|| invalid(part))?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start, part)?, number(end, part)?),
+                // `5/10` is from 5 to the end
+                None if step > 1 => (number(range, part)?, max),
+                None => {
+                    let value = number(range, part)?;
+                    (value, value)
+                },
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("`{}` is out of {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn number(text: &str, part: &str) -> Result<u32, String> {
+    text.parse().map_err(|_| invalid(part))
+}
+
+fn invalid(part: &str) -> String {
+    format!("`{}` is not a number, range or step", part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn days_are_either_only_when_both_are_restricted() {
+        // the 1st and 15th, and every Monday
+        let schedule = Schedule::parse("0 3 1,15 * 1").unwrap();
+        assert!(schedule.matches(at("2022-10-01 03:00")));
+        assert!(schedule.matches(at("2022-10-03 03:00")));
+        assert!(!schedule.matches(at("2022-10-04 03:00")));
+
+        // Mondays of odd days only, a step of `*` is not a restriction
+        let schedule = Schedule::parse("0 3 */2 * 1").unwrap();
+        assert!(schedule.matches(at("2022-10-03 03:00")));
+        assert!(!schedule.matches(at("2022-10-10 03:00")));
+        assert!(!schedule.matches(at("2022-10-05 03:00")));
+    }
+}
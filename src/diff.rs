@@ -0,0 +1,49 @@
+// Compare two backups of a world byte by byte, the save format itself is not parsed (yet).
+
+use std::io;
+
+use serde::Serialize;
+
+use crate::store::{self, BackupEntry};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupDiff {
+    pub a: BackupEntry,
+    pub b: BackupEntry,
+    pub identical: bool,
+    // size of `b` minus size of `a`
+    pub size_delta: i64,
+    pub a_hash: String,
+    pub b_hash: String,
+    // differing bytes within the length of the shorter one
+    pub changed_bytes: u64,
+    pub first_difference: Option<u64>,
+}
+
+pub fn diff(a: BackupEntry, b: BackupEntry) -> io::Result<BackupDiff> {
+    let a_bytes = store::read_backup(&a)?;
+    let b_bytes = store::read_backup(&b)?;
+
+    let mut changed_bytes = 0;
+    let mut first_difference = None;
+    for (i, (x, y)) in a_bytes.iter().zip(&b_bytes).enumerate() {
+        if x != y {
+            changed_bytes += 1;
+            first_difference.get_or_insert(i as u64);
+        }
+    }
+    if first_difference.is_none() && a_bytes.len() != b_bytes.len() {
+        first_difference = Some(a_bytes.len().min(b_bytes.len()) as u64);
+    }
+
+    Ok(BackupDiff {
+        identical: a_bytes == b_bytes,
+        size_delta: b_bytes.len() as i64 - a_bytes.len() as i64,
+        a_hash: store::sha256_bytes(&a_bytes),
+        b_hash: store::sha256_bytes(&b_bytes),
+        changed_bytes,
+        first_difference,
+        a,
+        b,
+    })
+}
@@ -0,0 +1,181 @@
+// Mail over SMTP when the backups need someone to look at them, for a machine nobody watches:
+// backups failed `failures` times in a row, or the game ran `stale_hours` without a backup (e.g.
+// the watcher stopped without an error). One mail each time, and one more once backups work again.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, TimeZone};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::{debug, info, warn};
+
+use crate::notifier::{Notification, Notifier};
+use crate::{EmailConfig, Result, SmtpSecurity, Taiwu, TaiwuError, TaiwuEvent};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+const SUBJECT_PREFIX: &str = "[Taiwu Backup]";
+const DEFAULT_FAILURES: u32 = 3;
+const DEFAULT_STALE_HOURS: u64 = 6;
+// how often the thread looks whether the game runs without backups
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Email {
+    mailer: Arc<Mailer>,
+    state: Arc<Mutex<State>>,
+    failures: u32,
+}
+
+struct Mailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+#[derive(Default)]
+struct State {
+    failed_in_a_row: u32,
+    // a mail was sent and backups did not work since
+    alerted: bool,
+    // the game runs (seen by the thread) since
+    game_since: Option<Instant>,
+}
+
+impl Email {
+    pub fn new(tw: &Arc<Taiwu>, config: &EmailConfig) -> Result<Email> {
+        let invalid = |key: &str, message: String| TaiwuError::ConfigValueInvalid { key: format!("notifiers.{}", key), message };
+        let host = config.smtp_host.as_deref().ok_or_else(|| invalid("smtp_host", "an `email` notifier needs it".to_owned()))?;
+        let builder = match config.smtp_security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(host),
+            SmtpSecurity::Tls => SmtpTransport::relay(host),
+            SmtpSecurity::None => Ok(SmtpTransport::builder_dangerous(host)),
+        };
+        let mut builder = builder.map_err(|e| invalid("smtp_host", e.to_string()))?.timeout(Some(SMTP_TIMEOUT));
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        } else if config.smtp_security == SmtpSecurity::None {
+            builder = builder.port(25);
+        }
+        let password = match &config.smtp_password_env {
+            Some(name) => Some(std::env::var(name).map_err(|e| invalid("smtp_password_env", format!("`{}`: {}", name, e)))?),
+            None => config.smtp_password.clone(),
+        };
+        if let Some(user) = &config.smtp_user {
+            builder = builder.credentials(Credentials::new(user.clone(), password.unwrap_or_default()));
+        }
+
+        let mailbox = |key: &str, address: &str| address.parse::<Mailbox>().map_err(|e| invalid(key, format!("`{}`: {}", address, e)));
+        let from = config.from.as_deref().or(config.smtp_user.as_deref()).ok_or_else(|| invalid("from", "an `email` notifier needs it".to_owned()))?;
+        let from = mailbox("from", from)?;
+        let to = config.to.iter().map(|address| mailbox("to", address)).collect::<Result<Vec<_>>>()?;
+        if to.is_empty() {
+            return Err(invalid("to", "an `email` notifier needs at least one address".to_owned()));
+        }
+        info!("Email notifications to {} via `{}`", config.to.join(", "), host);
+
+        let email = Email {
+            mailer: Arc::new(Mailer { transport: builder.build(), from, to }),
+            state: Arc::default(),
+            failures: config.failures.unwrap_or(DEFAULT_FAILURES).max(1),
+        };
+        match config.stale_hours.unwrap_or(DEFAULT_STALE_HOURS) {
+            0 => {},
+            hours => email.spawn_stale_check(tw, Duration::from_secs(hours * 3600)),
+        }
+        Ok(email)
+    }
+
+    fn spawn_stale_check(&self, tw: &Arc<Taiwu>, stale_after: Duration) {
+        let (tw, mailer, state) = (Arc::clone(tw), Arc::clone(&self.mailer), Arc::clone(&self.state));
+        thread::spawn(move || {
+            while !tw.is_shutting_down() {
+                thread::sleep(STALE_CHECK_INTERVAL);
+                let mut state = state.lock().unwrap();
+                if !tw.is_game_running() {
+                    state.game_since = None;
+                    continue;
+                }
+                let game_since = *state.game_since.get_or_insert_with(Instant::now);
+                let status = tw.status();
+                let last_backup = status.last_backups.iter().map(|last| last.time).max();
+                let since_backup = last_backup.map(|time| Duration::from_secs((chrono::Utc::now().timestamp() - time).max(0) as u64));
+                // counted from the start of the game, a backup from the day before does not count against it
+                let without_backup = since_backup.map_or(game_since.elapsed(), |since| since.min(game_since.elapsed()));
+                if state.alerted || without_backup < stale_after {
+                    continue;
+                }
+                state.alerted = true;
+                drop(state);
+                let hours = without_backup.as_secs() / 3600;
+                let mut body = format!("The game has been running for {} hours without a backup of its saves.\n", hours);
+                if !status.watching {
+                    body.push_str("The saves are not being watched.\n");
+                }
+                mailer.send(&tw, &format!("No backup for {} hours while the game is running", hours), &body);
+            }
+        });
+    }
+}
+
+impl Notifier for Email {
+    fn notify(&self, notification: &Notification) {
+        let mut state = self.state.lock().unwrap();
+        match &notification.event {
+            TaiwuEvent::BackupFailed { .. } | TaiwuEvent::SaveTruncated { .. } => {
+                state.failed_in_a_row += 1;
+                debug!("{} backups failed in a row", state.failed_in_a_row);
+                if state.failed_in_a_row >= self.failures && !state.alerted {
+                    state.alerted = true;
+                    let subject = format!("Backups failed {} times in a row", state.failed_in_a_row);
+                    self.mailer.send_notification(&subject, notification);
+                }
+            },
+            TaiwuEvent::BackupDone { .. } => {
+                state.failed_in_a_row = 0;
+                if std::mem::take(&mut state.alerted) {
+                    self.mailer.send_notification("Backups work again", notification);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Mailer {
+    fn send_notification(&self, subject: &str, notification: &Notification) {
+        self.send_body(subject, &format!("{}\n\nAt {}.\n", notification.text, Local::now().to_rfc2822()));
+    }
+
+    // with what the status says
+    fn send(&self, tw: &Taiwu, subject: &str, body: &str) {
+        let status = tw.status();
+        let mut body = format!("{}\nBackup folder: {}\n", body, tw.backup_root().display());
+        for last in &status.last_backups {
+            if let Some(time) = Local.timestamp_opt(last.time, 0).single() {
+                body.push_str(&format!("Last backup of world {}: {}\n", last.world, time.to_rfc2822()));
+            }
+        }
+        if let Some(error) = &status.last_error {
+            body.push_str(&format!("Last error: {}\n", error.message));
+        }
+        self.send_body(subject, &body);
+    }
+
+    fn send_body(&self, subject: &str, body: &str) {
+        let mut builder = Message::builder().from(self.from.clone()).subject(format!("{} {}", SUBJECT_PREFIX, subject)).header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let sent = match builder.body(body.to_owned()) {
+            Ok(message) => self.transport.send(&message).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match sent {
+            Ok(_) => info!("Sent email `{}`", subject),
+            Err(e) => warn!("[email] `{}` not sent: {}", subject, e),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// What a watcher event means for one of its paths. The backends report the same change
+// differently: windows sends `ModifyKind::Any` for a write, inotify `ModifyKind::Data` while
+// writing and `AccessKind::Close(Write)` when done, FSEvents `ModifyKind::Data` or
+// `RenameMode::Any` for both ends of a rename.
+
+use std::fs;
+use std::path::Path;
+
+use notify::event::{AccessKind, AccessMode, CreateKind, EventKind, MetadataKind, ModifyKind, RenameMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Change {
+    // the content of the file changed
+    Written,
+    // a file renamed into place or created with content, may be reported more than once
+    Appeared,
+    FolderCreated,
+    // a file or folder gone, which one is not known anymore
+    Removed,
+    // metadata only, read, or not there anymore
+    Ignored,
+}
+
+// `index` of `path` in the paths of the event, `polling` if it came from the poll watcher
+pub(crate) fn classify(kind: &EventKind, index: usize, path: &Path, polling: bool) -> Change {
+    match kind {
+        // polling only sees the change of the modified time (or the content)
+        EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime) | ModifyKind::Data(_)) if polling => Change::Written,
+        EventKind::Modify(ModifyKind::Any | ModifyKind::Other) => Change::Written,
+        // inotify sends one for every write, the file is only complete when closed
+        #[cfg(not(target_os = "linux"))]
+        EventKind::Modify(ModifyKind::Data(_)) => Change::Written,
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => Change::Written,
+
+        // written to a temporary file first, then renamed into place, the paths of `Both` are `[from, to]`
+        EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Any)) if path.is_file() => Change::Appeared,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if index == 1 && path.is_file() => Change::Appeared,
+
+        // an empty file just created gets a write event after it
+        EventKind::Create(CreateKind::File | CreateKind::Any | CreateKind::Other) => match fs::metadata(path) {
+            Ok(meta) if meta.is_dir() => Change::FolderCreated,
+            Ok(meta) if meta.len() > 0 => Change::Appeared,
+            _ => Change::Ignored,
+        },
+        EventKind::Create(CreateKind::Folder) if path.is_dir() => Change::FolderCreated,
+
+        EventKind::Remove(_) if !path.exists() => Change::Removed,
+        // e.g. a folder moved to the recycle bin
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) if !path.exists() => Change::Removed,
+
+        _ => Change::Ignored,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MockGame;
+
+    use std::path::PathBuf;
+
+    use notify::event::{DataChange, RemoveKind};
+
+    const WRITE_TIME: EventKind = EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime));
+    const DATA: EventKind = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+
+    // a save with content, one written empty and one not there
+    fn saves() -> (MockGame, PathBuf, PathBuf, PathBuf) {
+        let game = MockGame::with_worlds(&[1]).unwrap();
+        let empty = game.write_save(2, &[]).unwrap();
+        let (save, missing) = (game.save_path(1), game.save_path(3));
+        (game, save, empty, missing)
+    }
+
+    #[test]
+    fn inotify_write_is_complete_when_closed() {
+        let (_game, save, ..) = saves();
+        assert_eq!(classify(&EventKind::Access(AccessKind::Close(AccessMode::Write)), 0, &save, false), Change::Written);
+        assert_eq!(classify(&EventKind::Access(AccessKind::Close(AccessMode::Read)), 0, &save, false), Change::Ignored);
+        #[cfg(target_os = "linux")]
+        assert_eq!(classify(&DATA, 0, &save, false), Change::Ignored);
+        #[cfg(not(target_os = "linux"))]
+        assert_eq!(classify(&DATA, 0, &save, false), Change::Written);
+    }
+
+    #[test]
+    fn windows_write_is_any() {
+        let (_game, save, ..) = saves();
+        assert_eq!(classify(&EventKind::Modify(ModifyKind::Any), 0, &save, false), Change::Written);
+        assert_eq!(classify(&EventKind::Create(CreateKind::Any), 0, &save, false), Change::Appeared);
+        assert_eq!(classify(&EventKind::Remove(RemoveKind::Any), 0, &save, false), Change::Ignored);
+    }
+
+    #[test]
+    fn fsevents_rename_is_any_at_both_ends() {
+        let (_game, save, _, missing) = saves();
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Any));
+        assert_eq!(classify(&rename, 0, &save, false), Change::Appeared);
+        assert_eq!(classify(&rename, 0, &missing, false), Change::Ignored);
+        assert_eq!(classify(&EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)), 0, &save, false), Change::Ignored);
+    }
+
+    #[test]
+    fn rename_into_place() {
+        let (_game, save, _, missing) = saves();
+        let both = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        assert_eq!(classify(&both, 0, &missing, false), Change::Ignored);
+        assert_eq!(classify(&both, 1, &save, false), Change::Appeared);
+        assert_eq!(classify(&EventKind::Modify(ModifyKind::Name(RenameMode::To)), 0, &save, false), Change::Appeared);
+        assert_eq!(classify(&EventKind::Modify(ModifyKind::Name(RenameMode::From)), 0, &missing, false), Change::Removed);
+    }
+
+    #[test]
+    fn created_empty_waits_for_the_write() {
+        let (game, save, empty, missing) = saves();
+        let create = EventKind::Create(CreateKind::File);
+        assert_eq!(classify(&create, 0, &save, false), Change::Appeared);
+        assert_eq!(classify(&create, 0, &empty, false), Change::Ignored);
+        assert_eq!(classify(&create, 0, &missing, false), Change::Ignored);
+        let folder = save.parent().unwrap();
+        assert_eq!(classify(&create, 0, folder, false), Change::FolderCreated);
+        assert_eq!(classify(&EventKind::Create(CreateKind::Folder), 0, &game.save_root(), false), Change::FolderCreated);
+        assert_eq!(classify(&EventKind::Remove(RemoveKind::File), 0, &missing, false), Change::Removed);
+    }
+
+    #[test]
+    fn poll_watcher_sees_the_write_time() {
+        let (_game, save, ..) = saves();
+        assert_eq!(classify(&WRITE_TIME, 0, &save, true), Change::Written);
+        assert_eq!(classify(&DATA, 0, &save, true), Change::Written);
+        assert_eq!(classify(&WRITE_TIME, 0, &save, false), Change::Ignored);
+        assert_eq!(classify(&EventKind::Create(CreateKind::Any), 0, &save, true), Change::Appeared);
+    }
+}
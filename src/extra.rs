@@ -0,0 +1,128 @@
+// Game files backed up besides the saves (e.g. settings or mod configs), chosen by globs
+// relative to the game root. There are none without the `globs` feature.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "globs")]
+use std::path::Component;
+
+#[cfg(feature = "globs")]
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use log::debug;
+#[cfg(not(feature = "globs"))]
+use log::warn;
+
+#[derive(Debug)]
+pub struct ExtraFiles {
+    #[cfg(feature = "globs")]
+    root: PathBuf,
+    #[cfg(feature = "globs")]
+    globs: GlobSet,
+    bases: Vec<Base>,
+}
+
+// the fixed folder of a glob, which is watched and searched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Base {
+    pub path: PathBuf,
+    // with its subfolders, unless the glob only names files right in it
+    pub recursive: bool,
+}
+
+#[cfg(feature = "globs")]
+impl Base {
+    fn covers(&self, other: &Base) -> bool {
+        if self.recursive {
+            other.path.starts_with(&self.path)
+        } else {
+            !other.recursive && other.path == self.path
+        }
+    }
+}
+
+impl ExtraFiles {
+    #[cfg(feature = "globs")]
+    pub fn new(root: &Path, patterns: &[String]) -> Result<ExtraFiles, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        let mut bases: Vec<Base> = Vec::new();
+        for pattern in patterns {
+            builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+            let (path, recursive) = glob_base(pattern);
+            let base = Base { path: root.join(path), recursive };
+            if !bases.iter().any(|other| other.covers(&base)) {
+                bases.retain(|other| !base.covers(other));
+                bases.push(base);
+            }
+        }
+        Ok(ExtraFiles { root: root.to_owned(), globs: builder.build()?, bases })
+    }
+
+    #[cfg(not(feature = "globs"))]
+    pub fn new(_root: &Path, patterns: &[String]) -> Result<ExtraFiles, crate::TaiwuError> {
+        if !patterns.is_empty() {
+            warn!("`extra_files` is set, but this build has no `globs` feature, ignored");
+        }
+        Ok(ExtraFiles { bases: Vec::new() })
+    }
+
+    pub fn bases(&self) -> &[Base] {
+        &self.bases
+    }
+
+    // relative to the game root, if `path` is one of the extra files
+    #[cfg(feature = "globs")]
+    pub fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        self.globs.is_match(relative).then_some(relative)
+    }
+
+    #[cfg(not(feature = "globs"))]
+    pub fn relative<'a>(&self, _path: &'a Path) -> Option<&'a Path> {
+        None
+    }
+
+    pub fn find(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for base in &self.bases {
+            self.find_in(&base.path, base.recursive, &mut files);
+        }
+        files
+    }
+
+    fn find_in(&self, folder: &Path, recursive: bool, files: &mut Vec<PathBuf>) {
+        let entries = match fs::read_dir(folder) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("could not read `{}`: {}", folder.display(), e);
+                return;
+            }
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                if recursive {
+                    self.find_in(&path, true, files);
+                }
+            } else if self.relative(&path).is_some() {
+                files.push(path);
+            }
+        }
+    }
+}
+
+// the components before the first one with a wildcard (or the parent folder of a plain path),
+// and whether the rest reaches into subfolders
+#[cfg(feature = "globs")]
+fn glob_base(pattern: &str) -> (PathBuf, bool) {
+    let path = Path::new(pattern);
+    let components: Vec<_> = path.components().collect();
+    let is_glob = |component: &Component| {
+        matches!(component, Component::Normal(part) if part.to_string_lossy().contains(['*', '?', '[', '{']))
+    };
+    match components.iter().position(is_glob) {
+        Some(i) => {
+            let base = components[..i].iter().collect();
+            (base, components.len() - i > 1 || pattern.contains("**"))
+        },
+        None => (path.parent().map(Path::to_owned).unwrap_or_default(), false),
+    }
+}
@@ -0,0 +1,66 @@
+// Which files under the save root the watcher backs up, by include and exclude globs
+// relative to the save root. The default is the save of every world folder, see
+// `GameProfile::save_include`, the only one without the `globs` feature.
+
+use std::path::Path;
+
+#[cfg(feature = "globs")]
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+#[cfg(not(feature = "globs"))]
+use log::warn;
+
+#[cfg(not(feature = "globs"))]
+use crate::game::game;
+
+#[cfg(feature = "globs")]
+#[derive(Debug)]
+pub struct SaveFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+#[cfg(not(feature = "globs"))]
+#[derive(Debug)]
+pub struct SaveFilter;
+
+#[cfg(feature = "globs")]
+impl SaveFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<SaveFilter, globset::Error> {
+        Ok(SaveFilter { include: glob_set(include)?, exclude: glob_set(exclude)? })
+    }
+
+    // `relative` to the save root
+    pub fn is_match(&self, relative: &Path) -> bool {
+        self.include.is_match(relative) && !self.exclude.is_match(relative)
+    }
+}
+
+// `<world_prefix>*/<save_file_name>`, as the default glob matches
+#[cfg(not(feature = "globs"))]
+impl SaveFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<SaveFilter, crate::TaiwuError> {
+        if include != [game().save_include()] || !exclude.is_empty() {
+            warn!("`save_include` or `save_exclude` is set, but this build has no `globs` feature, ignored");
+        }
+        Ok(SaveFilter)
+    }
+
+    pub fn is_match(&self, relative: &Path) -> bool {
+        let mut components = relative.iter();
+        match (components.next(), components.next(), components.next()) {
+            (Some(folder), Some(file), None) => {
+                folder.to_str().is_some_and(|folder| folder.starts_with(game().world_prefix())) && file == game().save_file_name()
+            },
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "globs")]
+fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    builder.build()
+}
@@ -0,0 +1,112 @@
+// What is of 太吾绘卷 (The Scroll Of Taiwu) itself: how it is found, where and how it keeps its
+// saves. Watching, copying, pruning and restoring only go by the profile set with
+// `set_game_profile`, so another game whose saves need backing up is one more `GameProfile`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+static GAME_PROFILE: OnceLock<Box<dyn GameProfile>> = OnceLock::new();
+
+// keys of `GameProfile::parse_metadata` backups can be sorted and filtered by: the in-game date as
+// `<year>-<month>` (or only the year) and the name of the character played
+pub const GAME_DATE_KEY: &str = "date";
+pub const CHARACTER_KEY: &str = "character";
+
+pub trait GameProfile: Send + Sync {
+    // shown in the log
+    fn name(&self) -> &str;
+    // finds it (and its saves in Steam userdata and Proton prefixes) through Steam
+    fn steam_app_id(&self) -> Option<u32>;
+    // in a Steam library or a folder of games
+    fn folder_name(&self) -> &str;
+    fn exe_name(&self) -> &str;
+    // lowercase, an entry in the registry or GOG Galaxy (or a folder in a Proton prefix) is of the
+    // game if its name contains one
+    fn name_keywords(&self) -> &[&str];
+    // of the folder in the game root with the world folders
+    fn save_root_name(&self) -> &str;
+    // of a world folder before its number, `world_` for `world_1`
+    fn world_prefix(&self) -> &str;
+    // the worlds shown before their folders exist
+    fn world_count(&self) -> usize;
+    // in a world folder
+    fn save_file_name(&self) -> &str;
+
+    // relative to the save root, the default of `save_include`
+    fn save_include(&self) -> String {
+        format!("{}*/{}", self.world_prefix(), self.save_file_name())
+    }
+
+    // of local mods in the game root, one folder each
+    fn mod_folder_name(&self) -> Option<&str> {
+        None
+    }
+
+    // what the save says about the game in it (e.g. the in-game date, see `GAME_DATE_KEY`),
+    // recorded with its backup; none if it can not be read
+    fn parse_metadata(&self, _save: &Path) -> Option<BTreeMap<String, String>> {
+        None
+    }
+}
+
+pub struct TaiwuGame;
+
+impl GameProfile for TaiwuGame {
+    fn name(&self) -> &str {
+        "太吾绘卷 (The Scroll Of Taiwu)"
+    }
+
+    fn steam_app_id(&self) -> Option<u32> {
+        Some(838350)
+    }
+
+    fn folder_name(&self) -> &str {
+        "The Scroll Of Taiwu"
+    }
+
+    fn exe_name(&self) -> &str {
+        "The Scroll of Taiwu.exe"
+    }
+
+    fn name_keywords(&self) -> &[&str] {
+        &["taiwu", "太吾"]
+    }
+
+    fn save_root_name(&self) -> &str {
+        "Save"
+    }
+
+    fn world_prefix(&self) -> &str {
+        "world_"
+    }
+
+    fn world_count(&self) -> usize {
+        5
+    }
+
+    fn save_file_name(&self) -> &str {
+        "local.sav"
+    }
+
+    fn mod_folder_name(&self) -> Option<&str> {
+        Some("Mod")
+    }
+}
+
+// before the config is loaded or anything is backed up, later ones are ignored
+pub fn set_game_profile(profile: impl GameProfile + 'static) {
+    if GAME_PROFILE.set(Box::new(profile)).is_err() {
+        log::warn!("the game profile was already set, ignored");
+    }
+}
+
+// the one set, 太吾绘卷 if none was
+pub fn game() -> &'static dyn GameProfile {
+    GAME_PROFILE.get_or_init(|| Box::new(TaiwuGame)).as_ref()
+}
+
+pub(crate) fn is_name_of_game(name: &str) -> bool {
+    let name = name.to_lowercase();
+    game().name_keywords().iter().any(|keyword| name.contains(keyword))
+}
@@ -1,52 +1,490 @@
+use std::fmt;
+use std::fs;
+use std::io;
 use std::path::{PathBuf, Path};
+use std::process::Command;
 
-use log::{debug, error};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 
-const TAIWU_GAME_STEAM_APPID: usize = 838350;
+use crate::game::{game, is_name_of_game};
+
+// how the game root was found, shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectMethod {
+    Manual,
+    Steam,
+    CommonPath,
+    Registry,
+    GogGalaxy,
+}
+
+impl fmt::Display for DetectMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DetectMethod::Manual => "manual",
+            DetectMethod::Steam => "Steam",
+            DetectMethod::CommonPath => "common path",
+            DetectMethod::Registry => "registry",
+            DetectMethod::GogGalaxy => "GOG Galaxy",
+        };
+        f.write_str(name)
+    }
+}
+
+type Detector = fn() -> Option<PathBuf>;
 
 #[derive(Debug)]
 pub struct GameRoot {
     path: PathBuf,
+    method: DetectMethod,
 }
 
 impl GameRoot {
-    pub fn new(path: impl AsRef<Path>) -> Option<GameRoot> {
-        let path = path.as_ref();
-        if path.is_dir() {
-            let path = path.to_owned();
-            Some(GameRoot { path })
+    pub fn new(path: impl AsRef<Path>) -> Result<GameRoot, GameRootDiagnosis> {
+        let diagnosis = GameRootDiagnosis::of(path.as_ref());
+        if diagnosis.is_valid() {
+            Ok(GameRoot { path: diagnosis.path, method: DetectMethod::Manual })
         } else {
-            None
+            Err(diagnosis)
         }
     }
 
     pub fn auto() -> Option<GameRoot> {
-        if let Some(path) = get_game_root_by_appid(TAIWU_GAME_STEAM_APPID) {
-            Some(GameRoot { path })
-        } else {
-            None
+        let detectors: [(DetectMethod, Detector); 4] = [
+            (DetectMethod::Steam, || game().steam_app_id().and_then(get_game_root_by_appid)),
+            (DetectMethod::CommonPath, get_game_root_by_common_path),
+            (DetectMethod::Registry, get_game_root_by_registry),
+            (DetectMethod::GogGalaxy, get_game_root_by_gog_galaxy),
+        ];
+        for (method, detect) in detectors {
+            if let Some(path) = detect() {
+                info!("found {} by {}: `{}`", game().name(), method, path.display());
+                return Some(GameRoot { path, method });
+            }
+            debug!("could not find {} by {}", game().name(), method);
         }
+        error!("could not locate {} on this computer", game().name());
+        None
     }
 
     pub fn path(&self) -> &Path {
         self.path.as_ref()
     }
+
+    pub fn method(&self) -> DetectMethod {
+        self.method
+    }
+
+    pub fn save_root(&self) -> PathBuf {
+        let native = self.path.join(game().save_root_name());
+        if native.is_dir() {
+            return native;
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(save_root) = game().steam_app_id().and_then(get_proton_save_root) {
+            info!("use the save folder in the proton prefix: `{}`", save_root.display());
+            return save_root;
+        }
+
+        native
+    }
+
+    // the one above first, then the known other places and `extra` (configured) that exist, the
+    // saves in all of them are backed up
+    pub fn save_roots(&self, extra: &[PathBuf], steam_user: Option<&str>) -> Vec<PathBuf> {
+        let mut roots = vec![self.save_root()];
+        for root in game().steam_app_id().map(|app_id| get_userdata_save_roots(app_id, steam_user)).unwrap_or_default() {
+            if !roots.contains(&root) {
+                info!("also use the save folder in Steam userdata: `{}`", root.display());
+                roots.push(root);
+            }
+        }
+        for root in extra {
+            if !root.is_dir() {
+                warn!("the save folder `{}` does not exist, ignored", root.display());
+            } else if !roots.contains(root) {
+                info!("also use the save folder `{}`", root.display());
+                roots.push(root.clone());
+            }
+        }
+        roots
+    }
 }
 
-fn get_game_root_by_appid(app_id: usize) -> Option<PathBuf> {
-    use steamlocate::SteamDir;
+// what a folder given as the game root has of the game, to tell what is wrong with it (and for a
+// setup UI to show)
+#[derive(Debug, Clone, Serialize)]
+pub struct GameRootDiagnosis {
+    pub path: PathBuf,
+    pub exists: bool,
+    pub is_dir: bool,
+    // `The Scroll of Taiwu.exe`
+    pub has_exe: bool,
+    // `Save`
+    pub has_save_root: bool,
+    // the world folders in `Save`
+    pub worlds: Vec<usize>,
+    // a folder next to or in it that is the game root, e.g. when the Steam library was given
+    pub suggestion: Option<PathBuf>,
+}
 
-    let app_id = &(u32::try_from(app_id).unwrap());
+impl GameRootDiagnosis {
+    pub fn of(path: &Path) -> GameRootDiagnosis {
+        let save_root = path.join(game().save_root_name());
+        let mut diagnosis = GameRootDiagnosis {
+            path: path.to_owned(),
+            exists: path.exists(),
+            is_dir: path.is_dir(),
+            has_exe: is_game_root(path),
+            has_save_root: save_root.is_dir(),
+            worlds: crate::find_worlds(&save_root),
+            suggestion: None,
+        };
+        diagnosis.worlds.sort();
+        if !diagnosis.is_valid() {
+            diagnosis.suggestion = suggest_game_root(path);
+        }
+        diagnosis
+    }
+
+    // the game writes `Save` on its first start, an installed game is enough
+    pub fn is_valid(&self) -> bool {
+        self.is_dir && (self.has_exe || self.has_save_root)
+    }
+
+    // what is missing, none if it is valid
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.exists {
+            problems.push("it does not exist".to_owned());
+        } else if !self.is_dir {
+            problems.push("it is not a folder".to_owned());
+        } else if !self.is_valid() {
+            problems.push(format!("it has no `{}`", game().exe_name()));
+            problems.push(format!("it has no `{}` folder", game().save_root_name()));
+        }
+        problems
+    }
+}
+
+impl fmt::Display for GameRootDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is not the game folder", self.path.display())?;
+        let problems = self.problems();
+        if !problems.is_empty() {
+            write!(f, ", {}", problems.join(" and "))?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", maybe `{}` is meant", suggestion.display())?;
+        }
+        Ok(())
+    }
+}
+
+// the folder of the game in a Steam library (or its `common`), or above `Save` or a world folder
+fn suggest_game_root(path: &Path) -> Option<PathBuf> {
+    let below = [
+        path.join(game().folder_name()),
+        path.join("common").join(game().folder_name()),
+        path.join("steamapps").join("common").join(game().folder_name()),
+    ];
+    let above = path.ancestors().skip(1).take(2).map(Path::to_owned);
+    below.into_iter().chain(above).find(|candidate| is_game_root(candidate) || candidate.join(game().save_root_name()).is_dir())
+}
+
+// what a save may depend on, recorded with each backup: saves often do not load with another
+// patch or other mods
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSetup {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+    // installed, local folders by name and Workshop items as `workshop <id>`; which are enabled is
+    // kept by the game itself
+    #[serde(default)]
+    pub mods: Vec<String>,
+}
+
+impl GameSetup {
+    pub fn detect(game_root: &Path) -> GameSetup {
+        GameSetup { game_version: installed_version(game_root), mods: installed_mods(game_root) }
+    }
+
+    // how `self` (of a backup) differs from `current`, to warn before restoring it
+    pub fn differences(&self, current: &GameSetup) -> Vec<String> {
+        let mut differences = Vec::new();
+        if let (Some(then), Some(now)) = (&self.game_version, &current.game_version) {
+            if then != now {
+                differences.push(format!("it was backed up with game version {}, {} is installed", then, now));
+            }
+        }
+        let missing: Vec<&str> = self.mods.iter().filter(|name| !current.mods.contains(name)).map(String::as_str).collect();
+        if !missing.is_empty() {
+            differences.push(format!("mods it was backed up with are not installed: {}", missing.join(", ")));
+        }
+        let added: Vec<&str> = current.mods.iter().filter(|name| !self.mods.contains(name)).map(String::as_str).collect();
+        if !added.is_empty() {
+            differences.push(format!("mods installed since it was backed up: {}", added.join(", ")));
+        }
+        differences
+    }
+}
+
+// the Steam build of the game at `game_root`, from the app manifest of its library, none for
+// other installs
+fn installed_version(game_root: &Path) -> Option<String> {
+    let steamapps = game_root.parent()?.parent()?;
+    let manifest = fs::read_to_string(steamapps.join(format!("appmanifest_{}.acf", game().steam_app_id()?))).ok()?;
+    manifest.lines().find_map(|line| {
+        let mut fields = line.split('"').filter(|field| !field.trim().is_empty());
+        match (fields.next(), fields.next()) {
+            (Some("buildid"), Some(build)) => Some(format!("steam build {}", build)),
+            _ => None,
+        }
+    })
+}
+
+fn installed_mods(game_root: &Path) -> Vec<String> {
+    let folders = |path: &Path| -> Vec<String> {
+        let Ok(entries) = fs::read_dir(path) else { return Vec::new() };
+        entries.flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect()
+    };
+    let mut mods = game().mod_folder_name().map(|name| folders(&game_root.join(name))).unwrap_or_default();
+    if let Some((steamapps, app_id)) = game_root.parent().and_then(Path::parent).zip(game().steam_app_id()) {
+        let workshop = steamapps.join("workshop").join("content").join(app_id.to_string());
+        mods.extend(folders(&workshop).into_iter().map(|id| format!("workshop {}", id)));
+    }
+    mods.sort();
+    mods
+}
+
+// through Steam if it was found there, so it gets the overlay and cloud saves, the exe otherwise
+pub fn launch(game_root: &Path, method: DetectMethod) -> io::Result<()> {
+    if let Some(app_id) = game().steam_app_id().filter(|_| method == DetectMethod::Steam) {
+        return open_url(&format!("steam://rungameid/{}", app_id));
+    }
+    Command::new(game_root.join(game().exe_name())).current_dir(game_root).spawn().map(|_| ())
+}
 
-    let mut steamdir = SteamDir::locate().unwrap();
-    match steamdir.app(app_id) {
+// by the handler of the system, like the tray opens folders
+fn open_url(url: &str) -> io::Result<()> {
+    #[cfg(windows)]
+    let opener = "explorer";
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let opener = "xdg-open";
+
+    Command::new(opener).arg(url).spawn().map(|_| ())
+}
+
+fn is_game_root(path: &Path) -> bool {
+    path.join(game().exe_name()).is_file()
+}
+
+fn get_game_root_by_appid(app_id: u32) -> Option<PathBuf> {
+    use steamlocate::SteamDir;
+
+    let mut steamdir = SteamDir::locate()?;
+    match steamdir.app(&app_id) {
         Some(app) => {
             debug!("{:?}", app);
             Some(app.path.to_owned())
         },
-        None => {
-            error!("could not locate 太吾绘卷 (The Scroll Of Taiwu) on this computer");
-            None
+        None => None,
+    }
+}
+
+// Proton keeps a windows-like `drive_c` per game under `steamapps/compatdata/<appid>/pfx`,
+// where the saves are found in the usual windows user folders
+#[cfg(target_os = "linux")]
+fn get_proton_save_root(app_id: u32) -> Option<PathBuf> {
+    use steamlocate::SteamDir;
+
+    let mut steamdir = SteamDir::locate()?;
+    for steamapps in &steamdir.libraryfolders().paths {
+        let drive_c = steamapps.join("compatdata").join(app_id.to_string()).join("pfx").join("drive_c");
+        if !drive_c.is_dir() {
+            continue;
+        }
+        debug!("found proton prefix `{}`", drive_c.display());
+
+        let user = drive_c.join("users").join("steamuser");
+        let bases = [
+            user.join("AppData").join("LocalLow"),
+            user.join("AppData").join("Roaming"),
+            user.join("Documents"),
+        ];
+        for base in &bases {
+            if let Some(save_root) = find_save_root_in(base, 2) {
+                return Some(save_root);
+            }
+        }
+    }
+    None
+}
+
+// some setups keep the saves in the Steam Cloud folder `userdata/<user id>/<appid>` instead,
+// there only where a world is, of `user` only if given
+fn get_userdata_save_roots(app_id: u32, user: Option<&str>) -> Vec<PathBuf> {
+    use steamlocate::SteamDir;
+
+    let Some(steamdir) = SteamDir::locate() else {
+        return Vec::new();
+    };
+    let Ok(users) = fs::read_dir(steamdir.path.join("userdata")) else {
+        return Vec::new();
+    };
+    let mut roots = Vec::new();
+    for entry in users.flatten() {
+        if user.is_some_and(|user| entry.file_name() != user) {
+            continue;
+        }
+        let app = entry.path().join(app_id.to_string());
+        let candidates = [app.join("remote").join(game().save_root_name()), app.join("remote"), app.join(game().save_root_name())];
+        if let Some(root) = candidates.into_iter().find(|root| !crate::find_worlds(root).is_empty()) {
+            debug!("found saves in Steam userdata `{}`", root.display());
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+// look for `<..>/<game folder>/Save` at most `depth` folders below `base`
+#[cfg(target_os = "linux")]
+fn find_save_root_in(base: &Path, depth: usize) -> Option<PathBuf> {
+    let entries = fs::read_dir(base).ok()?;
+    for folder in entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()) {
+        let name = folder.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let save_root = folder.join(game().save_root_name());
+        if is_name_of_game(name) && save_root.is_dir() {
+            return Some(save_root);
+        }
+        if depth > 1 {
+            if let Some(save_root) = find_save_root_in(&folder, depth - 1) {
+                return Some(save_root);
+            }
+        }
+    }
+    None
+}
+
+fn get_game_root_by_common_path() -> Option<PathBuf> {
+    const RELATIVES: [&str; 6] = [
+        "Program Files (x86)\\Steam\\steamapps\\common",
+        "Program Files\\Steam\\steamapps\\common",
+        "SteamLibrary\\steamapps\\common",
+        "Steam\\steamapps\\common",
+        "GOG Games",
+        "Games",
+    ];
+
+    for drive in 'C'..='Z' {
+        let drive = PathBuf::from(format!("{}:\\", drive));
+        if !drive.is_dir() {
+            continue;
+        }
+        for relative in RELATIVES {
+            let path = drive.join(relative).join(game().folder_name());
+            if is_game_root(&path) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(windows)]
+fn get_game_root_by_registry() -> Option<PathBuf> {
+    use winreg::enums::HKEY_LOCAL_MACHINE;
+    use winreg::RegKey;
+
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+
+    // the uninstall entry written by steam, it is also there for steam libraries steamlocate missed
+    let uninstall_keys = game().steam_app_id().map(|app_id| [
+        format!("SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\Steam App {}", app_id),
+        format!("SOFTWARE\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\Steam App {}", app_id),
+    ]).unwrap_or_default();
+    for key in &uninstall_keys {
+        if let Ok(key) = hklm.open_subkey(key) {
+            if let Ok(path) = key.get_value::<String, _>("InstallLocation") {
+                let path = PathBuf::from(path);
+                if is_game_root(&path) {
+                    return Some(path);
+                }
+            }
+        }
+    }
+
+    // one subkey per installed GOG game, the id is not known so match by name
+    let games = hklm.open_subkey("SOFTWARE\\WOW6432Node\\GOG.com\\Games").ok()?;
+    for id in games.enum_keys().flatten() {
+        let game = match games.open_subkey(&id) {
+            Ok(game) => game,
+            Err(_) => continue,
+        };
+        let name: String = game.get_value("gameName").unwrap_or_default();
+        if !is_name_of_game(&name) {
+            continue;
+        }
+        if let Ok(path) = game.get_value::<String, _>("path") {
+            let path = PathBuf::from(path);
+            if is_game_root(&path) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(not(windows))]
+fn get_game_root_by_registry() -> Option<PathBuf> {
+    None
+}
+
+// every game installed by GOG Galaxy has a `goggame-<id>.info` json manifest with its name
+fn get_game_root_by_gog_galaxy() -> Option<PathBuf> {
+    const LIBRARIES: [&str; 2] = [
+        "C:\\Program Files (x86)\\GOG Galaxy\\Games",
+        "C:\\Program Files\\GOG Galaxy\\Games",
+    ];
+
+    for library in LIBRARIES {
+        let entries = match fs::read_dir(library) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for game in entries.flatten().map(|entry| entry.path()) {
+            if is_gog_manifest_of_game(&game) && is_game_root(&game) {
+                return Some(game);
+            }
+        }
+    }
+    None
+}
+
+fn is_gog_manifest_of_game(game: &Path) -> bool {
+    let entries = match fs::read_dir(game) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if !(name.starts_with("goggame-") && name.ends_with(".info")) {
+            continue;
+        }
+        let manifest = fs::read_to_string(&path).ok().and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok());
+        if let Some(manifest) = manifest {
+            let name = manifest["name"].as_str().unwrap_or_default();
+            if is_name_of_game(name) {
+                return true;
+            }
         }
     }
+    false
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+// `status.json` in the appdata folder, rewritten every few seconds while running, so scripts
+// (and `taiwu ctl status` when the control channel does not answer) can tell it is alive.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{store, LastError, Result, Taiwu};
+
+const STATUS_FILE_NAME: &str = "status.json";
+// how often a stop is looked for between two writes
+const HEARTBEAT_TICK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub version: String,
+    pub pid: u32,
+    // unix seconds of this write, it is the next write after `interval_secs`
+    pub time: i64,
+    pub interval_secs: u64,
+    // written a last time when it quits
+    pub stopped: bool,
+    // unix seconds of the last change seen by the watcher
+    #[serde(default)]
+    pub last_event: Option<i64>,
+    pub watching: bool,
+    pub paused: bool,
+    pub polling: bool,
+    pub game_running: bool,
+    pub worlds: Vec<WorldHeartbeat>,
+    #[serde(default)]
+    pub queued_jobs: usize,
+    #[serde(default)]
+    pub last_error: Option<LastError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldHeartbeat {
+    pub world: usize,
+    // unix seconds of its newest backup
+    pub last_backup: i64,
+}
+
+impl Heartbeat {
+    fn of(tw: &Taiwu, interval: Duration, stopped: bool) -> Heartbeat {
+        let status = tw.status();
+        Heartbeat {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            pid: std::process::id(),
+            time: chrono::offset::Local::now().timestamp(),
+            interval_secs: interval.as_secs(),
+            stopped,
+            last_event: status.last_event,
+            watching: status.watching,
+            paused: status.paused,
+            polling: status.polling,
+            game_running: status.game_running,
+            worlds: status.last_backups.iter().map(|last| WorldHeartbeat { world: last.world, last_backup: last.time }).collect(),
+            queued_jobs: status.queued_jobs,
+            last_error: status.last_error,
+        }
+    }
+
+    // seconds since it was written
+    pub fn age(&self) -> i64 {
+        chrono::offset::Local::now().timestamp() - self.time
+    }
+
+    // not stopped, and written again in time (a write may be a little late)
+    pub fn is_alive(&self) -> bool {
+        !self.stopped && self.age() <= 2 * self.interval_secs as i64 + HEARTBEAT_TICK.as_secs() as i64
+    }
+
+    // the last one written, `None` if there is none yet
+    pub fn read() -> Result<Option<Heartbeat>> {
+        let text = match fs::read_to_string(status_file()?) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_str(&text).map_err(io::Error::from)?))
+    }
+
+    fn write(&self) -> Result<()> {
+        let path = status_file()?;
+        let tmp = store::temp_path(&path);
+        fs::write(&tmp, serde_json::to_string_pretty(self).map_err(io::Error::from)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+// until `tw` shuts down, then it is written once more as stopped
+pub fn spawn(tw: Arc<Taiwu>, interval: Duration) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        let stopped = tw.is_shutting_down();
+        match Heartbeat::of(&tw, interval, stopped).write() {
+            Ok(()) => debug!("heartbeat written"),
+            Err(e) => warn!("[heartbeat] error: {}", e),
+        }
+        if stopped {
+            return;
+        }
+        let written = Instant::now();
+        while written.elapsed() < interval && !tw.is_shutting_down() {
+            thread::sleep(HEARTBEAT_TICK);
+        }
+    })
+}
+
+fn status_file() -> Result<PathBuf> {
+    let appdata = crate::get_appdata_root_default()?;
+    fs::create_dir_all(&appdata)?;
+    Ok(appdata.join(STATUS_FILE_NAME))
+}
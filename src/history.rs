@@ -0,0 +1,226 @@
+// What was backed up, skipped, restored and pruned, and what failed, one JSON line each in the
+// appdata folder, see `Taiwu::history`. Every program on this machine (the tray, the commands of
+// every profile) adds to the same file, the oldest entries are dropped once there are too many.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::store;
+
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+const HISTORY_MAX_ENTRIES: usize = 10_000;
+// written again without the oldest once this many more were added
+const HISTORY_COMPACT_SLACK: usize = HISTORY_MAX_ENTRIES / 4;
+// held while adding, so that a line added by another program is not lost when it is compacted
+const HISTORY_LOCK_FILE_NAME: &str = "history.lock";
+const HISTORY_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const HISTORY_LOCK_RETRY_DELAY: Duration = Duration::from_millis(20);
+// adding takes milliseconds, a lock this old was left by a crash
+const HISTORY_LOCK_STALE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Backup,
+    // not backed up, the same backup exists or the save is truncated
+    Skip,
+    Error,
+    // undoing one too
+    Restore,
+    Prune,
+}
+
+impl HistoryKind {
+    pub const ALL: [HistoryKind; 5] = [HistoryKind::Backup, HistoryKind::Skip, HistoryKind::Error, HistoryKind::Restore, HistoryKind::Prune];
+}
+
+impl fmt::Display for HistoryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            HistoryKind::Backup => "backup",
+            HistoryKind::Skip => "skip",
+            HistoryKind::Error => "error",
+            HistoryKind::Restore => "restore",
+            HistoryKind::Prune => "prune",
+        };
+        // so it can be padded in a table
+        f.pad(name)
+    }
+}
+
+impl FromStr for HistoryKind {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<HistoryKind, String> {
+        HistoryKind::ALL.into_iter()
+            .find(|kind| kind.to_string().eq_ignore_ascii_case(text))
+            .ok_or_else(|| format!("unknown kind `{}`, one of backup, skip, error, restore or prune", text))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // unix seconds
+    pub time: i64,
+    pub kind: HistoryKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub world: Option<usize>,
+    // of the program that added it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub message: String,
+    // the backup made, restored or skipped for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl HistoryEntry {
+    pub fn new(kind: HistoryKind, world: Option<usize>, message: impl Into<String>) -> HistoryEntry {
+        HistoryEntry { time: chrono::Utc::now().timestamp(), kind, world, profile: None, message: message.into(), path: None }
+    }
+
+    pub fn path(mut self, path: impl AsRef<Path>) -> HistoryEntry {
+        self.path = Some(path.as_ref().to_owned());
+        self
+    }
+}
+
+// which entries `Taiwu::history` returns, only those of its own profile
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    // local wall clock times, `since` is included and `until` is not
+    pub since: Option<chrono::NaiveDateTime>,
+    pub until: Option<chrono::NaiveDateTime>,
+    // any kind if empty
+    pub kinds: Vec<HistoryKind>,
+    pub world: Option<usize>,
+    pub newest_first: bool,
+    // the newest ones, whatever the order
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        let since = self.since.and_then(store::local_to_utc).map(|time| time.timestamp());
+        let until = self.until.and_then(store::local_to_utc).map(|time| time.timestamp());
+        since.is_none_or(|since| entry.time >= since)
+            && until.is_none_or(|until| entry.time < until)
+            && (self.kinds.is_empty() || self.kinds.contains(&entry.kind))
+            && self.world.is_none_or(|world| entry.world == Some(world))
+    }
+}
+
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    // nothing is written in a dry run
+    enabled: bool,
+    // lines in the file as far as this process knows
+    lines: Mutex<Option<usize>>,
+}
+
+impl History {
+    pub fn open(folder: &Path, enabled: bool) -> History {
+        History { path: folder.join(HISTORY_FILE_NAME), enabled, lines: Mutex::new(None) }
+    }
+
+    pub fn add(&self, entry: HistoryEntry) {
+        if !self.enabled {
+            return;
+        }
+        if let Err(e) = self.append(&entry) {
+            warn!("could not add to the history `{}`: {}", self.path.display(), e);
+        }
+    }
+
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        let mut lines = self.lines.lock().unwrap();
+        let count = match *lines {
+            Some(count) => count,
+            None => self.read().map_or(0, |entries| entries.len()),
+        };
+        if let Some(folder) = self.path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let _lock = self.lock()?;
+        let mut line = serde_json::to_string(entry).map_err(io::Error::from)?;
+        line.push('\n');
+        // one write, so lines of programs adding at the same time do not mix
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(line.as_bytes())?;
+        *lines = Some(count + 1);
+        if count + 1 >= HISTORY_MAX_ENTRIES + HISTORY_COMPACT_SLACK {
+            *lines = Some(self.compact()?);
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> io::Result<HistoryLock> {
+        let path = self.path.with_file_name(HISTORY_LOCK_FILE_NAME);
+        let started = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(HistoryLock { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path).and_then(|meta| meta.modified()).ok().and_then(|time| time.elapsed().ok());
+                    if age.is_some_and(|age| age >= HISTORY_LOCK_STALE) {
+                        warn!("broke the lock `{}` left by a crash", path.display());
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() >= HISTORY_LOCK_TIMEOUT {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, format!("`{}` is held by another program", path.display())));
+                    }
+                    thread::sleep(HISTORY_LOCK_RETRY_DELAY);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // written next to it and renamed over it, without the oldest entries, under the lock
+    fn compact(&self) -> io::Result<usize> {
+        let entries = self.read()?;
+        let kept = &entries[entries.len().saturating_sub(HISTORY_MAX_ENTRIES)..];
+        let mut text = String::new();
+        for entry in kept {
+            text.push_str(&serde_json::to_string(entry).map_err(io::Error::from)?);
+            text.push('\n');
+        }
+        let temp = store::temp_path(&self.path);
+        fs::write(&temp, text)?;
+        fs::rename(&temp, &self.path)?;
+        debug!("history compacted, {} of {} entries kept", kept.len(), entries.len());
+        Ok(kept.len())
+    }
+
+    // oldest first, broken lines (e.g. cut short by a crash) are left out
+    pub fn read(&self) -> io::Result<Vec<HistoryEntry>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+    }
+}
+
+struct HistoryLock {
+    path: PathBuf,
+}
+
+impl Drop for HistoryLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("could not unlock the history `{}`: {}", self.path.display(), e);
+        }
+    }
+}
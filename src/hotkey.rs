@@ -0,0 +1,71 @@
+// A system wide hotkey, e.g. to back up right before a risky fight without leaving the game.
+
+use std::error::Error;
+
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use log::info;
+
+// the hotkey is unregistered when dropped
+pub struct Hotkey {
+    #[cfg(not(windows))]
+    _manager: GlobalHotKeyManager,
+}
+
+// `combo` like `Ctrl+Alt+B`, `on_press` is called from the thread of the event loop
+pub fn register(combo: &str, on_press: impl Fn() + Send + Sync + 'static) -> Result<Hotkey, Box<dyn Error>> {
+    let hotkey: HotKey = combo.parse()?;
+    let id = hotkey.id();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+        if event.id == id && event.state == HotKeyState::Pressed {
+            on_press();
+        }
+    }));
+
+    // the hotkey messages go to the thread which registered it, so it needs its own message loop
+    #[cfg(windows)]
+    {
+        use std::sync::mpsc;
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let manager = match GlobalHotKeyManager::new().and_then(|manager| manager.register(hotkey).map(|()| manager)) {
+                Ok(manager) => {
+                    let _ = tx.send(Ok(()));
+                    manager
+                },
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                },
+            };
+            message_loop();
+            drop(manager);
+        });
+        rx.recv()??;
+        info!("Registered hotkey `{}`", combo);
+        Ok(Hotkey {})
+    }
+
+    // x11 has a thread of its own, macos must register on the main thread (with the tray event loop)
+    #[cfg(not(windows))]
+    {
+        let manager = GlobalHotKeyManager::new()?;
+        manager.register(hotkey)?;
+        info!("Registered hotkey `{}`", combo);
+        Ok(Hotkey { _manager: manager })
+    }
+}
+
+#[cfg(windows)]
+fn message_loop() {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{DispatchMessageW, GetMessageW, TranslateMessage, MSG};
+
+    unsafe {
+        let mut msg: MSG = std::mem::zeroed();
+        while GetMessageW(&mut msg, 0, 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
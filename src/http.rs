@@ -0,0 +1,363 @@
+// A small JSON API on localhost for scripts, and a dashboard page using it.
+//
+//   GET  /                     the dashboard
+//   GET  /status               same as `taiwu ctl status`
+//   GET  /backups?world=<N>    backups of one world, or of all worlds without `world`; of one
+//                              in-game year with `game_year=3`, or by the in-game date with
+//                              `sort=game-date`
+//   GET  /stats
+//   GET  /metrics              counters for Prometheus, in its text format
+//   GET  /diff?world=<N>&a=<backup id>&b=<backup id>
+//   GET  /history?kind=backup,error&since=<time>  as `taiwu history`
+//   POST /backup-now
+//   POST /restore              body `{"world": 1, "id": "<backup id>"}`, or instead of the id
+//                              `"at": "yesterday 21:00"` or `"before_label": "<label>"`; into
+//                              another slot with `"to": 2` (and `"overwrite": true` if that one
+//                              has a save)
+//   POST /label                body `{"world": 1, "id": "<backup id>", "label": "before the fight"}`
+//
+// Requests must name the server by `127.0.0.1:<port>` or `localhost:<port>` in `Host`, POSTs need
+// `Content-Type: application/json` and, if they have an `Origin`, the one of the dashboard.
+
+use std::io;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::ipc::{self, Status};
+use crate::{BackupEntry, BackupQuery, BackupSelector, HistoryFilter, HistoryKind, Result, Taiwu};
+
+const DASHBOARD_HTML: &str = include_str!("../assets/dashboard.html");
+
+#[derive(Debug, Serialize)]
+struct WorldBackups {
+    world: usize,
+    backups: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestoreRequest {
+    world: usize,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    at: Option<String>,
+    #[serde(default)]
+    before_label: Option<String>,
+    #[serde(default)]
+    to: Option<usize>,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelRequest {
+    world: usize,
+    id: String,
+    #[serde(default)]
+    label: String,
+}
+
+// only bound to the loopback interface, see `check_request` for the pages of a browser
+pub fn serve(tw: Arc<Taiwu>, port: u16) -> Result<JoinHandle<()>> {
+    let server = Server::http(("127.0.0.1", port)).map_err(io::Error::other)?;
+    info!("HTTP API listening on `http://127.0.0.1:{}`", port);
+
+    let handle = thread::spawn(move || {
+        for request in server.incoming_requests() {
+            debug!("http request {} {}", request.method(), request.url());
+            if let Err(e) = handle_request(&tw, request, port) {
+                warn!("http response error: {}", e);
+            }
+        }
+    });
+    Ok(handle)
+}
+
+fn handle_request(tw: &Taiwu, mut request: Request, port: u16) -> io::Result<()> {
+    let url = request.url().to_owned();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    if let Err(message) = check_request(&request, port) {
+        warn!("http request {} {} refused: {}", request.method(), url, message);
+        let (code, body) = json(403, &ipc::Response::err(message));
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        return request.respond(Response::from_string(body).with_status_code(code).with_header(header));
+    }
+
+    if (request.method(), path) == (&Method::Get, "/") {
+        let header = Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap();
+        return request.respond(Response::from_string(DASHBOARD_HTML).with_header(header));
+    }
+    if (request.method(), path) == (&Method::Get, "/metrics") {
+        let header = Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+        return request.respond(Response::from_string(tw.metrics()).with_header(header));
+    }
+
+    let (code, body) = match (request.method(), path) {
+        (Method::Get, "/status") => json(200, &Status::of(tw)),
+        (Method::Get, "/backups") => backups(tw, query),
+        (Method::Get, "/stats") => match tw.stats() {
+            Ok(stats) => json(200, &stats),
+            Err(e) => json(500, &ipc::Response::err(format!("stats failed: {}", e))),
+        },
+        (Method::Get, "/diff") => diff(tw, query),
+        (Method::Get, "/history") => history(tw, query),
+        (Method::Post, "/backup-now") => match tw.backup_once().into_result() {
+            Ok(report) => json(200, &ipc::Response::ok(format!("backup done, {} files", report.done.len()))),
+            Err(e) => json(500, &ipc::Response::err(format!("backup failed: {}", e))),
+        },
+        (Method::Post, "/restore") => {
+            let mut text = String::new();
+            request.as_reader().read_to_string(&mut text)?;
+            restore(tw, &text)
+        },
+        (Method::Post, "/label") => {
+            let mut text = String::new();
+            request.as_reader().read_to_string(&mut text)?;
+            label(tw, &text)
+        },
+        (_, "/" | "/status" | "/backups" | "/stats" | "/metrics" | "/diff" | "/history" | "/backup-now" | "/restore" | "/label") => {
+            json(405, &ipc::Response::err("method not allowed"))
+        },
+        _ => json(404, &ipc::Response::err("not found")),
+    };
+
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    request.respond(Response::from_string(body).with_status_code(code).with_header(header))
+}
+
+// any web page open in the browser can send requests here. A rebound DNS name of its own would
+// let it read the answers, so the host must be the loopback one. It can not send JSON without a
+// CORS preflight, which is never allowed, only forms and text, and its `Origin` is not ours
+fn check_request(request: &Request, port: u16) -> std::result::Result<(), &'static str> {
+    let header = |name: &'static str| request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.as_str());
+    let ours = [format!("127.0.0.1:{}", port), format!("localhost:{}", port)];
+    if !header("Host").is_some_and(|host| ours.iter().any(|ours| host.eq_ignore_ascii_case(ours))) {
+        return Err("unknown host");
+    }
+    if request.method() != &Method::Post {
+        return Ok(());
+    }
+    let json_body = header("Content-Type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"));
+    if !json_body {
+        return Err("`Content-Type: application/json` is needed");
+    }
+    match header("Origin") {
+        Some(origin) if !ours.iter().any(|ours| origin.eq_ignore_ascii_case(&format!("http://{}", ours))) => Err("unknown origin"),
+        _ => Ok(()),
+    }
+}
+
+fn backups(tw: &Taiwu, query: &str) -> (u16, String) {
+    let worlds = match query_param(query, "world") {
+        Some(world) => match world.parse::<usize>() {
+            Ok(world) if tw.worlds().contains(&world) => vec![world],
+            _ => return json(400, &ipc::Response::err(format!("invalid world `{}`", world))),
+        },
+        None => tw.worlds(),
+    };
+    let query = match backup_query(query) {
+        Ok(query) => query,
+        Err(message) => return json(400, &ipc::Response::err(message)),
+    };
+
+    let mut list = Vec::new();
+    for world in worlds {
+        match tw.query_backups(world, &query) {
+            Ok(backups) => list.push(WorldBackups { world, backups }),
+            Err(e) => return json(500, &ipc::Response::err(format!("list backups failed: {}", e))),
+        }
+    }
+    json(200, &list)
+}
+
+// `since`, `until`, `order=newest`, `limit`, `hash=1`, and of the saves `game_year`, `character` and
+// `sort=game-date`
+fn backup_query(query: &str) -> std::result::Result<BackupQuery, String> {
+    let time = |name| match query_param(query, name) {
+        Some(text) => match BackupQuery::parse_time(&text.replace("%20", " ").replace('+', " ")) {
+            Some(time) => Ok(Some(time)),
+            None => Err(format!("invalid `{}` time `{}`", name, text)),
+        },
+        None => Ok(None),
+    };
+    let limit = match query_param(query, "limit") {
+        Some(limit) => Some(limit.parse().map_err(|_| format!("invalid limit `{}`", limit))?),
+        None => None,
+    };
+    let game_year = match query_param(query, "game_year") {
+        Some(year) => Some(year.parse().map_err(|_| format!("invalid game_year `{}`", year))?),
+        None => None,
+    };
+    Ok(BackupQuery {
+        since: time("since")?,
+        until: time("until")?,
+        newest_first: query_param(query, "order") == Some("newest"),
+        limit,
+        hash: matches!(query_param(query, "hash"), Some("1" | "true")),
+        game_year,
+        character: query_param(query, "character").map(decode_param),
+        by_game_date: query_param(query, "sort") == Some("game-date"),
+    })
+}
+
+// `world`, `kind=backup,error` and the filters of `backup_query`
+fn history(tw: &Taiwu, query: &str) -> (u16, String) {
+    let world = match query_param(query, "world").map(str::parse::<usize>) {
+        Some(Ok(world)) => Some(world),
+        Some(Err(_)) => return json(400, &ipc::Response::err("invalid world")),
+        None => None,
+    };
+    let kinds = match query_param(query, "kind") {
+        Some(kinds) => match kinds.split(',').map(str::parse).collect::<std::result::Result<Vec<HistoryKind>, String>>() {
+            Ok(kinds) => kinds,
+            Err(message) => return json(400, &ipc::Response::err(message)),
+        },
+        None => Vec::new(),
+    };
+    let (since, until, newest_first, limit) = match backup_query(query) {
+        Ok(query) => (query.since, query.until, query.newest_first, query.limit),
+        Err(message) => return json(400, &ipc::Response::err(message)),
+    };
+    match tw.history(&HistoryFilter { since, until, kinds, world, newest_first, limit }) {
+        Ok(entries) => json(200, &entries),
+        Err(e) => json(500, &ipc::Response::err(format!("history failed: {}", e))),
+    }
+}
+
+fn diff(tw: &Taiwu, query: &str) -> (u16, String) {
+    let params = ["world", "a", "b"].map(|name| query_param(query, name));
+    let [Some(world), Some(a), Some(b)] = params else {
+        return json(400, &ipc::Response::err("`world`, `a` and `b` are needed"));
+    };
+    let Ok(world) = world.parse() else {
+        return json(400, &ipc::Response::err(format!("invalid world `{}`", world)));
+    };
+    match tw.diff_backups(world, a, b) {
+        Ok(diff) => json(200, &diff),
+        Err(e @ crate::TaiwuError::BackupNotFound(_)) => json(404, &ipc::Response::err(e.to_string())),
+        Err(e) => json(500, &ipc::Response::err(format!("diff failed: {}", e))),
+    }
+}
+
+fn restore(tw: &Taiwu, body: &str) -> (u16, String) {
+    let request: RestoreRequest = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(e) => return json(400, &ipc::Response::err(format!("invalid request: {}", e))),
+    };
+    let selector = match (request.id, request.at, request.before_label) {
+        (Some(id), None, None) => BackupSelector::Id(id),
+        (None, Some(at), None) => match BackupSelector::parse_at(&at) {
+            Some(at) => BackupSelector::At(at),
+            None => return json(400, &ipc::Response::err(format!("invalid time `{}`", at))),
+        },
+        (None, None, Some(label)) => BackupSelector::BeforeLabel(label),
+        _ => return json(400, &ipc::Response::err("give one of `id`, `at` and `before_label`")),
+    };
+    let result = tw.select_backup(request.world, &selector).and_then(|entry| {
+        match request.to {
+            Some(to) => tw.restore_to(request.world, &entry.id, to, request.overwrite),
+            None => tw.restore(request.world, &entry.id),
+        }
+        .map(|()| entry)
+    });
+    match result {
+        Ok(entry) => json(200, &ipc::Response::ok(format!("restored {}", entry.id))),
+        Err(e @ crate::TaiwuError::BackupNotFound(_)) => json(404, &ipc::Response::err(e.to_string())),
+        Err(e @ (crate::TaiwuError::WorldNotFound(_) | crate::TaiwuError::TargetSlotNotEmpty(_) | crate::TaiwuError::GameRunning | crate::TaiwuError::RestoreTargetLocked { .. } | crate::TaiwuError::BackupDamaged { .. })) => {
+            json(409, &ipc::Response::err(e.to_string()))
+        },
+        Err(e) => json(500, &ipc::Response::err(format!("restore failed: {}", e))),
+    }
+}
+
+fn label(tw: &Taiwu, body: &str) -> (u16, String) {
+    let request: LabelRequest = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(e) => return json(400, &ipc::Response::err(format!("invalid request: {}", e))),
+    };
+    match tw.set_label(request.world, &request.id, &request.label) {
+        Ok(()) => json(200, &ipc::Response::ok("labeled")),
+        Err(e @ crate::TaiwuError::BackupNotFound(_)) => json(404, &ipc::Response::err(e.to_string())),
+        Err(e) => json(500, &ipc::Response::err(format!("label failed: {}", e))),
+    }
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| match pair.split_once('=') {
+        Some((key, value)) if key == name => Some(value),
+        _ => None,
+    })
+}
+
+// `%e5%a4%aa` and `+` of a query value, e.g. a character's name
+fn decode_param(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+                continue;
+            },
+            (b'+', _) => bytes.push(b' '),
+            _ => bytes.push(byte),
+        }
+        rest = tail;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn json(code: u16, value: &impl Serialize) -> (u16, String) {
+    (code, serde_json::to_string(value).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_http::TestRequest;
+
+    fn request(method: Method, headers: &[(&str, &str)]) -> Request {
+        headers.iter()
+            .fold(TestRequest::new().with_method(method).with_path("/restore"), |request, (field, value)| {
+                request.with_header(Header::from_bytes(*field, *value).unwrap())
+            })
+            .into()
+    }
+
+    #[test]
+    fn requests_of_other_pages_are_refused() {
+        let host = ("Host", "127.0.0.1:8930");
+        let json = ("Content-Type", "application/json; charset=utf-8");
+        assert!(check_request(&request(Method::Get, &[host]), 8930).is_ok());
+        assert!(check_request(&request(Method::Get, &[("Host", "localhost:8930")]), 8930).is_ok());
+        assert!(check_request(&request(Method::Post, &[host, json]), 8930).is_ok());
+        assert!(check_request(&request(Method::Post, &[host, json, ("Origin", "http://127.0.0.1:8930")]), 8930).is_ok());
+
+        // a rebound DNS name, or no host at all
+        assert!(check_request(&request(Method::Get, &[("Host", "evil.example:8930")]), 8930).is_err());
+        assert!(check_request(&request(Method::Get, &[]), 8930).is_err());
+        // what a page can send without a preflight
+        assert!(check_request(&request(Method::Post, &[host]), 8930).is_err());
+        assert!(check_request(&request(Method::Post, &[host, ("Content-Type", "text/plain")]), 8930).is_err());
+        assert!(check_request(&request(Method::Post, &[host, json, ("Origin", "https://evil.example")]), 8930).is_err());
+    }
+
+    #[test]
+    fn query_values_are_decoded() {
+        assert_eq!(decode_param("%E5%A4%AA%E5%90%BE"), "太吾");
+        assert_eq!(decode_param("li+si%20"), "li si ");
+        assert_eq!(decode_param("100%"), "100%");
+        let query = backup_query("game_year=3&character=%E5%A4%AA&sort=game-date").unwrap();
+        assert_eq!((query.game_year, query.character.as_deref(), query.by_game_date), (Some(3), Some("太"), true));
+        assert!(backup_query("game_year=three").is_err());
+    }
+}
@@ -0,0 +1,72 @@
+// The texts of the tray, the notifications, the terminal UI and the command line, in Chinese
+// or English. `language` in the config picks one, otherwise the system locale does.
+//
+// Every text is written in both languages where it is used: `tr!("备份完成", "Backup done")`,
+// with format arguments after the two strings.
+
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+macro_rules! tr {
+    ($zh:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        match $crate::i18n::lang() {
+            $crate::i18n::Lang::Zh => format!($zh $(, $arg)*),
+            $crate::i18n::Lang::En => format!($en $(, $arg)*),
+        }
+    };
+}
+pub(crate) use tr;
+
+// `zh-CN`, `en-US` and the like, falls back to the system locale if not set or unknown
+pub fn init(language: Option<&str>) {
+    let lang = language.and_then(parse).unwrap_or_else(system_lang);
+    let _ = LANG.set(lang);
+}
+
+pub fn lang() -> Lang {
+    *LANG.get_or_init(system_lang)
+}
+
+fn parse(locale: &str) -> Option<Lang> {
+    let locale = locale.to_ascii_lowercase();
+    if locale.starts_with("zh") {
+        Some(Lang::Zh)
+    } else if locale.starts_with("en") {
+        Some(Lang::En)
+    } else {
+        None
+    }
+}
+
+// anything but chinese gets english
+fn system_lang() -> Lang {
+    system_locale().and_then(|locale| parse(&locale)).unwrap_or(Lang::En)
+}
+
+#[cfg(windows)]
+fn system_locale() -> Option<String> {
+    use windows_sys::Win32::Globalization::GetUserDefaultLocaleName;
+
+    // LOCALE_NAME_MAX_LENGTH
+    let mut name = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+    if len <= 1 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&name[..len as usize - 1]))
+}
+
+#[cfg(not(windows))]
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
@@ -0,0 +1,55 @@
+// Only one watcher per user, a second one would back up every save twice.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fs2::FileExt;
+use log::debug;
+
+use crate::{Result, TaiwuError};
+
+const INSTANCE_LOCK_FILE_NAME: &str = "instance.lock";
+
+// held until dropped, the OS releases the lock when the process dies
+#[derive(Debug)]
+pub struct InstanceGuard {
+    file: File,
+}
+
+impl InstanceGuard {
+    pub fn acquire() -> Result<InstanceGuard> {
+        let appdata = crate::get_appdata_root_default()?;
+        fs::create_dir_all(&appdata)?;
+
+        let path = appdata.join(INSTANCE_LOCK_FILE_NAME);
+        let mut file = fs::OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        if file.try_lock_exclusive().is_err() {
+            return Err(TaiwuError::AlreadyRunning);
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        debug!("acquired instance lock `{}`", path.display());
+
+        Ok(InstanceGuard { file })
+    }
+
+    // for an instance started by the one still quitting
+    pub fn wait(timeout: Duration) -> Result<InstanceGuard> {
+        let start = Instant::now();
+        loop {
+            match InstanceGuard::acquire() {
+                Err(TaiwuError::AlreadyRunning) if start.elapsed() < timeout => thread::sleep(Duration::from_millis(200)),
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
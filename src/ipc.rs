@@ -0,0 +1,245 @@
+// Control a running instance from other processes (e.g. `taiwu ctl status`).
+//
+// One JSON request per line, answered by one JSON response line, over a named pipe
+// on windows and a unix socket in the appdata folder elsewhere. Without the `ipc` feature there
+// is no channel, only the messages the HTTP API shares.
+
+#[cfg(feature = "ipc")]
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+#[cfg(feature = "ipc")]
+use std::sync::Arc;
+#[cfg(feature = "ipc")]
+use std::thread::{self, JoinHandle};
+
+#[cfg(feature = "ipc")]
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+#[cfg(feature = "ipc")]
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{LogLevel, Taiwu, TaiwuStatus};
+#[cfg(feature = "ipc")]
+use crate::{BackupSelector, Result, TaiwuError};
+
+#[cfg(all(feature = "ipc", not(windows)))]
+const IPC_SOCKET_FILE_NAME: &str = "control.sock";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum Request {
+    BackupNow,
+    Pause,
+    Resume,
+    Status,
+    SetLogLevel { level: LogLevel },
+    Quit,
+    // restores write the save, which the watcher of the instance only knows not to back up if it
+    // wrote it itself; without `id` the latest good backup, `to` and `overwrite` as `taiwu restore`
+    Restore {
+        world: usize,
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default)]
+        to: Option<usize>,
+        #[serde(default)]
+        overwrite: bool,
+    },
+    UndoRestore { world: usize },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub version: String,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub log_level: LogLevel,
+    #[serde(default)]
+    pub portable: bool,
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub game_root: PathBuf,
+    pub backup_root: PathBuf,
+    #[serde(flatten)]
+    pub state: TaiwuStatus,
+}
+
+impl Status {
+    pub fn of(tw: &Taiwu) -> Status {
+        Status {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            dry_run: tw.is_dry_run(),
+            log_level: LogLevel::current(),
+            portable: crate::is_portable(),
+            profile: tw.profile().map(str::to_owned),
+            game_root: tw.game_root(),
+            backup_root: tw.backup_root(),
+            state: tw.status(),
+        }
+    }
+}
+
+impl Response {
+    #[cfg(any(feature = "ipc", feature = "http"))]
+    pub(crate) fn ok(message: impl Into<String>) -> Response {
+        Response { ok: true, message: message.into(), status: None }
+    }
+
+    #[cfg(any(feature = "ipc", feature = "http"))]
+    pub(crate) fn err(message: impl Into<String>) -> Response {
+        Response { ok: false, message: message.into(), status: None }
+    }
+}
+
+// serve requests on a background thread for as long as the process lives,
+// `on_quit` is how the app itself shuts down (the tray and the daemon differ)
+#[cfg(feature = "ipc")]
+pub fn serve(tw: Arc<Taiwu>, on_quit: impl Fn() + Send + 'static) -> Result<JoinHandle<()>> {
+    let name = socket_name()?;
+
+    // a socket file left by a crashed instance, safe to remove since we are the single instance
+    #[cfg(not(windows))]
+    let _ = std::fs::remove_file(&name);
+
+    let listener = LocalSocketListener::bind(name.as_str())?;
+    info!("Control channel listening on `{}`", name);
+
+    let handle = thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_client(&tw, &on_quit, stream) {
+                        debug!("control client error: {}", e);
+                    }
+                },
+                Err(e) => warn!("control channel accept error: {}", e),
+            }
+        }
+    });
+    Ok(handle)
+}
+
+#[cfg(feature = "ipc")]
+fn handle_client(tw: &Taiwu, on_quit: &impl Fn(), stream: LocalSocketStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let response = match serde_json::from_str::<Request>(line.trim()) {
+            Ok(request) => {
+                debug!("control request {:?}", request);
+                let quit = request == Request::Quit;
+                let response = handle_request(tw, request);
+                if quit {
+                    write_line(reader.get_mut(), &response)?;
+                    on_quit();
+                    return Ok(());
+                }
+                response
+            },
+            Err(e) => Response::err(format!("invalid request: {}", e)),
+        };
+        write_line(reader.get_mut(), &response)?;
+        line.clear();
+    }
+    Ok(())
+}
+
+#[cfg(feature = "ipc")]
+fn handle_request(tw: &Taiwu, request: Request) -> Response {
+    match request {
+        Request::BackupNow => match tw.backup_once().into_result() {
+            Ok(report) => Response::ok(format!("backup done, {} files", report.done.len())),
+            Err(e) => Response::err(format!("backup failed: {}", e)),
+        },
+        Request::Pause => {
+            tw.pause();
+            Response::ok("paused")
+        },
+        Request::Resume => {
+            tw.resume();
+            Response::ok("resumed")
+        },
+        Request::Status => Response { status: Some(Status::of(tw)), ..Response::ok("running") },
+        Request::SetLogLevel { level } => {
+            level.apply();
+            Response::ok(format!("log level is {}", level))
+        },
+        Request::Quit => Response::ok("quitting"),
+        Request::Restore { world, id, to, overwrite } => {
+            let result = match id {
+                Some(id) => tw.select_backup(world, &BackupSelector::Id(id)).and_then(|entry| {
+                    match to {
+                        Some(to) => tw.restore_to(world, &entry.id, to, overwrite),
+                        None => tw.restore(world, &entry.id),
+                    }
+                    .map(|()| entry)
+                }),
+                None => tw.restore_latest_good(world),
+            };
+            match result {
+                Ok(entry) => Response::ok(format!("restored {}", entry.id)),
+                Err(e) => Response::err(format!("restore failed: {}", e)),
+            }
+        },
+        Request::UndoRestore { world } => match tw.undo_restore(world) {
+            Ok(record) => Response::ok(format!("undid the restore of backup {} of world {}", record.backup, record.from_world)),
+            Err(e) => Response::err(format!("undo failed: {}", e)),
+        },
+    }
+}
+
+// send one request to the running instance and wait for its response
+#[cfg(feature = "ipc")]
+pub fn send(request: Request) -> Result<Response> {
+    let name = socket_name()?;
+    let stream = LocalSocketStream::connect(name.as_str())?;
+    let mut reader = BufReader::new(stream);
+    write_line(reader.get_mut(), &request)?;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let response = serde_json::from_str(line.trim()).map_err(io::Error::from)?;
+    Ok(response)
+}
+
+// the error of `send` when nothing listens: no socket or pipe, or a socket left by a crash
+#[cfg(feature = "ipc")]
+pub fn is_not_running(e: &TaiwuError) -> bool {
+    matches!(e, TaiwuError::IoError(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused))
+}
+
+#[cfg(feature = "ipc")]
+fn write_line(stream: &mut LocalSocketStream, value: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_string(value).map_err(io::Error::from)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    stream.flush()
+}
+
+// named pipes are global, so one per user (and per folder of a portable copy)
+#[cfg(all(feature = "ipc", windows))]
+fn socket_name() -> Result<String> {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    if crate::is_portable() {
+        let appdata = crate::get_appdata_root_default()?;
+        let hash = crate::store::sha256_bytes(appdata.to_string_lossy().as_bytes());
+        return Ok(format!("@taiwu-backup-{}-{}", user, &hash[..16]));
+    }
+    Ok(format!("@taiwu-backup-{}", user))
+}
+
+#[cfg(all(feature = "ipc", not(windows)))]
+fn socket_name() -> Result<String> {
+    let appdata = crate::get_appdata_root_default()?;
+    std::fs::create_dir_all(&appdata)?;
+    Ok(appdata.join(IPC_SOCKET_FILE_NAME).to_string_lossy().into_owned())
+}
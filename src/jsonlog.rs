@@ -0,0 +1,97 @@
+// `log_format = "json"`, one JSON object per line for log shipping tools, with the
+// key-values of the record (e.g. world, bytes, duration_ms) as fields next to the message.
+
+use std::io::Write;
+use std::sync::Mutex;
+
+use log::kv::{self, Key, Value, VisitSource, VisitValue};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value as Json};
+
+struct JsonLogger<W> {
+    level: LevelFilter,
+    writer: Mutex<W>,
+}
+
+pub fn init(level: LevelFilter, writer: impl Write + Send + 'static) -> Result<(), log::SetLoggerError> {
+    log::set_boxed_logger(Box::new(JsonLogger { level, writer: Mutex::new(writer) }))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+impl<W: Write + Send> Log for JsonLogger<W> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut fields = Map::new();
+        fields.insert("time".to_owned(), chrono::offset::Local::now().to_rfc3339().into());
+        fields.insert("level".to_owned(), record.level().as_str().into());
+        fields.insert("target".to_owned(), record.target().into());
+        fields.insert("message".to_owned(), record.args().to_string().into());
+        let _ = record.key_values().visit(&mut Fields(&mut fields));
+
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{}", Json::Object(fields));
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+struct Fields<'a>(&'a mut Map<String, Json>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.insert(key.as_str().to_owned(), to_json(&value));
+        Ok(())
+    }
+}
+
+// numbers and bools stay what they are, everything else is its display text
+fn to_json(value: &Value) -> Json {
+    let mut json = Json::Null;
+    if value.visit(ToJson(&mut json)).is_err() {
+        json = value.to_string().into();
+    }
+    json
+}
+
+struct ToJson<'a>(&'a mut Json);
+
+impl<'v> VisitValue<'v> for ToJson<'_> {
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        *self.0 = value.to_string().into();
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        *self.0 = Json::Null;
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        *self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        *self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        *self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        *self.0 = value.into();
+        Ok(())
+    }
+}
@@ -1,30 +1,266 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::io;
 use std::fs;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use log::{trace, debug, info, warn, error};
 use thiserror::Error;
-use notify::{event, RecommendedWatcher, RecursiveMode, Watcher, Config, Event};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher, Event};
 use directories::BaseDirs;
+use chrono::Timelike;
 
+mod archive;
+pub mod autostart;
+mod bus;
+mod catalog;
+mod chunks;
+mod config;
+mod cron;
+mod diff;
+#[cfg(feature = "email")]
+pub mod email;
+mod events;
+mod extra;
+mod filter;
+mod game;
 mod game_root;
+pub mod heartbeat;
+mod history;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "ipc")]
+mod instance;
+mod longpath;
+mod manifest;
+mod metrics;
+#[cfg(feature = "config_edit")]
+mod migrate;
+pub mod notifier;
+pub mod ipc;
+mod outbox;
+mod pack;
+mod process;
+mod priority;
+mod prune;
+mod queue;
+mod shutdown;
+mod stats;
+mod status;
+mod store;
+mod storelock;
+mod suppress;
+#[cfg(any(test, feature = "test_support"))]
+pub mod test_support;
+mod verify;
+mod vss;
+mod watcher;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 
-use game_root::GameRoot;
+use game::game;
+use game_root::{GameRoot, GameSetup};
+pub use game::{set_game_profile, GameProfile, TaiwuGame};
+pub use game_root::{DetectMethod, GameRootDiagnosis};
+use catalog::Catalog;
+use manifest::{Manifest, NewBackup};
+use events::Change;
+use extra::ExtraFiles;
+use filter::SaveFilter;
+use outbox::{Outbox, OutboxJob};
+use history::History;
+use process::GameMonitor;
+use queue::{FolderLocks, Job, JobQueue};
+use metrics::Metrics;
+use cron::Schedule;
+use shutdown::Shutdown;
+use storelock::{StoreLock, StoreLockGuard};
+use suppress::SelfWrites;
+use watcher::{AnyWatcher, MissedChanges};
+
+pub use bus::{CopyProgress, StoreStatus, TaiwuEvent};
+pub use config::{save_active_profile, set_config_overrides, Config, ConfigOverrides, EmailConfig, LogFormat, LogLevel, NotificationMode, NotifierBackend, NotifierConfig, ProfileConfig, SmtpSecurity, StoreFormat, WatcherBackend, WebhookFormat, WorldConfig, WorldSettings};
+#[cfg(feature = "config_edit")]
+pub use config::set_config_value;
+pub use diff::BackupDiff;
+pub use history::{HistoryEntry, HistoryFilter, HistoryKind};
+#[cfg(feature = "ipc")]
+pub use instance::InstanceGuard;
+pub use manifest::RestoreRecord;
+#[cfg(feature = "config_edit")]
+pub use migrate::{migrate_store, MigrateReport};
+pub use notifier::{Notification, NotificationKind, Notifier};
+pub use prune::{parse_age, parse_size, PrunePolicy, PruneReport};
+pub use stats::{CopyTimes, Stats, WorldStats};
+pub use status::{LastBackup, LastError, TaiwuStatus};
+pub use store::{BackupEntry, BackupQuery, BackupSelector};
+pub use pack::PackReport;
+pub use archive::{ArchiveInfo, Imported};
+pub use verify::{VerifyProblem, VerifyReport};
+pub use storelock::set_break_lock;
 
 pub type Result<T> = std::result::Result<T, TaiwuError>;
 
 const APPDATA_FOLDER_NAME: &'static str = "TaiwuBackup";
 const BACKUP_FOLDER_NAME: &'static str = "BackupData";
-const TAIWU_GAME_SAVE_ROOT_NAME: &'static str = "Save";
-const TAIWU_GAME_SAVE_FILE_NAME: &'static str = "local.sav";
-const TAIWU_GAME_SAVE_WORLD_NUMBER_MAX: usize = 5;
+const OUTBOX_FOLDER_NAME: &str = "Outbox";
+const LOG_FOLDER_NAME: &str = "logs";
+// next to the executable, everything is kept there instead of in the appdata folder
+const PORTABLE_FLAG_FILE_NAME: &str = "portable.flag";
+
+static PORTABLE: AtomicBool = AtomicBool::new(false);
+// see `set_appdata_root`
+static APPDATA_ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+// backups of the extra game files, by their path in the game root
+const EXTRA_FOLDER_NAME: &str = "Extra";
+// backups of world folders deleted in the game, see `archive_removed_worlds`
+const ARCHIVE_FOLDER_NAME: &str = "Archive";
+// given to the backup a world is frozen to if it has no label, so that it is never pruned
+const FROZEN_LABEL: &str = "frozen";
+// the saves replaced by restores, see `pre_restore_keep`
+const PRE_RESTORE_FOLDER_NAME: &str = "PreRestore";
+const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+const GAME_PROCESS_POLL_INTERVAL: Duration = Duration::from_secs(3);
+// how often the watcher is checked for changes it missed, and restarted at most
+const WATCHER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// how long quitting waits for the copies still running
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+// the saves and extra files of `backup_once` are copied by this many threads at most
+const BACKUP_WORKERS: usize = 4;
+// changed files waiting for the watch worker, the watcher blocks above it
+const JOB_QUEUE_CAPACITY: usize = 64;
+// how often the maintenance schedules are looked at, more than once a minute
+const MAINTENANCE_TICK: Duration = Duration::from_secs(20);
+// events of a file a restore wrote are not backed up for this long after it (plus a poll interval)
+const SELF_WRITE_WINDOW: Duration = Duration::from_secs(5);
+// a smaller save was left by the game crashing in the middle of writing it, a new world takes
+// hundreds of KB
+const TRUNCATED_SAVE_SIZE: u64 = 1024;
+// the recent backups of a world a save is compared with for `shrink_alert_percent`
+const SIZE_HISTORY_LEN: usize = 5;
+// how often a copy reports how far it got, shorter ones never do
+const COPY_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+// how often `flush` looks whether the worker is done
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Debug)]
 pub struct Taiwu {
+    profile: Option<String>,
     game_root: PathBuf,
+    // the first one is the game's own
+    save_roots: Vec<PathBuf>,
+    detect_method: DetectMethod,
     backup_root: PathBuf,
-    watcher: Mutex<Option<RecommendedWatcher>>,
+    mirror_roots: Vec<PathBuf>,
+    world_settings: BTreeMap<usize, WorldSettings>,
+    save_filter: SaveFilter,
+    extra_files: ExtraFiles,
+    watcher: Mutex<Option<AnyWatcher>>,
+    watcher_backend: WatcherBackend,
+    poll_interval: Duration,
+    // also back up the changed saves this often while watching, without events
+    snapshot_interval: Option<Duration>,
+    verify_schedule: Option<Schedule>,
+    prune_schedule: Option<(Schedule, PrunePolicy)>,
+    pack_schedule: Option<Schedule>,
+    // backups older than this are packed, see `pack`
+    pack_after: Duration,
+    // `WatcherBackend::Auto` switched to polling
+    poll_fallback: AtomicBool,
+    // received from the watcher so far
+    events_seen: AtomicU64,
+    // unix seconds of the last one, 0 before the first
+    last_event: AtomicI64,
+    // it sent an error, restarted by the game monitor thread
+    watcher_failed: AtomicBool,
+    // the system woke up from sleep, the watcher is restarted the same way
+    resumed: AtomicBool,
+    // set while watch() runs, both taken by stop()
+    unwatch_signal: Mutex<Option<mpsc::Sender<()>>>,
+    stop_signal: Mutex<Option<mpsc::Sender<WatchSignal>>>,
+    only_while_game_running: bool,
+    archive_removed_worlds: bool,
+    pre_restore_keep: usize,
+    // keep watching, but changed saves are not backed up
+    paused: AtomicBool,
+    game_running: AtomicBool,
+    bus: bus::EventBus,
+    outbox: Outbox,
+    history: History,
+    lock_retry: LockRetry,
+    // bytes, oldest unlabeled backups of the primary store are pruned above it
+    quota: Option<u64>,
+    // scanned when first needed
+    catalog: Mutex<Option<Catalog>>,
+    // bytes to leave free on a store volume
+    min_free_space: u64,
+    dry_run: bool,
+    // fsync the backups and their folders
+    durable: bool,
+    // copy and pack at the lowest CPU and disk priority, see `priority.rs`
+    background_priority: bool,
+    // new backups go into the stores as chunk lists
+    chunked: bool,
+    shutdown: Shutdown,
+    metrics: Metrics,
+    self_writes: SelfWrites,
+    store_lock: StoreLock,
+    // 0 disables it
+    shrink_alert_percent: u64,
+    // worlds whose save was found truncated, until a good one is written or restored
+    truncated: Mutex<BTreeSet<usize>>,
+    // that took longer than `COPY_PROGRESS_INTERVAL` so far
+    copies: Mutex<Vec<CopyProgress>>,
+    // of the worker, set while watch() runs
+    job_queue: Mutex<Option<JobQueue>>,
+    folder_locks: FolderLocks,
+    // of a backup or the watcher, see `record_error`
+    last_error: Mutex<Option<LastError>>,
+}
+
+// what the loop of watch() gets, the watcher events until stop() sends `Stop`
+enum WatchSignal {
+    Event(notify::Result<Event>),
+    Stop,
+}
+
+// how watch() ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEnd {
+    // stopped, and if shutting down the backups and restores running were done
+    Clean,
+    // shutting down, these were still running after `SHUTDOWN_TIMEOUT`
+    Unfinished { in_flight: usize },
+}
+
+// of `backup_once`, every file is tried even if some fail
+#[derive(Debug, Default)]
+pub struct BackupReport {
+    pub done: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, TaiwuError)>,
+}
+
+impl BackupReport {
+    // the first failure as the error
+    pub fn into_result(mut self) -> Result<BackupReport> {
+        if self.failed.is_empty() {
+            Ok(self)
+        } else {
+            Err(self.failed.remove(0).1)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LockRetry {
+    attempts: u32,
+    delay: Duration,
+    shadow_copy: bool,
 }
 
 
@@ -32,8 +268,71 @@ pub struct Taiwu {
 pub enum TaiwuError {
     #[error("game root path not found")]
     GameRootNotFound,
+    #[error("{0}")]
+    GameRootInvalid(GameRootDiagnosis),
     #[error("defatul backup destination path not available")]
     BackupRootDefaultNotAvailable,
+    #[error("user home directory not available")]
+    HomeDirNotAvailable,
+    #[error("another instance is already running")]
+    AlreadyRunning,
+    #[error("not enough free space in `{}`: {available} bytes free, {required} needed", root.display())]
+    InsufficientSpace { root: PathBuf, available: u64, required: u64 },
+    #[error("world {0} already has a save, overwrite it explicitly")]
+    TargetSlotNotEmpty(usize),
+    #[error("world {0} does not exist")]
+    WorldNotFound(usize),
+    #[error("the game is running, quit it first")]
+    GameRunning,
+    #[error("shutting down, not started")]
+    ShuttingDown,
+    #[error("backup `{0}` not found")]
+    BackupNotFound(String),
+    #[error("the save of world {world} `{}` is only {size} bytes, the game may have crashed while writing it", path.display())]
+    SaveTruncated { world: usize, path: PathBuf, size: u64 },
+    #[error("backup of `{}` to `{}` failed", src.display(), dst.display())]
+    BackupCopyFailed { world: Option<usize>, src: PathBuf, dst: PathBuf, #[source] source: io::Error },
+    #[error("could not start watching `{}`", path.display())]
+    WatchStartFailed { path: PathBuf, #[source] source: notify::Error },
+    #[error("the save of world {world} `{}` is locked, quit the game first", path.display())]
+    RestoreTargetLocked { world: usize, path: PathBuf, #[source] source: io::Error },
+    #[error("world {0} has no restore to undo")]
+    NothingToUndo(usize),
+    #[error("could not keep a copy of the save of world {world} before restoring")]
+    PreRestoreFailed { world: usize, #[source] source: io::Error },
+    #[error("backup `{}` is damaged, {problem}, not restored (see `taiwu verify`)", path.display())]
+    BackupDamaged { path: PathBuf, problem: String },
+    #[error("the save of world {world} `{}` can not be written", path.display())]
+    RestoreTargetNotWritable { world: usize, path: PathBuf, #[source] source: io::Error },
+    #[error("restore of `{}` to `{}` failed", src.display(), dst.display())]
+    RestoreFailed { world: usize, src: PathBuf, dst: PathBuf, #[source] source: io::Error },
+    #[error("`{}` can not be imported, {message}", path.display())]
+    ArchiveInvalid { path: PathBuf, message: String },
+    #[error("could not launch the game")]
+    LaunchFailed(#[source] io::Error),
+    #[error("could not export to `{}`", path.display())]
+    ExportFailed { path: PathBuf, #[source] source: io::Error },
+    #[error("could not delete backup `{}`", path.display())]
+    BackupDeleteFailed { path: PathBuf, #[source] source: io::Error },
+    #[error("the backup store is locked by {holder}, if it is not running anymore break the lock with `--force`")]
+    StoreLocked { path: PathBuf, holder: String },
+    #[error("can not move the store to `{}`, {message}", path.display())]
+    MigrateTargetInvalid { path: PathBuf, message: String },
+    #[error("the copy `{}` of `{}` differs from it, the store was not moved", dst.display(), src.display())]
+    MigrateVerifyFailed { src: PathBuf, dst: PathBuf },
+    #[error("the backup root is set by {by}, it would still be the old one after the store was moved; set `backup_root` in the config file instead")]
+    MigrateRootOverridden { by: String },
+    #[error("could not read config file `{}`", path.display())]
+    ConfigReadFailed { path: PathBuf, #[source] source: io::Error },
+    #[cfg(feature = "globs")]
+    #[error("glob pattern is invalid")]
+    GlobInvalid(#[from] globset::Error),
+    #[error("config file is invalid")]
+    ConfigInvalid(#[source] toml::de::Error),
+    #[error("`{key}` in the config file is invalid: {message}")]
+    ConfigValueInvalid { key: String, message: String },
+    #[error("a `TAIWU_BACKUP_*` environment variable is invalid: {0}")]
+    EnvInvalid(String),
     #[error("IO error")]
     IoError(#[from] io::Error),
     #[error("notify error")]
@@ -42,206 +341,2375 @@ pub enum TaiwuError {
     Unknown,
 }
 
+impl TaiwuError {
+    // worth trying again later as it is, e.g. once the game is closed or a drive is back
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            TaiwuError::BackupCopyFailed { .. }
+                | TaiwuError::RestoreTargetLocked { .. }
+                | TaiwuError::InsufficientSpace { .. }
+                | TaiwuError::GameRunning
+                | TaiwuError::AlreadyRunning
+                | TaiwuError::StoreLocked { .. }
+        )
+    }
+}
+
+// the config file (or a given `Config`) with some of it overridden, like the command line does
+#[derive(Debug, Clone, Default)]
+pub struct TaiwuBuilder {
+    config: Option<Config>,
+    overrides: ConfigOverrides,
+    dry_run: bool,
+}
+
+impl TaiwuBuilder {
+    // instead of loading the config file
+    pub fn config(mut self, config: Config) -> TaiwuBuilder {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn config_file(mut self, path: impl AsRef<Path>) -> TaiwuBuilder {
+        self.overrides.config_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    pub fn profile(mut self, name: impl Into<String>) -> TaiwuBuilder {
+        self.overrides.profile = Some(name.into());
+        self
+    }
+
+    pub fn game_root(mut self, path: impl AsRef<Path>) -> TaiwuBuilder {
+        self.overrides.game_root = Some(path.as_ref().to_owned());
+        self
+    }
+
+    pub fn backup_root(mut self, path: impl AsRef<Path>) -> TaiwuBuilder {
+        self.overrides.backup_root = Some(path.as_ref().to_owned());
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> TaiwuBuilder {
+        self.dry_run = dry_run;
+        self
+    }
+
+    // what `build` uses
+    pub fn load_config(&self) -> Result<Config> {
+        let mut config = match &self.config {
+            Some(config) => config.clone(),
+            None => Config::load_with(&self.overrides)?,
+        };
+        self.overrides.apply(&mut config);
+        config.dry_run |= self.dry_run;
+        Ok(config)
+    }
+
+    pub fn build(&self) -> Result<Taiwu> {
+        Taiwu::with_config(&self.load_config()?)
+    }
+}
+
 impl Taiwu {
     pub fn new() -> Result<Taiwu> {
-        if let Some(root) = GameRoot::auto() {
-            let game_root = root.path().to_owned();
-            let backup_root = get_backup_root_default()?;
-            let watcher = Mutex::new(None);
-            Ok(Taiwu { game_root, backup_root, watcher })
-        } else {
-            Err(TaiwuError::GameRootNotFound)
-        }
+        Taiwu::with_config(&Config::default())
+    }
+
+    pub fn builder() -> TaiwuBuilder {
+        TaiwuBuilder::default()
     }
 
     pub fn with_path(path: impl AsRef<Path>) -> Result<Taiwu> {
-        if let Some(root) = GameRoot::new(path) {
+        let config = Config { game_root: Some(path.as_ref().to_owned()), ..Config::default() };
+        Taiwu::with_config(&config)
+    }
+
+    pub fn with_config(config: &Config) -> Result<Taiwu> {
+        let config = &config.profile_applied()?;
+        let root = match config.game_root {
+            Some(ref path) => Some(GameRoot::new(path).map_err(TaiwuError::GameRootInvalid)?),
+            None => GameRoot::auto(),
+        };
+        if let Some(root) = root {
             let game_root = root.path().to_owned();
-            let backup_root = get_backup_root_default()?;
+            let save_roots = root.save_roots(&config.save_roots, config.steam_user.as_deref());
+            let detect_method = root.method();
+            let backup_root = match config.backup_root {
+                Some(ref path) => path.clone(),
+                None => get_backup_root_default()?,
+            };
+            let mirror_roots = config.mirror_roots.clone();
+            let world_settings = config.world_settings()?;
+            let save_filter = SaveFilter::new(&config.save_include, &config.save_exclude)?;
+            let schedule = |key: &str, text: &str| {
+                Schedule::parse(text).map_err(|message| TaiwuError::ConfigValueInvalid { key: key.to_owned(), message })
+            };
+            let verify_schedule = config.verify.as_deref().map(|text| schedule("verify", text)).transpose()?;
+            let prune_schedule = match config.prune.as_deref() {
+                Some(text) => {
+                    let policy = config.prune_policy()?;
+                    if policy.is_empty() {
+                        return Err(TaiwuError::ConfigValueInvalid {
+                            key: "prune".to_owned(),
+                            message: "give `prune_keep_last`, `prune_older_than` or `prune_max_size` too".to_owned(),
+                        });
+                    }
+                    Some((schedule("prune", text)?, policy))
+                },
+                None => None,
+            };
+            let pack_schedule = match config.pack.as_deref() {
+                Some(_) if !cfg!(feature = "pack") => {
+                    warn!("`pack` is set, but this build has no `pack` feature, ignored");
+                    None
+                },
+                text => text.map(|text| schedule("pack", text)).transpose()?,
+            };
+            let pack_after = Duration::from_secs(config.pack_after_days * 24 * 3600);
+            let extra_files = ExtraFiles::new(&game_root, &config.extra_files)?;
             let watcher = Mutex::new(None);
-            Ok(Taiwu { game_root, backup_root, watcher })
+            let watcher_backend = config.watcher;
+            let poll_interval = Duration::from_secs(config.poll_interval_secs.max(1));
+            let snapshot_interval = (config.snapshot_interval_mins > 0).then(|| Duration::from_secs(config.snapshot_interval_mins * 60));
+            let poll_fallback = AtomicBool::new(false);
+            let events_seen = AtomicU64::new(0);
+            let last_event = AtomicI64::new(0);
+            let watcher_failed = AtomicBool::new(false);
+            let resumed = AtomicBool::new(false);
+            let unwatch_signal = Mutex::new(None);
+            let stop_signal = Mutex::new(None);
+            let only_while_game_running = config.only_while_game_running && cfg!(feature = "process");
+            if config.only_while_game_running && !only_while_game_running {
+                warn!("`only_while_game_running` is set, but this build has no `process` feature, ignored");
+            }
+            let archive_removed_worlds = config.archive_removed_worlds;
+            let pre_restore_keep = config.pre_restore_keep;
+            if config.shrink_alert_percent >= 100 {
+                return Err(TaiwuError::ConfigValueInvalid {
+                    key: "shrink_alert_percent".to_owned(),
+                    message: "give a percent below 100, or 0 to disable it".to_owned(),
+                });
+            }
+            let shrink_alert_percent = config.shrink_alert_percent;
+            let bus = bus::EventBus::default();
+            let outbox = Outbox::open(get_appdata_root_default()?.join(OUTBOX_FOLDER_NAME));
+            let history = History::open(&get_appdata_root_default()?, !config.dry_run);
+            let lock_retry = LockRetry {
+                attempts: config.lock_retry_attempts,
+                delay: Duration::from_millis(config.lock_retry_delay_ms),
+                shadow_copy: config.vss_fallback,
+            };
+            if lock_retry.shadow_copy && !cfg!(all(windows, feature = "vss")) {
+                warn!("`vss_fallback` is set, but this build has no volume shadow copy support");
+            }
+            let paused = AtomicBool::new(false);
+            let game_running = AtomicBool::new(false);
+            let quota = config.store_quota_mb.map(|mb| mb * 1024 * 1024);
+            let catalog = Mutex::new(None);
+            let min_free_space = config.min_free_space_mb * 1024 * 1024;
+            let dry_run = config.dry_run;
+            let durable = config.durable;
+            let background_priority = config.background_priority;
+            let chunked = match config.store_format {
+                StoreFormat::Chunked if !cfg!(feature = "chunked") => {
+                    warn!("`store_format` is `chunked`, but this build has no `chunked` feature, backups are written as files");
+                    false
+                },
+                format => format == StoreFormat::Chunked,
+            };
+            let shutdown = Shutdown::default();
+            let metrics = Metrics::default();
+            let self_writes = SelfWrites::default();
+            let store_lock = StoreLock::new(&backup_root, !dry_run);
+            if dry_run {
+                info!("Dry run, nothing will be written");
+            }
+            Ok(Taiwu {
+                profile: config.profile.clone(),
+                game_root,
+                save_roots,
+                detect_method,
+                backup_root,
+                mirror_roots,
+                world_settings,
+                save_filter,
+                extra_files,
+                watcher,
+                watcher_backend,
+                poll_interval,
+                snapshot_interval,
+                verify_schedule,
+                prune_schedule,
+                pack_schedule,
+                pack_after,
+                poll_fallback,
+                events_seen,
+                last_event,
+                watcher_failed,
+                resumed,
+                unwatch_signal,
+                stop_signal,
+                only_while_game_running,
+                archive_removed_worlds,
+                pre_restore_keep,
+                paused,
+                game_running,
+                bus,
+                outbox,
+                history,
+                lock_retry,
+                quota,
+                catalog,
+                min_free_space,
+                dry_run,
+                durable,
+                background_priority,
+                chunked,
+                shutdown,
+                metrics,
+                self_writes,
+                store_lock,
+                shrink_alert_percent,
+                truncated: Mutex::new(BTreeSet::new()),
+                copies: Mutex::new(Vec::new()),
+                job_queue: Mutex::new(None),
+                folder_locks: FolderLocks::default(),
+                last_error: Mutex::new(None),
+            })
         } else {
             Err(TaiwuError::GameRootNotFound)
         }
     }
 
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
     pub fn game_root(&self) -> PathBuf {
         self.game_root.clone()
     }
 
+    pub fn detect_method(&self) -> DetectMethod {
+        self.detect_method
+    }
+
     pub fn backup_root(&self) -> PathBuf {
         self.backup_root.clone()
     }
 
-    fn save_root(&self) -> PathBuf {
-        self.game_root.join(TAIWU_GAME_SAVE_ROOT_NAME)
+    pub fn mirror_roots(&self) -> Vec<PathBuf> {
+        self.mirror_roots.clone()
     }
 
+    pub fn pending_retries(&self) -> usize {
+        self.outbox.len()
+    }
+
+    // the copies going on that took a while so far
+    pub fn copies(&self) -> Vec<CopyProgress> {
+        self.copies.lock().unwrap().clone()
+    }
+
+    // the newest backups are looked up in the stores, the rest is what it has in memory
+    pub fn status(&self) -> TaiwuStatus {
+        TaiwuStatus {
+            watching: self.is_watching(),
+            polling: self.is_polling(),
+            paused: self.is_paused(),
+            game_running: self.is_game_running(),
+            last_event: self.last_event(),
+            last_backups: self.last_backups().into_iter().map(|(world, time)| LastBackup { world, time }).collect(),
+            queued_jobs: self.job_queue.lock().unwrap().as_ref().map_or(0, JobQueue::len),
+            pending_retries: self.pending_retries(),
+            copying: self.copies(),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+
+    // shown by the status, and kept in the history
+    fn record_error(&self, world: Option<usize>, message: impl fmt::Display) {
+        let message = message.to_string();
+        self.set_last_error(&message);
+        self.record_history(HistoryEntry::new(HistoryKind::Error, world, message));
+    }
+
+    fn set_last_error(&self, message: impl fmt::Display) {
+        let time = chrono::offset::Local::now().timestamp();
+        *self.last_error.lock().unwrap() = Some(LastError { time, message: message.to_string() });
+    }
+
+    fn record_history(&self, mut entry: HistoryEntry) {
+        entry.profile = self.profile.clone();
+        self.history.add(entry);
+    }
+
+    // of this profile, see `HistoryFilter`
+    pub fn history(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>> {
+        let mut entries: Vec<_> = self.history.read()?
+            .into_iter()
+            .filter(|entry| entry.profile == self.profile && filter.matches(entry))
+            .collect();
+        if let Some(limit) = filter.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+        if filter.newest_first {
+            entries.reverse();
+        }
+        Ok(entries)
+    }
+
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<TaiwuEvent> {
+        self.bus.subscribe()
+    }
+
+    // the first one that has the world, the game's own for a new one
     fn save_file(&self, world: usize) -> PathBuf {
-        self.save_root().join(format!("world_{}", world)).join(TAIWU_GAME_SAVE_FILE_NAME)
+        let folder_name = world_folder_name(world);
+        let root = self.save_roots.iter().find(|root| root.join(&folder_name).is_dir()).unwrap_or(&self.save_roots[0]);
+        root.join(folder_name).join(game().save_file_name())
+    }
+
+    // the save root `path` is in
+    fn save_root_of(&self, path: &Path) -> Option<&Path> {
+        self.save_roots.iter().map(PathBuf::as_path).filter(|root| path.starts_with(root)).max_by_key(|root| root.as_os_str().len())
     }
 
-    pub fn backup_once_for_new_save(&self) -> Result<()> {
+    pub fn backup_once_for_new_save(&self) -> BackupReport {
         trace!("do backup once if the save file has not been backed up before");
-        for world in 1..=TAIWU_GAME_SAVE_WORLD_NUMBER_MAX {
-            let save = self.save_file(world);
-            if !save.is_file() {
-                continue;
+        if !self.dry_run {
+            self.remove_temp_files();
+        }
+        self.backup_parallel(|src| match self.extra_files.relative(src) {
+            Some(relative) if !self.is_save_file(src) => {
+                let same = self.has_same_backup_file(src, &self.extra_backup_folder(relative))?;
+                match same {
+                    Some(_) => Ok(false),
+                    None => self.backup_extra(src, relative).map(|()| true),
+                }
+            },
+            _ => self.backup_if_new(src),
+        })
+    }
+
+    // true if backed up
+    fn backup_if_new(&self, save: &Path) -> Result<bool> {
+        if !save.is_file() || !self.is_save_file(save) {
+            return Ok(false);
+        }
+        let folder_name = save.parent().unwrap().file_name().unwrap();
+        let same = self.has_same_backup_file(save, &self.backup_root.join(folder_name))?;
+        if let Some(same) = same {
+            info!("[Not Backup] {}", save.display());
+            info!("[Same Exist] {}", same.display());
+            let world = save.parent().and_then(|folder| self.world_of_folder(folder));
+            let message = format!("`{}` not backed up, the same backup exists", save.display());
+            self.record_history(HistoryEntry::new(HistoryKind::Skip, world, message).path(same));
+            Ok(false)
+        } else {
+            self.backup(save).map(|()| true)
+        }
+    }
+
+    // ahead of the jobs queued by the watcher, those of the same files are dropped
+    pub fn backup_once(&self) -> BackupReport {
+        trace!("do backup once");
+        self.backup_parallel(|src| {
+            if let Some(queue) = self.job_queue.lock().unwrap().as_ref() {
+                queue.discard(src);
             }
-            let same = self.has_same_backup_file(&save)?;
-            if let Some(same) = same {
-                info!("[Not Backup] {}", save.display());
-                info!("[Same Exist] {}", same.display());
-            } else {
-                self.backup(&save)?;
+            match self.extra_files.relative(src) {
+                Some(relative) if !self.is_save_file(src) => self.backup_extra(src, relative).map(|()| true),
+                _ => self.backup(src).map(|()| true),
+            }
+        })
+    }
+
+    // the saves of every world and the extra files, `f` is true if it backed one up
+    fn backup_parallel(&self, f: impl Fn(&Path) -> Result<bool> + Sync) -> BackupReport {
+        let mut sources: Vec<PathBuf> = self.worlds().into_iter()
+            .map(|world| self.save_file(world))
+            .filter(|save| save.is_file() && self.is_save_file(save))
+            .collect();
+        sources.extend(self.extra_files.find());
+
+        let queue = Mutex::new(sources.into_iter());
+        let report = Mutex::new(BackupReport::default());
+        let workers = BACKUP_WORKERS.min(queue.lock().unwrap().len());
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| loop {
+                    let Some(src) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let lock = self.folder_locks.lock(&src);
+                    let result = f(&src);
+                    drop(lock);
+                    let mut report = report.lock().unwrap();
+                    match result {
+                        Ok(true) => report.done.push(src),
+                        Ok(false) => {},
+                        // warned about by itself, once, as in `run_job`
+                        Err(e @ TaiwuError::SaveTruncated { .. }) => {
+                            debug!("{}", e);
+                            self.set_last_error(&e);
+                            report.failed.push((src, e));
+                        },
+                        Err(e) => {
+                            error!("[backup] `{}` error: {:?}", src.display(), e);
+                            let world = src.parent().and_then(|folder| self.world_of_folder(folder));
+                            self.record_error(world, format_args!("backup of `{}` failed: {}", src.display(), e));
+                            report.failed.push((src, e));
+                        },
+                    }
+                });
             }
+        });
+        report.into_inner().unwrap()
+    }
+
+    // the slots of the game, and every other world in the saves or in the backups
+    pub fn worlds(&self) -> Vec<usize> {
+        // the slots the game always has, more world folders (e.g. from mods) are found on disk
+        let mut worlds: BTreeSet<usize> = (1..=game().world_count()).collect();
+        self.save_roots.iter().for_each(|root| worlds.extend(find_worlds(root)));
+        worlds.extend(find_worlds(&self.backup_root));
+        worlds.into_iter().collect()
+    }
+
+    // oldest first
+    pub fn list_backups(&self, world: usize) -> Result<Vec<BackupEntry>> {
+        let folder = self.backup_root.join(world_folder_name(world));
+        if !folder.is_dir() {
+            return Ok(Vec::new());
         }
-        Ok(())
+        Ok(store::list_backups(&folder)?)
     }
 
-    pub fn backup_once(&self) -> Result<()> {
-        trace!("do backup once");
-        for world in 1..=TAIWU_GAME_SAVE_WORLD_NUMBER_MAX {
-            let save = self.save_file(world);
-            if save.is_file() {
-                self.backup(&save)?;
+    // filtered, sorted and limited as `query` says, the list above is the same as the default query
+    pub fn query_backups(&self, world: usize, query: &BackupQuery) -> Result<Vec<BackupEntry>> {
+        Ok(query.apply(self.list_backups(world)?)?)
+    }
+
+    // how backup `b` differs from the older `a`
+    pub fn diff_backups(&self, world: usize, a: &str, b: &str) -> Result<BackupDiff> {
+        let a = self.find_backup(world, a)?;
+        let b = self.find_backup(world, b)?;
+        Ok(diff::diff(a, b)?)
+    }
+
+    pub fn stats(&self) -> Result<Stats> {
+        let mut worlds = Vec::new();
+        // the size and copy time of every backup the manifest has the time of
+        let mut copies = Vec::new();
+        for world in self.worlds() {
+            let backups = self.list_backups(world)?;
+            let manifest = Manifest::load(&self.backup_root.join(world_folder_name(world))).unwrap_or_default();
+            copies.extend(backups.iter().filter_map(|entry| Some((entry.size, manifest.backups.get(&entry.id)?.duration_ms?))));
+            worlds.push((world, backups));
+        }
+        Ok(stats::collect(worlds, copies)?)
+    }
+
+    // bad backups are quarantined (not in a dry run), the problems are logged and notified too
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let started = Instant::now();
+        let _lock = self.store_lock.acquire()?;
+        let report = verify::verify(&self.backup_root, &self.mirror_roots, !self.dry_run)?;
+        for problem in &report.problems {
+            warn!(kind = "verify_problem", path:% = problem.path.display(); "[Verify] `{}` {}", problem.path.display(), problem.problem);
+            if let Some(moved) = &problem.quarantined {
+                warn!(kind = "quarantine", path:% = moved.display(); "[Quarantine] {}", moved.display());
             }
         }
-        Ok(())
+        info!(
+            kind = "verify", checked = report.checked, bytes = report.bytes, problems = report.problems.len(),
+            duration_ms = started.elapsed().as_millis() as u64;
+            "[Verify] {} backups checked, {} problems", report.checked, report.problems.len()
+        );
+        let problems = report.problems.iter().map(|problem| format!("`{}` {}", problem.path.display(), problem.problem)).collect();
+        self.bus.emit(TaiwuEvent::VerifyDone { checked: report.checked, problems });
+        Ok(report)
     }
 
-    pub fn watch(&self) -> Result<()> {
-        let (tx, rx) = std::sync::mpsc::channel();
-    
-        let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
-    
-        debug!("RecommendedWatcher::kind() is {:?}", RecommendedWatcher::kind());
+    // in the Prometheus text format
+    pub fn metrics(&self) -> String {
+        self.metrics.render(&self.last_backups())
+    }
 
-        let watched = self.save_root();
-    
-        // Add a path to be watched. All files and directories at that path and
-        // below will be monitored for changes.
-        watcher.watch(&watched, RecursiveMode::Recursive)?;
+    // unix seconds of the newest backup of each world that has one
+    pub fn last_backups(&self) -> Vec<(usize, i64)> {
+        self.worlds()
+            .into_iter()
+            .filter_map(|world| {
+                let latest = self.list_backups(world).ok()?.pop()?;
+                Some((world, latest.utc.timestamp()))
+            })
+            .collect()
+    }
 
-        info!("Watching `{}`", watched.display());
-        info!("Then will backup to `{}`", watched.display());
+    // by its id, or its number `#143` (also as `world_2#143`)
+    fn find_backup(&self, world: usize, id: &str) -> Result<BackupEntry> {
+        let folder = world_folder_name(world);
+        let id = id.strip_prefix(folder.as_str()).filter(|rest| rest.starts_with('#')).unwrap_or(id);
+        let seq = id.strip_prefix('#').and_then(|seq| seq.parse::<u64>().ok());
+        self.list_backups(world)?
+            .into_iter()
+            .find(|entry| entry.id == id || seq.is_some() && entry.seq == seq)
+            .ok_or_else(|| TaiwuError::BackupNotFound(format!("{}#{}", folder, id.trim_start_matches('#'))))
+    }
 
-        *self.watcher.lock().unwrap() = Some(watcher);
+    // by its id or number as `find_backup`, or by time or label
+    pub fn select_backup(&self, world: usize, selector: &BackupSelector) -> Result<BackupEntry> {
+        match selector {
+            BackupSelector::Id(id) => self.find_backup(world, id),
+            _ => selector
+                .select(self.list_backups(world)?)
+                .ok_or_else(|| TaiwuError::BackupNotFound(format!("{} {}", world_folder_name(world), selector))),
+        }
+    }
+
+    pub fn restore(&self, world: usize, id: &str) -> Result<()> {
+        let entry = self.find_backup(world, id)?;
+        self.restore_file(&entry, world, world)
+    }
 
-        for res in rx {
-            match res {
-                Ok(event) => {
-                    debug!("{}", print_event(&event));
-                    self.process(event)?;
+    // the newest backup that is not truncated or damaged itself, for a save the game left truncated
+    pub fn restore_latest_good(&self, world: usize) -> Result<BackupEntry> {
+        let candidates = self.list_backups(world)?.into_iter().rev().filter(|entry| entry.size >= TRUNCATED_SAVE_SIZE);
+        for entry in candidates {
+            match self.restore_file(&entry, world, world) {
+                Err(e @ TaiwuError::BackupDamaged { .. }) => warn!("{}, try an older one", e),
+                result => {
+                    result?;
+                    self.truncated.lock().unwrap().remove(&world);
+                    return Ok(entry);
                 },
-                Err(e) => error!("watch error: {:?}", e),
             }
         }
+        Err(TaiwuError::BackupNotFound(format!("{}#latest", world_folder_name(world))))
+    }
 
-        info!("End watching");
+    // worlds whose save is truncated, see `restore_latest_good`
+    pub fn truncated_worlds(&self) -> Vec<usize> {
+        self.truncated.lock().unwrap().iter().copied().collect()
+    }
 
-        Ok(())
+    // load a backup of `world` into another slot, the game must not be running and the slot must
+    // be empty unless `overwrite` is set
+    pub fn restore_to(&self, world: usize, id: &str, target_world: usize, overwrite: bool) -> Result<()> {
+        if !self.worlds().contains(&target_world) {
+            return Err(TaiwuError::WorldNotFound(target_world));
+        }
+        let entry = self.find_backup(world, id)?;
+        if GameMonitor::new().is_running() {
+            return Err(TaiwuError::GameRunning);
+        }
+        if self.save_file(target_world).exists() && !overwrite {
+            return Err(TaiwuError::TargetSlotNotEmpty(target_world));
+        }
+        self.restore_file(&entry, world, target_world)
     }
 
-    pub fn unwatch(&self) {
-        if let Some(watcher) = self.watcher.lock().unwrap().take() {
-            drop(watcher);
-            trace!("drop the member Taiwu::watcher");
+    // what a backup was made with differs in from the game as it is now, e.g. `mods it was backed up
+    // with are not installed: ...`, nothing if it was not recorded
+    pub fn compatibility(&self, world: usize, id: &str) -> Result<Vec<String>> {
+        let entry = self.find_backup(world, id)?;
+        Ok(self.backup_setup(&entry).map(|setup| setup.differences(&GameSetup::detect(&self.game_root))).unwrap_or_default())
+    }
+
+    fn backup_setup(&self, entry: &BackupEntry) -> Option<GameSetup> {
+        let manifest = Manifest::load(entry.path.parent()?).ok()?;
+        manifest.backups.get(&entry.id)?.setup.clone()
+    }
+
+    // with the content recorded when it was backed up, backups from before that are only read
+    fn read_verified(&self, entry: &BackupEntry) -> Result<Vec<u8>> {
+        let damaged = |problem: String| TaiwuError::BackupDamaged { path: entry.path.clone(), problem };
+        let bytes = store::read_backup(entry).map_err(|e| damaged(format!("it could not be read: {}", e)))?;
+        if bytes.len() as u64 != entry.size {
+            return Err(damaged(format!("it is {} bytes instead of {}", bytes.len(), entry.size)));
+        }
+        let manifest = Manifest::load(entry.path.parent().unwrap()).unwrap_or_default();
+        let recorded = manifest.backups.get(&entry.id).and_then(|record| record.sha256.as_deref());
+        if recorded.is_some_and(|recorded| recorded != store::sha256_bytes(&bytes)) {
+            return Err(damaged("it differs from what was backed up".to_owned()));
         }
+        Ok(bytes)
     }
 
-    fn process(&self, event: Event) -> io::Result<()> {
-        for path in &event.paths {
-            if !self.is_save_file(path) {
-                continue;
+    // the game overwrites a restored save with the world it has loaded, only warned about as a
+    // frozen world is restored while it runs, like a backup made with another game setup
+    fn check_restore_target(&self, entry: &BackupEntry, world: usize, dst: &Path, size: u64) -> Result<()> {
+        if self.is_game_running() {
+            warn!("the game is running, it may overwrite the restored save of world {} when it saves", world);
+        }
+        if let Some(setup) = self.backup_setup(entry) {
+            for difference in setup.differences(&GameSetup::detect(&self.game_root)) {
+                warn!(kind = "restore_setup", world; "[Restore] backup {} may not load, {}", entry.id, difference);
             }
-            match event.kind {
-                event::EventKind::Modify(ref modify_kind) => {
-                    match modify_kind {
-                        event::ModifyKind::Any => {
-                            trace!("file changed, backup it");
-                            self.backup(path)?;
-                        },
-                        event::ModifyKind::Name(event::RenameMode::From) => {
-                            trace!("rename to other file, do nothing");
-                        }
-                        _ => warn!("unexpected modify type (not ModifyKind::Any), do nothing"),
-                    }
-                }
-                _ => trace!("not modify event, do nothing"),
-            };
+        }
+        let folder = dst.parent().unwrap();
+        let not_writable = |source| TaiwuError::RestoreTargetNotWritable { world, path: dst.to_owned(), source };
+        if !self.dry_run {
+            fs::create_dir_all(folder).map_err(not_writable)?;
+        }
+        match fs::OpenOptions::new().append(true).open(dst) {
+            Err(source) if is_lock_error(&source) => return Err(TaiwuError::RestoreTargetLocked { world, path: dst.to_owned(), source }),
+            Err(source) if source.kind() != io::ErrorKind::NotFound => return Err(not_writable(source)),
+            _ => {},
+        }
+        let existing = folder.ancestors().find(|path| path.exists()).unwrap_or(folder);
+        // the restored copy is written beside the save before it replaces it
+        let available = store::available_space(existing)?;
+        if available < size {
+            return Err(TaiwuError::InsufficientSpace { root: folder.to_owned(), available, required: size });
+        }
+        Ok(())
+    }
+
+    // `entry` of `from_world`, the current save is kept in the pre-restore folder first
+    // nothing is changed unless the backup and the slot pass the checks, and the save is only
+    // replaced once the restored copy is complete
+    fn restore_file(&self, entry: &BackupEntry, from_world: usize, world: usize) -> Result<()> {
+        let dst = self.save_file(world);
+        let bytes = self.read_verified(entry)?;
+        self.check_restore_target(entry, world, &dst, bytes.len() as u64)?;
+        if self.dry_run {
+            info!("[Dry Run] restore `{}` to `{}`", entry.path.display(), dst.display());
+            return Ok(());
+        }
+        let _in_flight = self.shutdown.begin().ok_or(TaiwuError::ShuttingDown)?;
+        let lock = self.store_lock.acquire()?;
+        let snapshot = self.snapshot_before_restore(world, &lock)?;
+        let started = Instant::now();
+        match self.replace_save(&dst, &bytes) {
+            Err(source) if is_lock_error(&source) => {
+                return Err(TaiwuError::RestoreTargetLocked { world, path: dst, source });
+            },
+            Err(source) => {
+                return Err(TaiwuError::RestoreFailed { world, src: entry.path.clone(), dst, source });
+            },
+            Ok(_) => {},
+        }
+
+        info!(
+            kind = "restore", world, src:% = entry.path.display(), dst:% = dst.display(), bytes = entry.size,
+            duration_ms = started.elapsed().as_millis() as u64;
+            "[Restore] {}", entry.path.display()
+        );
+        info!("[     to] {}", dst.display());
+        let message = format!("restored backup {} of world {} to `{}`", entry.id, from_world, dst.display());
+        self.record_history(HistoryEntry::new(HistoryKind::Restore, Some(world), message).path(&entry.path));
+
+        let record = RestoreRecord {
+            time: chrono::offset::Local::now().timestamp_nanos(),
+            backup: entry.id.clone(),
+            from_world,
+            snapshot,
+            undone: false,
+        };
+        if let Err(e) = self.record_restore(world, record) {
+            warn!("could not record the restore of world {}: {}", world, e);
         }
 
         Ok(())
     }
 
-    fn is_save_file(&self, path: &Path) -> bool {
-        for world in 1..=TAIWU_GAME_SAVE_WORLD_NUMBER_MAX {
-            if path == self.save_file(world) {
-                return true;
+    // with `bytes` written beside it first, so it is never left half written, and not backed up
+    // as a change of the game
+    fn replace_save(&self, dst: &Path, bytes: &[u8]) -> io::Result<()> {
+        let tmp = store::temp_path(dst);
+        self.self_writes.begin(dst, self.self_write_window());
+        let result = fs::write(&tmp, bytes).and_then(|()| fs::rename(&tmp, dst));
+        self.self_writes.done(dst, self.self_write_window());
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    // put back the save the last restore (not undone yet) replaced, or remove the save if the
+    // slot was empty, undoing again goes back one more restore
+    pub fn undo_restore(&self, world: usize) -> Result<RestoreRecord> {
+        let folder = self.backup_root.join(world_folder_name(world));
+        let _lock = self.store_lock.acquire()?;
+        let mut manifest = Manifest::load(&folder)?;
+        let record = manifest.restores.iter_mut().rev()
+            .find(|record| !record.undone)
+            .ok_or(TaiwuError::NothingToUndo(world))?;
+        let dst = self.save_file(world);
+        if self.dry_run {
+            info!("[Dry Run] undo the restore of world {} to `{}`", world, dst.display());
+            return Ok(record.clone());
+        }
+        // the save is left as it is if the snapshot can not be read whole
+        let snapshot = match &record.snapshot {
+            Some(snapshot) => Some(store::read_stored(snapshot).map_err(|e| TaiwuError::BackupDamaged {
+                path: snapshot.clone(),
+                problem: format!("it could not be read: {}", e),
+            })?),
+            None => None,
+        };
+        let _in_flight = self.shutdown.begin().ok_or(TaiwuError::ShuttingDown)?;
+
+        let result = match &snapshot {
+            Some(bytes) => self.replace_save(&dst, bytes),
+            None => {
+                self.self_writes.begin(&dst, self.self_write_window());
+                let removed = fs::remove_file(&dst).or_else(|e| if e.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(e) });
+                self.self_writes.done(&dst, self.self_write_window());
+                removed
+            },
+        };
+        match result {
+            Err(source) if is_lock_error(&source) => return Err(TaiwuError::RestoreTargetLocked { world, path: dst, source }),
+            Err(source) => {
+                let src = record.snapshot.clone().unwrap_or_default();
+                return Err(TaiwuError::RestoreFailed { world, src, dst, source });
+            },
+            Ok(()) => {},
+        }
+        match &record.snapshot {
+            Some(snapshot) => info!(kind = "undo_restore", world, src:% = snapshot.display(); "[Undo Restore] {} to {}", snapshot.display(), dst.display()),
+            None => info!(kind = "undo_restore", world; "[Undo Restore] removed {}, the slot was empty", dst.display()),
+        }
+
+        let message = match &record.snapshot {
+            Some(_) => format!("undid restore of backup {}, put back the save it replaced", record.backup),
+            None => format!("undid restore of backup {}, the slot is empty again", record.backup),
+        };
+        self.record_history(HistoryEntry::new(HistoryKind::Restore, Some(world), message));
+
+        record.undone = true;
+        let record = record.clone();
+        manifest.save(&folder)?;
+        Ok(record)
+    }
+
+    // of the restore that `undo_restore` would undo, the latest over all worlds
+    pub fn last_restored_world(&self) -> Option<usize> {
+        self.worlds().into_iter()
+            .filter_map(|world| {
+                let manifest = Manifest::load(&self.backup_root.join(world_folder_name(world))).ok()?;
+                let record = manifest.restores.into_iter().rev().find(|record| !record.undone)?;
+                Some((record.time, world))
+            })
+            .max()
+            .map(|(_, world)| world)
+    }
+
+    // none if the slot has no save
+    fn snapshot_before_restore(&self, world: usize, _lock: &StoreLockGuard) -> Result<Option<PathBuf>> {
+        let save = self.save_file(world);
+        if !save.is_file() {
+            return Ok(None);
+        }
+        let relative = Path::new(PRE_RESTORE_FOLDER_NAME)
+            .join(world_folder_name(world))
+            .join(new_backup_file_name_now(save.file_name().unwrap()));
+        match self.copy_to_store(&save, &self.backup_root, &relative) {
+            Ok(dst) => {
+                info!(kind = "pre_restore", world, dst:% = dst.display(); "[Pre-Restore] {} to {}", save.display(), dst.display());
+                Ok(Some(dst))
+            },
+            Err(source) if is_lock_error(&source) => Err(TaiwuError::RestoreTargetLocked { world, path: save, source }),
+            Err(source) => Err(TaiwuError::PreRestoreFailed { world, source }),
+        }
+    }
+
+    // only the last `pre_restore_keep` restores are kept, with their snapshots
+    fn record_restore(&self, world: usize, record: RestoreRecord) -> io::Result<()> {
+        let folder = self.backup_root.join(world_folder_name(world));
+        let mut manifest = Manifest::load(&folder)?;
+        manifest.restores.push(record);
+        let excess = manifest.restores.len().saturating_sub(self.pre_restore_keep);
+        let dropped: Vec<_> = manifest.restores.drain(..excess).collect();
+        manifest.save(&folder)?;
+        for snapshot in dropped.into_iter().filter_map(|record| record.snapshot) {
+            if let Err(e) = fs::remove_file(&snapshot) {
+                debug!("could not remove `{}`: {}", snapshot.display(), e);
             }
         }
-        false
+        Ok(())
     }
 
-    fn has_same_backup_file(&self, src: &Path) -> io::Result<Option<PathBuf>> {
-        let folder_name = src.parent().unwrap().file_name().unwrap();
-        let backup_folder = self.backup_root.join(folder_name);
+    // over all worlds, only logged what would be removed in a dry run
+    pub fn prune(&self, policy: &PrunePolicy) -> Result<PruneReport> {
+        let lock = self.store_lock.acquire()?;
+        let now = chrono::Utc::now();
+        let (mut planned, mut retained) = (Vec::new(), Vec::new());
+        let mut worlds = Vec::new();
+        for world in self.worlds() {
+            let backups = self.list_backups(world)?;
+            match self.world_settings.get(&world).filter(|settings| !settings.prune.is_empty()) {
+                // on its own, its `max_size` is for it alone
+                Some(settings) => {
+                    let (removed, kept) = settings.prune.or(policy).plan(vec![backups], now);
+                    planned.extend(removed);
+                    retained.extend(kept);
+                },
+                None => worlds.push(backups),
+            }
+        }
+        let (removed, kept) = policy.plan(worlds, now);
+        planned.extend(removed);
+        retained.extend(kept);
 
-        let src_meta = src.metadata()?;
-        assert!(src_meta.is_file());
-        
-        for entry in fs::read_dir(&backup_folder)? {
-            let path = entry?.path();
-            let meta = path.metadata()?;
+        let mut report = PruneReport { dry_run: self.dry_run, retained, ..PruneReport::default() };
+        self.remove_backups(planned, &mut report, &lock);
+        report.retained.sort_by_key(|entry| (entry.utc, entry.timestamp));
 
-            if is_same_file(&src_meta, &meta)? {
-                return Ok(Some(path));
+        if !report.dry_run && !report.deleted.is_empty() {
+            let removed = report.deleted.iter().map(|entry| entry.path.clone()).collect();
+            self.bus.emit(TaiwuEvent::Pruned { removed, freed: report.freed });
+            let message = format!("pruned {} backups, {} bytes freed", report.deleted.len(), report.freed);
+            self.record_history(HistoryEntry::new(HistoryKind::Prune, None, message));
+        }
+        Ok(report)
+    }
+
+    // by `prune` and the quota, into `report`: those that could not be removed are retained, and
+    // the labels and manifest records of the others go with them; only logged in a dry run
+    fn remove_backups(&self, planned: Vec<BackupEntry>, report: &mut PruneReport, lock: &StoreLockGuard) {
+        // a pack is written again once without all of its backups removed
+        let mut packed: BTreeMap<PathBuf, Vec<BackupEntry>> = BTreeMap::new();
+        for entry in planned {
+            if self.dry_run {
+                info!("[Dry Run] prune `{}`", entry.path.display());
+            } else if let Some(pack) = &entry.pack {
+                packed.entry(pack.clone()).or_default().push(entry);
+                continue;
+            } else if let Err(e) = fs::remove_file(entry.chunks.as_ref().unwrap_or(&entry.path)) {
+                warn!("prune `{}` error: {}", entry.path.display(), e);
+                report.retained.push(entry);
+                continue;
+            } else {
+                info!(kind = "prune", path:% = entry.path.display(), bytes = entry.size; "[Pruned] {}", entry.path.display());
+                if let Some(catalog) = self.catalog.lock().unwrap().as_mut() {
+                    catalog.remove(&entry.path);
+                }
+            }
+            // what the chunks of chunked backups take is freed below, once no backup uses them
+            if entry.chunks.is_none() {
+                report.freed += entry.size;
+            }
+            report.deleted.push(entry);
+        }
+        if report.deleted.iter().any(|entry| entry.chunks.is_some()) {
+            report.freed += self.collect_chunks(lock);
+        }
+        for (pack, entries) in packed {
+            let names: Vec<String> = entries.iter().map(|entry| entry.path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+            if let Err(e) = pack::rewrite(&pack, &[], &names) {
+                warn!("prune from `{}` error: {}", pack.display(), e);
+                report.retained.extend(entries);
+                continue;
+            }
+            for entry in entries {
+                info!(kind = "prune", path:% = entry.path.display(), bytes = entry.size; "[Pruned] {} from {}", entry.path.display(), pack.display());
+                if let Some(catalog) = self.catalog.lock().unwrap().as_mut() {
+                    catalog.remove(&entry.path);
+                }
+                report.freed += entry.size;
+                report.deleted.push(entry);
+            }
+        }
+        if self.dry_run {
+            return;
+        }
+        let mut folders: BTreeMap<&Path, Vec<&str>> = BTreeMap::new();
+        for entry in &report.deleted {
+            folders.entry(entry.path.parent().unwrap()).or_default().push(&entry.id);
+        }
+        for (folder, ids) in folders {
+            let forgotten = store::read_labels(folder).and_then(|mut labels| {
+                let labeled = labels.len();
+                labels.retain(|id, _| !ids.contains(&id.as_str()));
+                if labels.len() != labeled {
+                    store::write_labels(folder, &labels)?;
+                }
+                let mut manifest = Manifest::load(folder)?;
+                let recorded = manifest.backups.len();
+                manifest.backups.retain(|id, _| !ids.contains(&id.as_str()));
+                if manifest.backups.len() != recorded {
+                    manifest.save(folder)?;
+                }
+                Ok(())
+            });
+            if let Err(e) = forgotten {
+                warn!("could not forget the pruned backups in `{}`: {}", folder.display(), e);
             }
         }
+    }
 
-        Ok(None)
+    // the backups older than `older_than` (by default `pack_after_days`) into one pack per world
+    // and month, except the latest of each world and labeled ones, like `prune`
+    pub fn pack(&self, older_than: Option<Duration>) -> Result<PackReport> {
+        let _lock = self.store_lock.acquire()?;
+        let _background = priority::background(self.background_priority);
+        let older_than = chrono::Duration::from_std(older_than.unwrap_or(self.pack_after)).unwrap_or(chrono::Duration::max_value());
+        let cutoff = chrono::Utc::now().checked_sub_signed(older_than).unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+
+        let mut report = PackReport { dry_run: self.dry_run, ..PackReport::default() };
+        for world in self.worlds() {
+            let backups = self.list_backups(world)?;
+            let Some((_, older)) = backups.split_last() else { continue };
+            let mut months: BTreeMap<String, Vec<&BackupEntry>> = BTreeMap::new();
+            // chunked backups already share their space
+            let loose = older.iter().filter(|entry| entry.pack.is_none() && entry.chunks.is_none());
+            for entry in loose.filter(|entry| entry.label.is_none() && entry.utc < cutoff) {
+                months.entry(entry.utc.format("%Y-%m").to_string()).or_default().push(entry);
+            }
+
+            let folder = self.backup_root.join(world_folder_name(world));
+            for (month, entries) in months {
+                let pack = pack::pack_path(&folder, &month);
+                report.packed += entries.len();
+                report.bytes += entries.iter().map(|entry| entry.size).sum::<u64>();
+                if self.dry_run {
+                    info!("[Dry Run] pack {} backups of world {} into `{}`", entries.len(), world, pack.display());
+                    continue;
+                }
+                let files: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+                pack::rewrite(&pack, &files, &[])?;
+                for entry in &entries {
+                    if let Err(e) = fs::remove_file(&entry.path) {
+                        warn!("could not remove `{}` after packing it: {}", entry.path.display(), e);
+                    }
+                    if let Some(catalog) = self.catalog.lock().unwrap().as_mut() {
+                        catalog.remove(&entry.path);
+                    }
+                }
+                info!(kind = "pack", world, path:% = pack.display(), backups = entries.len(); "[Packed] {} backups into {}", entries.len(), pack.display());
+                report.packs.push(pack);
+            }
+        }
+        Ok(report)
     }
 
-    fn backup(&self, src: &Path) -> io::Result<()> {
-        let file_name = new_backup_file_name_now();
-        let folder_name = src.parent().unwrap().file_name().unwrap();
-        let dst = self.backup_root.join(folder_name).join(file_name);
-        debug!("[now do it] backup `{}` to `{}...`", src.display(), dst.display());
+    // a backup (by default the latest) with what is known about it as a `.taiwubak` file, at `dst`
+    // or in it if it is a folder
+    pub fn export(&self, world: usize, id: Option<&str>, dst: &Path) -> Result<PathBuf> {
+        use sha2::{Digest, Sha256};
 
-        fs::create_dir_all(dst.parent().unwrap())?;
-        fs::copy(src, dst.clone())?;
+        let entry = match id {
+            Some(id) => self.find_backup(world, id)?,
+            None => self.list_backups(world)?
+                .pop()
+                .ok_or_else(|| TaiwuError::BackupNotFound(format!("{}#latest", world_folder_name(world))))?,
+        };
+        let save = self.read_verified(&entry)?;
+        let sha256 = format!("{:x}", Sha256::digest(&save));
+        // the current one for backups from before it was recorded
+        let setup = self.backup_setup(&entry).unwrap_or_else(|| GameSetup::detect(&self.game_root));
+        let info = archive::describe(world, &entry, sha256, setup);
+        let path = archive::target_path(dst, &info);
+        if self.dry_run {
+            info!("[Dry Run] export `{}` to `{}`", entry.path.display(), path.display());
+            return Ok(path);
+        }
+        archive::write(&path, &info, &save).map_err(|source| TaiwuError::ExportFailed { path: path.clone(), source })?;
+        info!(kind = "export", world, id = entry.id.as_str(), path:% = path.display(); "[Export] {} to {}", entry.path.display(), path.display());
+        Ok(path)
+    }
+
+    // a `.taiwubak` file as a new backup of `world` (by default the world it was exported from),
+    // labeled as imported, the copy is checked against the archive first
+    pub fn import(&self, file: &Path, world: Option<usize>) -> Result<Imported> {
+        let invalid = |message: String| TaiwuError::ArchiveInvalid { path: file.to_owned(), message };
+        let (info, save) = archive::read(file).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => invalid(e.to_string()),
+            _ => TaiwuError::IoError(e),
+        })?;
+        if (save.len() as u64) < TRUNCATED_SAVE_SIZE {
+            return Err(invalid(format!("the save in it is only {} bytes", save.len())));
+        }
+        let world = world.unwrap_or(info.world);
+        if !self.worlds().contains(&world) {
+            return Err(TaiwuError::WorldNotFound(world));
+        }
+
+        let file_name = new_backup_file_name_now(game().save_file_name().as_ref());
+        let relative = Path::new(&world_folder_name(world)).join(&file_name);
+        let id = store::backup_file_timestamp(&relative).unwrap().to_string();
+        if self.dry_run {
+            info!("[Dry Run] import `{}` into world {}", file.display(), world);
+            return Ok(Imported { info, world, id });
+        }
+        // the save is taken from a file like any other, and only named once in the stores
+        let tmp = store::temp_path(&self.backup_root.join("import"));
+        fs::create_dir_all(&self.backup_root)?;
+        fs::write(&tmp, &save)?;
+        let result = self.backup_as(&tmp, &relative);
+        let _ = fs::remove_file(&tmp);
+        result?;
 
-        info!("[Backup] {}", src.display());
-        info!("[    to] {}", dst.display());
+        let _lock = self.store_lock.acquire()?;
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let label = match &info.label {
+            Some(label) => format!("{} ({})", label, name),
+            None => format!("imported {}", name),
+        };
+        self.set_label(world, &id, &label)?;
+        // of where it was exported, not of this computer
+        let folder = self.backup_root.join(world_folder_name(world));
+        let mut manifest = Manifest::load(&folder)?;
+        if let Some(record) = manifest.backups.get_mut(&id) {
+            record.setup = Some(GameSetup { game_version: info.game_version.clone(), mods: info.mods.clone() });
+            manifest.save(&folder)?;
+        }
+        info!(kind = "import", world, id = id.as_str(), path:% = file.display(); "[Import] {} into world {}", file.display(), world);
+        Ok(Imported { info, world, id })
+    }
+
+    pub fn delete_backup(&self, world: usize, id: &str) -> Result<()> {
+        let lock = self.store_lock.acquire()?;
+        let entry = self.find_backup(world, id)?;
+        let folder = entry.path.parent().unwrap();
+
+        let removed = match (&entry.pack, &entry.chunks) {
+            (Some(pack), _) => pack::rewrite(pack, &[], &[entry.path.file_name().unwrap().to_string_lossy().into_owned()]),
+            (_, Some(list)) => fs::remove_file(list),
+            _ => fs::remove_file(&entry.path),
+        };
+        removed.map_err(|source| TaiwuError::BackupDeleteFailed { path: entry.path.clone(), source })?;
+        if entry.chunks.is_some() {
+            self.collect_chunks(&lock);
+        }
+        if let Some(catalog) = self.catalog.lock().unwrap().as_mut() {
+            catalog.remove(&entry.path);
+        }
+        let mut labels = store::read_labels(folder)?;
+        if labels.remove(&entry.id).is_some() {
+            store::write_labels(folder, &labels)?;
+        }
+        let mut manifest = Manifest::load(folder)?;
+        if manifest.backups.remove(&entry.id).is_some() {
+            manifest.save(folder)?;
+        }
 
+        info!("[Delete] {}", entry.path.display());
         Ok(())
     }
-}
 
-fn get_backup_root_default() -> Result<PathBuf> {
-    if let Some(base_dirs) = BaseDirs::new() {
-        let backup_root = base_dirs.data_local_dir().to_path_buf().join(APPDATA_FOLDER_NAME).join(BACKUP_FOLDER_NAME);
-        Ok(backup_root)
-    } else {
-        Err(TaiwuError::BackupRootDefaultNotAvailable)
+    // of the primary store, after chunked backups were removed, the bytes freed; only under the
+    // store lock, a backup writing its chunks at the same time would lose those its list is not
+    // written for yet
+    fn collect_chunks(&self, _lock: &StoreLockGuard) -> u64 {
+        match chunks::collect_garbage(&self.backup_root) {
+            Ok((removed, freed)) => {
+                debug!("removed {} chunks no backup uses anymore, {} bytes", removed, freed);
+                freed
+            },
+            Err(e) => {
+                warn!("could not remove the unused chunks: {}", e);
+                0
+            },
+        }
     }
-}
 
-fn print_event(event: &Event) -> String {
-    let paths = &event.paths;
-    let path_info = if paths.len() == 1 {
-        paths.get(0).unwrap().display().to_string()
-    } else {
-        format!("{:?}", paths)
-    };
-    format!("[{:?}] `{}`", event.kind, path_info)
+    // an empty label removes it
+    pub fn set_label(&self, world: usize, id: &str, label: &str) -> Result<()> {
+        let _lock = self.store_lock.acquire()?;
+        let entry = self.find_backup(world, id)?;
+        let folder = entry.path.parent().unwrap();
+
+        let mut labels = store::read_labels(folder)?;
+        match label.trim() {
+            "" => labels.remove(&entry.id),
+            label => labels.insert(entry.id, label.to_owned()),
+        };
+        store::write_labels(folder, &labels)?;
+        Ok(())
+    }
+
+    // from now on the save of `world` is put back to the backup `id` (by default the latest
+    // labeled one, or else the latest) whenever the game writes it, until unfrozen
+    pub fn freeze(&self, world: usize, id: Option<&str>) -> Result<BackupEntry> {
+        let _lock = self.store_lock.acquire()?;
+        let entry = match id {
+            Some(id) => self.find_backup(world, id)?,
+            None => {
+                let backups = self.list_backups(world)?;
+                let labeled = backups.iter().rev().find(|entry| entry.label.is_some()).cloned();
+                labeled.or_else(|| backups.last().cloned())
+                    .ok_or_else(|| TaiwuError::BackupNotFound(format!("{}#latest", world_folder_name(world))))?
+            },
+        };
+        if entry.label.is_none() {
+            self.set_label(world, &entry.id, FROZEN_LABEL)?;
+        }
+        let folder = self.backup_root.join(world_folder_name(world));
+        let mut manifest = Manifest::load(&folder)?;
+        manifest.frozen = Some(entry.id.clone());
+        manifest.save(&folder)?;
+        warn!(
+            kind = "freeze", world, id = entry.id.as_str();
+            "[Freeze] world {} is frozen to backup {}, what the game saves to it will be replaced", world, entry.id
+        );
+        Ok(entry)
+    }
+
+    pub fn unfreeze(&self, world: usize) -> Result<()> {
+        let _lock = self.store_lock.acquire()?;
+        let folder = self.backup_root.join(world_folder_name(world));
+        let mut manifest = Manifest::load(&folder)?;
+        if manifest.frozen.take().is_some() {
+            manifest.save(&folder)?;
+            info!(kind = "unfreeze", world; "[Freeze] world {} is not frozen anymore", world);
+        }
+        Ok(())
+    }
+
+    // the id of the backup the world is frozen to
+    pub fn frozen(&self, world: usize) -> Option<String> {
+        Manifest::load(&self.backup_root.join(world_folder_name(world))).ok()?.frozen
+    }
+
+    // after backing up what the game wrote, so it is not lost either
+    fn enforce_freeze(&self, save: &Path) {
+        let Some(world) = save.parent().and_then(|folder| self.world_of_folder(folder)) else {
+            return;
+        };
+        if save != self.save_file(world) {
+            return;
+        }
+        let Some(id) = self.frozen(world) else {
+            return;
+        };
+        let entry = match self.find_backup(world, &id) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("[Freeze] world {} is frozen, but: {}", world, e);
+                return;
+            },
+        };
+        // the event of the restore below
+        let backup = store::read_backup(&entry).map(|bytes| store::sha256_bytes(&bytes));
+        if store::sha256(save).ok() == backup.ok() {
+            return;
+        }
+        warn!(kind = "freeze_restore", world, id = id.as_str(); "[Freeze] the save of world {} was written, put back backup {}", world, id);
+        if let Err(e) = self.restore_file(&entry, world, world) {
+            error!("[Freeze] restore of world {} error: {:?}", world, e);
+        }
+    }
+
+    // until stop() (or shutdown()) is called
+    pub fn watch(&self) -> Result<WatchEnd> {
+        if self.shutdown.is_cancelled() {
+            return Ok(WatchEnd::Clean);
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // dropped by stop(), which ends the game monitor
+        let (unwatch_tx, unwatch_rx) = mpsc::channel::<()>();
+        *self.unwatch_signal.lock().unwrap() = Some(unwatch_tx);
+        *self.stop_signal.lock().unwrap() = Some(tx.clone());
+
+        if !self.only_while_game_running {
+            if let Err(e) = self.start_watcher(tx.clone()) {
+                self.unwatch_signal.lock().unwrap().take();
+                self.stop_signal.lock().unwrap().take();
+                return Err(e);
+            }
+        }
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+
+        thread::scope(|s| {
+            s.spawn(move || {
+                // retry the failed backups until watching ends
+                while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(OUTBOX_RETRY_INTERVAL) {
+                    if let Err(e) = self.retry_outbox() {
+                        warn!("retry outbox error: {:?}", e);
+                    }
+                }
+            });
+
+            s.spawn(move || self.monitor_game(unwatch_rx, tx));
+
+            // the events are only sorted here, the copies are done by the worker
+            let (queue, jobs) = queue::bounded(JOB_QUEUE_CAPACITY);
+            *self.job_queue.lock().unwrap() = Some(queue.clone());
+            s.spawn(move || {
+                for job in jobs {
+                    self.run_job(job);
+                }
+            });
+
+            // a safety net for changes the watcher did not report, they are backed up the same way
+            let (snapshot_stop_tx, snapshot_stop_rx) = mpsc::channel::<()>();
+            if let Some(interval) = self.snapshot_interval {
+                let queue = queue.clone();
+                s.spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) = snapshot_stop_rx.recv_timeout(interval) {
+                        self.snapshot(&queue);
+                    }
+                });
+            }
+
+            let (maintenance_stop_tx, maintenance_stop_rx) = mpsc::channel::<()>();
+            if self.verify_schedule.is_some() || self.prune_schedule.is_some() || self.pack_schedule.is_some() {
+                s.spawn(move || self.run_maintenance(maintenance_stop_rx));
+            }
+
+            for signal in rx {
+                match signal {
+                    WatchSignal::Event(Ok(mut event)) => {
+                        event.paths = event.paths.into_iter().map(longpath::simplified).collect();
+                        debug!("{}", print_event(&event));
+                        self.events_seen.fetch_add(1, Ordering::SeqCst);
+                        self.last_event.store(chrono::offset::Local::now().timestamp(), Ordering::SeqCst);
+                        self.process(event, &queue);
+                    },
+                    WatchSignal::Event(Err(e)) => {
+                        error!("watch error: {:?}", e);
+                        self.record_error(None, format_args!("watching failed: {}", e));
+                        self.watcher_failed.store(true, Ordering::SeqCst);
+                    },
+                    WatchSignal::Stop => break,
+                }
+            }
+            // the worker still does what is queued
+            self.job_queue.lock().unwrap().take();
+            drop(queue);
+            drop(stop_tx);
+            drop(snapshot_stop_tx);
+            drop(maintenance_stop_tx);
+        });
+
+        // the other threads (control channel, hotkey...) may still be copying
+        let end = if self.shutdown.is_cancelled() && !self.wait_idle(SHUTDOWN_TIMEOUT) {
+            let in_flight = self.shutdown.in_flight();
+            warn!("quit with {} backups or restores unfinished", in_flight);
+            WatchEnd::Unfinished { in_flight }
+        } else {
+            WatchEnd::Clean
+        };
+
+        info!("End watching");
+
+        Ok(end)
+    }
+
+    // does nothing once stop() was called, so no watcher is left behind
+    fn start_watcher(&self, tx: mpsc::Sender<WatchSignal>) -> Result<()> {
+        let signal = self.unwatch_signal.lock().unwrap();
+        if signal.is_none() {
+            return Ok(());
+        }
+
+        let watched = &self.save_roots[0];
+        let failed = |source| TaiwuError::WatchStartFailed { path: watched.clone(), source };
+        let poll = match self.watcher_backend {
+            WatcherBackend::Native => None,
+            WatcherBackend::Poll => Some(self.poll_interval),
+            WatcherBackend::Auto => self.poll_fallback.load(Ordering::SeqCst).then_some(self.poll_interval),
+        };
+        let handler = move |event| {
+            let _ = tx.send(WatchSignal::Event(event));
+        };
+        let mut watcher = AnyWatcher::new(poll, handler).map_err(failed)?;
+
+        match poll {
+            Some(interval) => info!("Polling for changes every {:?}", interval),
+            None => debug!("RecommendedWatcher::kind() is {:?}", RecommendedWatcher::kind()),
+        }
+
+        let bases = self.extra_files.bases();
+    
+        // Add a path to be watched. All files and directories at that path and
+        // below will be monitored for changes.
+        if !self.is_save_root_in_extra_bases(watched) {
+            watcher.watch(watched, RecursiveMode::Recursive).map_err(failed)?;
+        }
+        info!("Watching `{}`", watched.display());
+        // the others may be on a drive that is not there now
+        for root in self.save_roots.iter().skip(1).filter(|root| !self.is_save_root_in_extra_bases(root)) {
+            match watcher.watch(root, RecursiveMode::Recursive) {
+                Ok(()) => info!("Watching `{}`", root.display()),
+                Err(e) => warn!("could not watch `{}`: {}", root.display(), e),
+            }
+        }
+        info!("Then will backup to `{}`", self.backup_root.display());
+
+        for base in bases.iter().filter(|base| self.save_root_of(&base.path).is_none()) {
+            let mode = if base.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+            match watcher.watch(&base.path, mode) {
+                Ok(()) => info!("Watching `{}` for extra files", base.path.display()),
+                Err(e) => warn!("could not watch `{}` for extra files: {}", base.path.display(), e),
+            }
+        }
+
+        *self.watcher.lock().unwrap() = Some(watcher);
+        Ok(())
+    }
+
+    // an extra files folder may have it already, events are not wanted twice
+    fn is_save_root_in_extra_bases(&self, save_root: &Path) -> bool {
+        self.extra_files.bases().iter().any(|base| base.recursive && save_root.starts_with(&base.path))
+    }
+
+    // the watch of a removed and recreated folder may be gone on some platforms
+    fn rewatch_save_root(&self, save_root: &Path) {
+        if self.is_save_root_in_extra_bases(save_root) {
+            return;
+        }
+        if let Some(watcher) = self.watcher.lock().unwrap().as_mut() {
+            let _ = watcher.unwatch(save_root);
+            if let Err(e) = watcher.watch(save_root, RecursiveMode::Recursive) {
+                error!("[watch] could not watch `{}` again: {:?}", save_root.display(), e);
+            }
+        }
+    }
+
+    fn stop_watcher(&self) {
+        if let Some(watcher) = self.watcher.lock().unwrap().take() {
+            drop(watcher);
+            trace!("drop the member Taiwu::watcher");
+        }
+    }
+
+    // watch() returns once the jobs queued are done, it can be called again after
+    pub fn stop(&self) {
+        let signal = self.unwatch_signal.lock().unwrap().take();
+        self.stop_watcher();
+        drop(signal);
+        if let Some(stop) = self.stop_signal.lock().unwrap().take() {
+            let _ = stop.send(WatchSignal::Stop);
+        }
+    }
+
+    // before the system sleeps or shuts down: the saves changed since their last backup are
+    // backed up and the queued jobs done, false if some are still waiting after `timeout`
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        if !self.is_paused() {
+            if let Err(e) = self.backup_once_for_new_save().into_result() {
+                warn!("[flush] backup error: {:?}", e);
+            }
+        }
+        let queued = || self.job_queue.lock().unwrap().as_ref().map_or(0, JobQueue::len);
+        while queued() > 0 || self.shutdown.in_flight() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(FLUSH_POLL_INTERVAL);
+        }
+        true
+    }
+
+    // after the system woke up, the native watcher may not report changes anymore
+    pub fn resumed(&self) {
+        self.resumed.store(true, Ordering::SeqCst);
+    }
+
+    // for quitting: stop, then no new backup or restore starts and watch() returns once
+    // the running ones are done
+    pub fn shutdown(&self) {
+        info!("Shutting down");
+        self.shutdown.cancel();
+        self.stop();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    // false if there are still backups or restores running after `timeout`
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        self.shutdown.wait(timeout)
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.lock().unwrap().is_some()
+    }
+
+    pub fn is_polling(&self) -> bool {
+        self.watcher.lock().unwrap().as_ref().is_some_and(AnyWatcher::is_polling)
+    }
+
+    // unix seconds of the last change the watcher saw
+    pub fn last_event(&self) -> Option<i64> {
+        Some(self.last_event.load(Ordering::SeqCst)).filter(|&time| time > 0)
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        info!("Paused");
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        info!("Resumed");
+    }
+
+    // watching is resumed first, so nothing played is missed
+    pub fn launch_game(&self) -> Result<()> {
+        if self.is_paused() {
+            self.resume();
+        }
+        if !self.only_while_game_running && !self.is_watching() {
+            warn!("[Game] launched while the saves are not watched, the watcher is restarted shortly");
+        }
+        game_root::launch(&self.game_root, self.detect_method).map_err(TaiwuError::LaunchFailed)?;
+        info!(kind = "game_launch", method:% = self.detect_method; "[Game] launched by {}", self.detect_method);
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn is_game_running(&self) -> bool {
+        self.game_running.load(Ordering::SeqCst)
+    }
+
+    // until `stop` is dropped, with `only_while_game_running` it also starts and stops the watcher
+    fn monitor_game(&self, stop: mpsc::Receiver<()>, tx: mpsc::Sender<WatchSignal>) {
+        let mut monitor = GameMonitor::new();
+        let mut missed = MissedChanges::default();
+        let mut last_check = Instant::now();
+        let mut last_restart = None::<Instant>;
+        self.game_running.store(monitor.is_running(), Ordering::SeqCst);
+        if self.only_while_game_running {
+            if monitor.is_running() {
+                self.start_watcher_logged(&tx);
+            } else {
+                info!("Waiting for the game to start");
+            }
+        }
+
+        while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(GAME_PROCESS_POLL_INTERVAL) {
+            let due = last_check.elapsed() >= WATCHER_CHECK_INTERVAL;
+            if due {
+                last_check = Instant::now();
+            }
+            // an error is acted on at once, but not restarted over and over
+            let failed = self.watcher_failed.load(Ordering::SeqCst)
+                && last_restart.is_none_or(|at: Instant| at.elapsed() >= WATCHER_CHECK_INTERVAL);
+            let resumed = self.resumed.load(Ordering::SeqCst);
+            if (due || failed || resumed) && self.supervise_watcher(&mut missed, due, &tx) {
+                last_restart = Some(Instant::now());
+            }
+            match monitor.poll() {
+                Some(true) => {
+                    info!(kind = "game_started"; "[Game] started");
+                    self.game_running.store(true, Ordering::SeqCst);
+                    self.bus.emit(TaiwuEvent::GameStarted);
+                    if self.only_while_game_running {
+                        self.start_watcher_logged(&tx);
+                    }
+                },
+                Some(false) => {
+                    info!(kind = "game_stopped"; "[Game] stopped");
+                    self.game_running.store(false, Ordering::SeqCst);
+                    self.bus.emit(TaiwuEvent::GameStopped);
+                    if self.only_while_game_running {
+                        self.stop_watcher();
+                        info!("Stop watching until the game starts again");
+                    }
+                    // the last autosave may have been written just before exiting
+                    if let Err(e) = self.backup_once().into_result() {
+                        error!("[Game] backup on exit error: {:?}", e);
+                    }
+                },
+                None => {},
+            }
+        }
+    }
+
+    // restarts the watcher if it sent an error, missed changes (`check_missed`) or is not
+    // running although it should be, true if it did
+    fn supervise_watcher(&self, missed: &mut MissedChanges, check_missed: bool, tx: &mpsc::Sender<WatchSignal>) -> bool {
+        let should_watch = !self.only_while_game_running || self.is_game_running();
+        let reason = if self.resumed.swap(false, Ordering::SeqCst) {
+            "the system woke up from sleep"
+        } else if self.watcher_failed.swap(false, Ordering::SeqCst) {
+            "the watcher reported an error"
+        } else if should_watch && !self.is_watching() {
+            "the watcher is not running"
+        } else if check_missed && self.is_watching() && missed.check(self.saves(), self.events_seen.load(Ordering::SeqCst)) {
+            // e.g. on a network drive, poll it instead from now on
+            if self.watcher_backend == WatcherBackend::Auto && !self.is_polling() {
+                self.poll_fallback.store(true, Ordering::SeqCst);
+            }
+            "the saves changed but no event came"
+        } else {
+            return false;
+        };
+        if !should_watch {
+            return false;
+        }
+
+        warn!(kind = "watcher_restart", reason; "[watch] {}, restart it", reason);
+        self.stop_watcher();
+        self.start_watcher_logged(tx);
+        self.bus.emit(TaiwuEvent::WatcherRestarted {
+            reason: reason.to_owned(),
+            polling: self.is_polling(),
+            running: self.is_watching(),
+        });
+        true
+    }
+
+    // a run missed while the game is running (or paused) is done once it is not anymore
+    fn run_maintenance(&self, stop: mpsc::Receiver<()>) {
+        let mut last_minute = None;
+        let (mut verify_due, mut prune_due, mut pack_due) = (false, false, false);
+        while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(MAINTENANCE_TICK) {
+            let minute = chrono::offset::Local::now().naive_local().with_second(0).and_then(|time| time.with_nanosecond(0));
+            if minute != last_minute {
+                last_minute = minute;
+                if let Some(minute) = minute {
+                    verify_due |= self.verify_schedule.as_ref().is_some_and(|schedule| schedule.matches(minute));
+                    prune_due |= self.prune_schedule.as_ref().is_some_and(|(schedule, _)| schedule.matches(minute));
+                    pack_due |= self.pack_schedule.as_ref().is_some_and(|schedule| schedule.matches(minute));
+                }
+            }
+            if self.is_game_running() || self.is_paused() {
+                continue;
+            }
+            if std::mem::take(&mut verify_due) {
+                if let Err(e) = self.verify() {
+                    error!("[Verify] error: {:?}", e);
+                }
+            }
+            if std::mem::take(&mut prune_due) {
+                if let Some((_, policy)) = &self.prune_schedule {
+                    match self.prune(policy) {
+                        Ok(report) => info!("[Prune] scheduled, {} removed, {} bytes freed", report.deleted.len(), report.freed),
+                        Err(e) => error!("[Prune] error: {:?}", e),
+                    }
+                }
+            }
+            if std::mem::take(&mut pack_due) {
+                match self.pack(None) {
+                    Ok(report) => info!("[Pack] scheduled, {} backups packed", report.packed),
+                    Err(e) => error!("[Pack] error: {:?}", e),
+                }
+            }
+        }
+    }
+
+    // only saves that differ from their latest backup are backed up
+    fn snapshot(&self, queue: &JobQueue) {
+        if self.is_paused() || !self.is_watching() {
+            return;
+        }
+        debug!("scheduled snapshot of all worlds");
+        for save in self.saves() {
+            queue.push(Job::BackupIfNew(save));
+        }
+    }
+
+    fn saves(&self) -> Vec<PathBuf> {
+        self.worlds().into_iter().map(|world| self.save_file(world)).collect()
+    }
+
+    // the poll watcher sees a write only at its next poll
+    fn self_write_window(&self) -> Duration {
+        if self.is_polling() {
+            SELF_WRITE_WINDOW + self.poll_interval
+        } else {
+            SELF_WRITE_WINDOW
+        }
+    }
+
+    fn start_watcher_logged(&self, tx: &mpsc::Sender<WatchSignal>) {
+        if let Err(e) = self.start_watcher(tx.clone()) {
+            error!("[watch] error: {:?}", e);
+        }
+    }
+
+    fn process(&self, event: Event, queue: &JobQueue) {
+        if self.is_paused() || self.shutdown.is_cancelled() {
+            trace!("paused or shutting down, do nothing");
+            return;
+        }
+        let polling = self.is_polling();
+        for (i, path) in event.paths.iter().enumerate() {
+            let change = events::classify(&event.kind, i, path, polling);
+            match (change, self.world_of_folder(path)) {
+                (Change::FolderCreated, Some(_)) => {
+                    info!("New world folder `{}`", path.display());
+                    self.rewatch_save_root(path.parent().unwrap());
+                    // the save may be written before the folder is watched
+                    let save = path.join(game().save_file_name());
+                    if !self.self_writes.is_own(&save) {
+                        queue.push(Job::BackupIfNew(save));
+                    }
+                    continue;
+                },
+                (Change::Removed, Some(world)) => {
+                    info!("World folder `{}` removed", path.display());
+                    if self.archive_removed_worlds {
+                        self.archive_world(world);
+                    }
+                    continue;
+                },
+                _ => {},
+            }
+            let job = if self.is_save_file(path) {
+                Job::Backup(path.clone())
+            } else if self.extra_files.relative(path).is_some() {
+                Job::BackupExtra(path.clone())
+            } else {
+                continue;
+            };
+            if matches!(change, Change::Written | Change::Appeared) && self.self_writes.is_own(path) {
+                debug!("`{}` was written by a restore, not backed up", path.display());
+                continue;
+            }
+            match change {
+                Change::Written => {
+                    trace!("file changed, backup it");
+                    queue.push(job);
+                },
+                Change::Appeared => {
+                    trace!("file renamed into place or created, backup it");
+                    queue.push(job.if_new());
+                },
+                _ => trace!("{:?}, do nothing", change),
+            }
+        }
+    }
+
+    // keep watching if it fails, the next save will be backed up again
+    fn run_job(&self, job: Job) {
+        let _lock = self.folder_locks.lock(job.path());
+        let (src, result) = match &job {
+            Job::Backup(src) => (src, self.backup(src)),
+            Job::BackupExtra(src) => match self.extra_files.relative(src) {
+                Some(relative) => (src, self.backup_extra(src, relative)),
+                None => return,
+            },
+            Job::BackupIfNew(save) => (save, self.backup_if_new(save).map(|_| ())),
+        };
+        match result {
+            Err(TaiwuError::ShuttingDown) => debug!("shutting down, `{}` not backed up", src.display()),
+            // warned about by itself, once
+            Err(e @ TaiwuError::SaveTruncated { .. }) => {
+                debug!("{}", e);
+                self.set_last_error(e);
+            },
+            Err(e) => {
+                error!("[backup] `{}` error: {:?}", src.display(), e);
+                let world = src.parent().and_then(|folder| self.world_of_folder(folder));
+                self.record_error(world, format_args!("backup of `{}` failed: {}", src.display(), e));
+            },
+            Ok(()) => {},
+        }
+        if !matches!(job, Job::BackupExtra(_)) {
+            self.enforce_freeze(src);
+        }
+    }
+
+    fn is_save_file(&self, path: &Path) -> bool {
+        let Some(relative) = self.save_root_of(path).map(|root| path.strip_prefix(root).unwrap()) else {
+            return false;
+        };
+        // in a world turned off
+        let world = relative.components().next().and_then(|folder| world_number(folder.as_os_str()));
+        if world.is_some_and(|world| !self.is_world_enabled(world)) {
+            return false;
+        }
+        self.save_filter.is_match(relative)
+    }
+
+    pub fn is_world_enabled(&self, world: usize) -> bool {
+        self.world_settings.get(&world).is_none_or(|settings| settings.enabled)
+    }
+
+    // the world of `path`, if it is a `world_N` folder right in a save root
+    fn world_of_folder(&self, path: &Path) -> Option<usize> {
+        if !path.parent().is_some_and(|parent| self.save_roots.iter().any(|root| root == parent)) {
+            return None;
+        }
+        path.file_name().and_then(world_number)
+    }
+
+    // move the backups of a world aside in every store, a new world in its slot starts empty
+    fn archive_world(&self, world: usize) {
+        let _lock = match self.store_lock.acquire() {
+            Ok(lock) => lock,
+            Err(e) => {
+                warn!("could not archive world {}: {}", world, e);
+                return;
+            },
+        };
+        let folder_name = world_folder_name(world);
+        let archived_name = new_backup_file_name_now(folder_name.as_ref());
+        for root in self.store_roots() {
+            let src = root.join(&folder_name);
+            if !src.is_dir() {
+                continue;
+            }
+            let dst = root.join(ARCHIVE_FOLDER_NAME).join(&archived_name);
+            if self.dry_run {
+                info!("[Dry Run] archive `{}` to `{}`", src.display(), dst.display());
+                continue;
+            }
+            let result = fs::create_dir_all(dst.parent().unwrap()).and_then(|()| fs::rename(&src, &dst));
+            match result {
+                Ok(()) => info!(kind = "archive", world, src:% = src.display(), dst:% = dst.display(); "[Archive] {} to {}", src.display(), dst.display()),
+                Err(e) => warn!("could not archive `{}`: {}", src.display(), e),
+            }
+        }
+        // scanned again on the next backup
+        self.catalog.lock().unwrap().take();
+    }
+
+    fn has_same_backup_file(&self, src: &Path, backup_folder: &Path) -> io::Result<Option<PathBuf>> {
+        let src_meta = src.metadata()?;
+        assert!(src_meta.is_file());
+
+        // not created yet or not reachable now, just back it up
+        let entries = match fs::read_dir(backup_folder) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("could not read `{}`: {}", backup_folder.display(), e);
+                return Ok(None);
+            }
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if store::is_temp_file(&path) {
+                continue;
+            }
+            let meta = path.metadata()?;
+
+            // a chunk list has the modified time of the save, and its size inside
+            if chunks::is_chunk_list(&path) {
+                let size = chunks::read_list(&path)?.size;
+                if meta.is_file() && size == src_meta.len() && meta.modified()? == src_meta.modified()? {
+                    return Ok(Some(path));
+                }
+            } else if is_same_file(&src_meta, &meta)? {
+                return Ok(Some(path));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn backup(&self, src: &Path) -> Result<()> {
+        if src.file_name() == Some(game().save_file_name().as_ref()) {
+            if let Some(world) = src.parent().and_then(|folder| self.world_of_folder(folder)) {
+                self.check_truncated(src, world)?;
+                self.check_shrink(src, world);
+            }
+        }
+
+        // same folders as under its save root, usually `world_N`
+        let file_name = new_backup_file_name_now(src.file_name().unwrap());
+        let root = self.save_root_of(src).unwrap_or(&self.save_roots[0]);
+        let folder = src.parent().unwrap().strip_prefix(root).unwrap();
+        let relative = folder.join(file_name);
+
+        // still back it up, both versions are kept
+        self.check_cloud_conflict(src, &self.backup_root.join(folder));
+
+        self.backup_as(src, &relative)
+    }
+
+    // a truncated save is not backed up, it would become the latest backup, and it is alerted once
+    fn check_truncated(&self, save: &Path, world: usize) -> Result<()> {
+        let size = fs::metadata(save)?.len();
+        if size >= TRUNCATED_SAVE_SIZE {
+            self.truncated.lock().unwrap().remove(&world);
+            return Ok(());
+        }
+        if self.truncated.lock().unwrap().insert(world) {
+            warn!(
+                kind = "save_truncated", world, bytes = size;
+                "[Truncated] `{}` is only {} bytes, not backed up, restore the latest good backup", save.display(), size
+            );
+            self.bus.emit(TaiwuEvent::SaveTruncated { world, src: save.to_owned(), size });
+            let message = format!("`{}` is only {} bytes, not backed up", save.display(), size);
+            self.record_history(HistoryEntry::new(HistoryKind::Skip, Some(world), message));
+        }
+        Err(TaiwuError::SaveTruncated { world, path: save.to_owned(), size })
+    }
+
+    // alerted once, the next save is compared with this smaller backup too
+    fn check_shrink(&self, save: &Path, world: usize) {
+        if self.shrink_alert_percent == 0 {
+            return;
+        }
+        let Ok(size) = fs::metadata(save).map(|meta| meta.len()) else {
+            return;
+        };
+        let recent: Vec<u64> = self.list_backups(world)
+            .unwrap_or_default()
+            .iter()
+            .rev()
+            .take(SIZE_HISTORY_LEN)
+            .map(|entry| entry.size)
+            .collect();
+        let Some(&latest) = recent.first() else {
+            return;
+        };
+        let average = recent.iter().sum::<u64>() / recent.len() as u64;
+        let limit = |typical: u64| typical - typical * self.shrink_alert_percent / 100;
+        if size < limit(average) && size < limit(latest) {
+            warn!(
+                kind = "save_shrank", world, bytes = size, average;
+                "[Shrank] `{}` is {} bytes, its recent backups {} on average", save.display(), size, average
+            );
+            self.bus.emit(TaiwuEvent::SaveShrank { world, src: save.to_owned(), size, average });
+        }
+    }
+
+    // `relative` to the game root
+    fn backup_extra(&self, src: &Path, relative: &Path) -> Result<()> {
+        let file_name = new_backup_file_name_now(relative.file_name().unwrap());
+        let folder = self.extra_backup_folder(relative);
+        self.backup_as(src, &folder.strip_prefix(&self.backup_root).unwrap().join(file_name))
+    }
+
+    fn extra_backup_folder(&self, relative: &Path) -> PathBuf {
+        self.backup_root.join(EXTRA_FOLDER_NAME).join(relative.parent().unwrap())
+    }
+
+    // `relative` is where the backup goes in every store
+    fn backup_as(&self, src: &Path, relative: &Path) -> Result<()> {
+        let world = src.parent().and_then(|folder| self.world_of_folder(folder));
+        let failed = |dst: PathBuf, source| TaiwuError::BackupCopyFailed {
+            world,
+            src: src.to_owned(),
+            dst,
+            source,
+        };
+        let _in_flight = self.shutdown.begin().ok_or(TaiwuError::ShuttingDown)?;
+        let _background = priority::background(self.background_priority);
+
+        // stores almost full are skipped, instead of failing in the middle of the copy
+        let started = Instant::now();
+        let size = fs::metadata(src).map_err(|e| {
+            self.metrics.error();
+            failed(self.backup_root.join(relative), e)
+        })?.len();
+        let mut stores = Vec::new();
+        let results: Vec<_> = self.store_roots_of(world)
+            .filter_map(|root| match self.check_free_space(root, size) {
+                Ok(()) => Some((root.to_owned(), self.copy_to_store(src, root, relative))),
+                Err(e) => {
+                    warn!("skip backup to `{}`: {}", root.display(), e);
+                    stores.push(StoreStatus { root: root.to_owned(), result: Err(e.to_string()) });
+                    None
+                },
+            })
+            .collect();
+
+        // not a failure, nothing to retry either: the event of that write backs it up again
+        let (written_again, results): (Vec<_>, Vec<_>) = results.into_iter()
+            .partition(|(_, result)| result.as_ref().is_err_and(is_written_again));
+        if !written_again.is_empty() {
+            info!("[Written Again] {}", src.display());
+            if results.is_empty() && stores.is_empty() {
+                let message = format!("`{}` was written again while it was copied, the next backup has it", src.display());
+                self.record_history(HistoryEntry::new(HistoryKind::Skip, world, message));
+                return Ok(());
+            }
+        }
+
+        let duration = started.elapsed();
+        let bytes_per_sec = metrics::bytes_per_sec(size, duration);
+        info!(
+            kind = "backup", world, src:% = src.display(), bytes = size,
+            duration_ms = duration.as_millis() as u64, bytes_per_sec;
+            "[Backup] {} ({} bytes in {} ms, {} KB/s)", src.display(), size, duration.as_millis(), bytes_per_sec / 1024
+        );
+
+        // stores that failed get queued to retry later, from a copy of our own that will not change
+        // (the backup made in another store would not do, prune or the quota may remove it)
+        let mut queued_src: Option<PathBuf> = None;
+        for (root, result) in results {
+            match result {
+                Ok(dst) => {
+                    info!(kind = "backup_store", world, dst:% = dst.display(); "[    to] {}", dst.display());
+                    stores.push(StoreStatus { root, result: Ok(dst) });
+                },
+                Err(e) => {
+                    self.metrics.error();
+                    warn!(
+                        kind = "backup_store_failed", world, root:% = root.display(), error:% = e;
+                        "backup to `{}` failed, queue it to retry: {}", root.display(), e
+                    );
+                    self.record_error(world, format_args!("backup to `{}` failed, it is retried: {}", root.display(), e));
+                    let from = match queued_src {
+                        Some(ref queued) => queued.clone(),
+                        None => match self.outbox.spool(src, relative) {
+                            Ok(spooled) => queued_src.insert(spooled).clone(),
+                            Err(_) => {
+                                let error = e.to_string();
+                                self.bus.emit(TaiwuEvent::BackupFailed { src: src.to_owned(), error });
+                                return Err(failed(root.join(relative), e));
+                            }
+                        },
+                    };
+                    self.outbox.push(OutboxJob::new(from, root.clone(), relative.to_owned(), true))?;
+                    stores.push(StoreStatus { root, result: Err(e.to_string()) });
+                }
+            }
+        }
+
+        if let Some(dst) = stores.iter().find_map(|store| store.result.as_ref().ok()) {
+            self.metrics.backup(world, size, duration);
+            let message = format!("backed up `{}`, {} bytes in {} ms", src.display(), size, duration.as_millis());
+            self.record_history(HistoryEntry::new(HistoryKind::Backup, world, message).path(dst));
+        }
+
+        let primary = stores.iter()
+            .find(|store| store.root == self.backup_root)
+            .and_then(|store| store.result.as_ref().ok())
+            .cloned();
+        self.bus.emit(TaiwuEvent::BackupDone { src: src.to_owned(), stores, bytes: size, duration });
+
+        if let Some(dst) = primary {
+            self.enforce_quota(&dst);
+        }
+
+        Ok(())
+    }
+
+    // `added` is a backup just copied to the primary store; the oldest backups go first, but not
+    // the latest of a world, labeled ones or those its own prune settings keep
+    fn enforce_quota(&self, added: &Path) {
+        let Some(quota) = self.quota else {
+            return;
+        };
+        let lock = match self.store_lock.acquire() {
+            Ok(lock) => lock,
+            Err(e) => {
+                warn!("could not check the quota: {}", e);
+                return;
+            },
+        };
+
+        let (before, candidates) = {
+            let mut catalog = self.catalog.lock().unwrap();
+            let catalog = match catalog.as_mut() {
+                Some(catalog) => {
+                    if let Some(entry) = store::backup_entry(added) {
+                        catalog.add(entry);
+                    }
+                    catalog
+                },
+                None => match Catalog::scan(&self.backup_root) {
+                    Ok(scanned) => catalog.insert(scanned),
+                    Err(e) => {
+                        warn!("scan the backup store error: {}", e);
+                        return;
+                    },
+                },
+            };
+            if catalog.total_size() <= quota {
+                return;
+            }
+            (catalog.total_size(), catalog.prune_candidates().into_iter().cloned().collect::<Vec<_>>())
+        };
+
+        let now = chrono::Utc::now();
+        let mut kept = BTreeSet::new();
+        for (world, settings) in self.world_settings.iter().filter(|(_, settings)| !settings.prune.is_empty()) {
+            match self.list_backups(*world) {
+                Ok(backups) => {
+                    let (_, retained) = settings.prune.plan(vec![backups], now);
+                    kept.extend(retained.into_iter().map(|entry| entry.path));
+                },
+                Err(e) => {
+                    warn!("could not list the backups of world {} to keep: {}", world, e);
+                    return;
+                },
+            }
+        }
+        let mut labels = HashMap::new();
+        let mut size = before;
+        let mut planned = Vec::new();
+        for entry in candidates {
+            if size <= quota {
+                break;
+            }
+            let folder = entry.path.parent().unwrap().to_owned();
+            let labels = labels.entry(folder).or_insert_with_key(|folder| store::read_labels(folder).unwrap_or_default());
+            if labels.contains_key(&entry.id) || kept.contains(&entry.path) {
+                continue;
+            }
+            size -= entry.size;
+            planned.push(entry);
+        }
+
+        let mut report = PruneReport { dry_run: self.dry_run, ..PruneReport::default() };
+        self.remove_backups(planned, &mut report, &lock);
+        if size > quota {
+            warn!("the backup store is still over its quota, only labeled, kept or latest backups are left");
+        }
+        if !report.dry_run && !report.deleted.is_empty() {
+            let message = format!("pruned {} backups over the quota, {} bytes freed", report.deleted.len(), report.freed);
+            self.record_history(HistoryEntry::new(HistoryKind::Prune, None, message));
+            let removed = report.deleted.into_iter().map(|entry| entry.path).collect();
+            self.bus.emit(TaiwuEvent::Pruned { removed, freed: report.freed });
+        }
+    }
+
+    fn check_free_space(&self, root: &Path, size: u64) -> Result<()> {
+        if self.min_free_space == 0 {
+            return Ok(());
+        }
+        // the store folder may not be created yet
+        let Some(existing) = root.ancestors().find(|path| path.exists()) else {
+            return Ok(());
+        };
+        let available = store::available_space(existing)?;
+        let required = size + self.min_free_space;
+        if available < required {
+            let root = root.to_owned();
+            self.bus.emit(TaiwuEvent::InsufficientSpace { root: root.clone(), available, required });
+            return Err(TaiwuError::InsufficientSpace { root, available, required });
+        }
+        Ok(())
+    }
+
+    // the chunk list of the backup in the chunked format; the store is only locked once the copy is
+    // complete, to name it and record it (and write its chunks, like unused ones are removed)
+    fn copy_to_store(&self, src: &Path, root: &Path, relative: &Path) -> io::Result<PathBuf> {
+        let dst = root.join(relative);
+        let stored = if self.chunked { chunks::list_path(&dst) } else { dst.clone() };
+        if self.dry_run {
+            info!("[Dry Run] copy `{}` to `{}`", src.display(), stored.display());
+            return Ok(stored);
+        }
+        debug!("[now do it] backup `{}` to `{}...`", src.display(), dst.display());
+
+        let started = Instant::now();
+        fs::create_dir_all(dst.parent().unwrap())?;
+
+        // a copy cut short must not look like a backup, so it only gets its name once complete
+        let tmp = store::temp_path(&dst);
+        let result = self.copy_verified(src, &tmp, root).and_then(|sha256| {
+            // from the copy, the save may be locked or changed already
+            let metadata = game().parse_metadata(&tmp).unwrap_or_default();
+            if self.durable && !self.chunked {
+                fs::File::options().write(true).open(&tmp)?.sync_all()?;
+            }
+            let lock = self.store_lock.acquire().map_err(io::Error::other)?;
+            if self.chunked {
+                chunks::write(root, &tmp, &stored, sha256.clone(), self.durable)?;
+                fs::remove_file(&tmp)?;
+            } else {
+                fs::rename(&tmp, &dst)?;
+            }
+            Ok((sha256, metadata, lock))
+        });
+        let (sha256, metadata, _lock) = match result {
+            Ok(done) => done,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                return Err(e);
+            },
+        };
+        if self.durable {
+            sync_folder(dst.parent().unwrap())?;
+        }
+
+        // the backup is complete without it, it falls back to the time in its name
+        if let Some(timestamp) = store::backup_file_timestamp(&dst) {
+            let setup = GameSetup::detect(&self.game_root);
+            let record = NewBackup { sha256, setup, metadata, duration: started.elapsed() };
+            if let Err(e) = Manifest::record_backup(dst.parent().unwrap(), timestamp, store::name_time(timestamp), record) {
+                warn!("could not record the time of `{}`: {}", dst.display(), e);
+            }
+        }
+
+        Ok(stored)
+    }
+
+    // the sha256 of the copy, checked against what was read: the save is not opened again after
+    // it, it may still be locked (copied from a shadow copy, or the retries ran out)
+    fn copy_verified(&self, src: &Path, dst: &Path, root: &Path) -> io::Result<String> {
+        // only the metadata, it can be read while the game holds the save
+        let stamp = |path: &Path| fs::metadata(path).and_then(|meta| Ok((meta.len(), meta.modified()?)));
+        let before = stamp(src)?;
+
+        // the game may still be writing the save, wait a little for it
+        let mut delay = self.lock_retry.delay;
+        let mut attempt = 0;
+        let read = loop {
+            attempt += 1;
+            match self.copy_with_progress(src, dst, root) {
+                Err(e) if is_lock_error(&e) && attempt <= self.lock_retry.attempts && !self.shutdown.is_cancelled() => {
+                    debug!("`{}` is locked, retry in {:?} ({}/{})", src.display(), delay, attempt, self.lock_retry.attempts);
+                    thread::sleep(delay);
+                    delay *= 2;
+                },
+                Err(e) if is_lock_error(&e) && self.lock_retry.shadow_copy => {
+                    warn!("`{}` is still locked, copy it from a volume shadow copy", src.display());
+                    break vss::copy_from_shadow(src, dst)?;
+                },
+                result => break result?,
+            }
+        };
+
+        let sha256 = store::sha256(dst)?;
+        if sha256 != read {
+            let _ = fs::remove_file(dst);
+            let msg = format!("copy of `{}` differs from what was read from it", src.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        // the copy may be half of the old save and half of the new one
+        if stamp(src).ok() != Some(before) {
+            let _ = fs::remove_file(dst);
+            return Err(io::Error::other(WrittenAgain));
+        }
+
+        // windows keeps it when copying, elsewhere the copy would not be taken as the same file
+        // (see `is_same_file`) and would look newer than the save
+        if let Err(e) = fs::File::options().write(true).open(dst).and_then(|file| file.set_modified(before.1)) {
+            debug!("could not keep the modified time of `{}`: {}", dst.display(), e);
+        }
+        Ok(sha256)
+    }
+
+    // reported in `copies` and as events once it took `COPY_PROGRESS_INTERVAL`, the sha256 of what
+    // was read
+    fn copy_with_progress(&self, src: &Path, dst: &Path, root: &Path) -> io::Result<String> {
+        let started = Instant::now();
+        let mut reported = started;
+        let result = store::copy_hashed_with_progress(src, dst, |copied, total| {
+            if reported.elapsed() < COPY_PROGRESS_INTERVAL || copied == total {
+                return;
+            }
+            reported = Instant::now();
+            let progress = CopyProgress { src: src.to_owned(), root: root.to_owned(), copied, total };
+            {
+                let mut copies = self.copies.lock().unwrap();
+                copies.retain(|copy| (&copy.src, &copy.root) != (&progress.src, &progress.root));
+                copies.push(progress.clone());
+            }
+            self.bus.emit(TaiwuEvent::CopyProgress(progress));
+        });
+        if reported != started {
+            self.copies.lock().unwrap().retain(|copy| (copy.src.as_path(), copy.root.as_path()) != (src, root));
+        }
+        result
+    }
+
+    // only when starting, a running backup may have one
+    fn remove_temp_files(&self) {
+        let _lock = match self.store_lock.acquire() {
+            Ok(lock) => lock,
+            Err(e) => {
+                debug!("could not look for unfinished backups: {}", e);
+                return;
+            },
+        };
+        for root in self.store_roots() {
+            match store::remove_temp_files(root) {
+                Ok(removed) => {
+                    for path in removed {
+                        warn!("removed `{}` of a backup cut short", path.display());
+                    }
+                },
+                Err(e) => debug!("could not look for unfinished backups in `{}`: {}", root.display(), e),
+            }
+        }
+    }
+
+    // Steam Cloud may put an older save back over the newer local one
+    fn check_cloud_conflict(&self, src: &Path, backup_folder: &Path) {
+        let latest = match store::latest_backup_file(backup_folder) {
+            Ok(Some(latest)) => latest,
+            _ => return,
+        };
+        let modified = |path: &Path| path.metadata().and_then(|meta| meta.modified());
+        let (src_modified, backup_modified) = match (modified(src), modified(&latest)) {
+            (Ok(a), Ok(b)) => (a, b),
+            _ => return,
+        };
+        if src_modified < backup_modified {
+            warn!(
+                kind = "cloud_conflict", src:% = src.display(), latest:% = latest.display();
+                "[Conflict] `{}` is older than the latest backup `{}`, maybe restored by Steam Cloud", src.display(), latest.display()
+            );
+            self.bus.emit(TaiwuEvent::CloudConflict {
+                src: src.to_owned(),
+                src_modified,
+                latest_backup: latest,
+                backup_modified,
+            });
+        }
+    }
+
+    fn store_roots(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.backup_root.as_path()).chain(self.mirror_roots.iter().map(PathBuf::as_path))
+    }
+
+    // and those of `world` only
+    fn store_roots_of(&self, world: Option<usize>) -> impl Iterator<Item = &Path> {
+        let world_roots = world.and_then(|world| self.world_settings.get(&world)).map(|settings| settings.mirror_roots.as_slice());
+        self.store_roots().chain(world_roots.unwrap_or_default().iter().map(PathBuf::as_path))
+    }
+
+    pub fn retry_outbox(&self) -> Result<()> {
+        // the jobs would be taken as done without a copy
+        if self.dry_run {
+            return Ok(());
+        }
+        let Some(_in_flight) = self.shutdown.begin() else {
+            return Ok(());
+        };
+        self.outbox.retry(|job| {
+            match self.copy_to_store(&job.src, &job.root, &job.relative) {
+                Ok(dst) => {
+                    info!("[Retry] {}", job.src.display());
+                    info!("[   to] {}", dst.display());
+                    if job.root == self.backup_root {
+                        self.enforce_quota(&dst);
+                    }
+                    self.bus.emit(TaiwuEvent::RetryDone { root: job.root.clone(), dst });
+                    true
+                },
+                Err(e) => {
+                    debug!("retry backup to `{}` failed (attempt {}): {}", job.root.display(), job.attempts + 1, e);
+                    false
+                }
+            }
+        })?;
+        Ok(())
+    }
+}
+
+// `--portable`, otherwise a `portable.flag` file decides
+pub fn set_portable(portable: bool) {
+    PORTABLE.store(portable, Ordering::SeqCst);
+}
+
+pub fn is_portable() -> bool {
+    PORTABLE.load(Ordering::SeqCst) || portable_root().is_some_and(|root| root.join(PORTABLE_FLAG_FILE_NAME).is_file())
+}
+
+// the folder of the executable
+fn portable_root() -> Option<PathBuf> {
+    std::env::current_exe().ok()?.parent().map(Path::to_owned)
+}
+
+// the folder of the config, history, outbox, logs and default backups from now on, over the
+// portable and the user's one, e.g. so the history of tests is not the user's (`MockGame` sets it)
+pub fn set_appdata_root(root: Option<PathBuf>) {
+    *APPDATA_ROOT.lock().unwrap() = root;
+}
+
+fn get_appdata_root_default() -> Result<PathBuf> {
+    if let Some(root) = APPDATA_ROOT.lock().unwrap().clone() {
+        return Ok(root);
+    }
+    if is_portable() {
+        return portable_root().ok_or(TaiwuError::BackupRootDefaultNotAvailable);
+    }
+    if let Some(base_dirs) = BaseDirs::new() {
+        Ok(base_dirs.data_local_dir().to_path_buf().join(APPDATA_FOLDER_NAME))
+    } else {
+        Err(TaiwuError::BackupRootDefaultNotAvailable)
+    }
+}
+
+// where the tray app and the service write their log files
+pub fn get_log_root_default() -> Result<PathBuf> {
+    Ok(get_appdata_root_default()?.join(LOG_FOLDER_NAME))
+}
+
+fn get_backup_root_default() -> Result<PathBuf> {
+    let backup_root = get_appdata_root_default()?.join(BACKUP_FOLDER_NAME);
+    Ok(backup_root)
+}
+
+fn print_event(event: &Event) -> String {
+    let paths = &event.paths;
+    let path_info = if paths.len() == 1 {
+        paths.get(0).unwrap().display().to_string()
+    } else {
+        format!("{:?}", paths)
+    };
+    format!("[{:?}] `{}`", event.kind, path_info)
+}
+
+// `<name>.<nanoseconds since the unix epoch>`, the same in every timezone; the name is kept as it
+// is, it may not be valid unicode
+fn new_backup_file_name_now(name: &std::ffi::OsStr) -> std::ffi::OsString {
+    let timestamp = chrono::Utc::now().timestamp_nanos();
+    let mut file_name = name.to_owned();
+    file_name.push(format!(".{}", timestamp));
+    file_name
+}
+
+fn world_folder_name(world: usize) -> String {
+    format!("{}{}", game().world_prefix(), world)
+}
+
+// `2` or `world_2`
+pub fn parse_world(text: &str) -> Option<usize> {
+    let text = text.trim();
+    text.parse().ok().or_else(|| world_number(text.as_ref()))
+}
+
+fn world_number(folder_name: &std::ffi::OsStr) -> Option<usize> {
+    folder_name.to_str()?.strip_prefix(game().world_prefix())?.parse().ok()
+}
+
+// `world_N` folders in `root`, none if it cannot be read
+fn find_worlds(root: &Path) -> Vec<usize> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| world_number(&entry.file_name()))
+        .collect()
+}
+
+// the save changed while it was copied, the copy is not kept and the change is backed up on its own
+#[derive(Debug)]
+struct WrittenAgain;
+
+impl fmt::Display for WrittenAgain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("written again while it was copied")
+    }
+}
+
+impl std::error::Error for WrittenAgain {}
+
+fn is_written_again(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|inner| inner.is::<WrittenAgain>())
+}
+
+fn is_lock_error(e: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(e.raw_os_error(), Some(32) | Some(33))
+}
+
+// so that a rename in it is on the disk too
+#[cfg(unix)]
+fn sync_folder(folder: &Path) -> io::Result<()> {
+    fs::File::open(folder)?.sync_all()
 }
 
-fn new_backup_file_name_now() -> String {
-    let now = chrono::offset::Local::now();
-    let timestamp = now.timestamp_nanos();
-    format!("{}.{}", TAIWU_GAME_SAVE_FILE_NAME, timestamp)
+// NTFS journals the rename itself, a folder can not be flushed
+#[cfg(not(unix))]
+fn sync_folder(_folder: &Path) -> io::Result<()> {
+    Ok(())
 }
 
 fn is_same_file(a: &fs::Metadata, b: &fs::Metadata) -> io::Result<bool> {
@@ -0,0 +1,119 @@
+// Log files of the tray app and the service in the appdata folder, one per day (and more
+// when a day gets too long), older ones are removed after `log_retention_days`.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::NaiveDate;
+
+// used if there is no appdata folder
+const LOG_TEMP_FOLDER_NAME: &str = "TaiwuBackupLogs";
+const LOG_FILE_PREFIX: &str = "taiwu-";
+const LOG_FILE_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+pub fn init() -> PathBuf {
+    let log_folder = taiwu::get_log_root_default().unwrap_or_else(|_| std::env::temp_dir().join(LOG_TEMP_FOLDER_NAME));
+    match RotatingFile::open(&log_folder, crate::early_config().log_retention_days) {
+        Ok(file) => crate::init_logger(simplelog::Config::default(), file),
+        Err(e) => eprintln!("could not open a log file in `{}`: {}", log_folder.display(), e),
+    }
+    log_folder
+}
+
+struct RotatingFile {
+    folder: PathBuf,
+    retention_days: u32,
+    day: NaiveDate,
+    // the n-th file of the day, 0 has no number in its name
+    index: u32,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    // appends to the last file of today
+    fn open(folder: &Path, retention_days: u32) -> io::Result<RotatingFile> {
+        fs::create_dir_all(folder)?;
+        prune(folder, retention_days);
+
+        let day = today();
+        let mut index = 0;
+        while log_file_path(folder, day, index + 1).exists() {
+            index += 1;
+        }
+        let (file, size) = open_log_file(&log_file_path(folder, day, index))?;
+        Ok(RotatingFile { folder: folder.to_owned(), retention_days, day, index, file, size })
+    }
+
+    fn rotate(&mut self, day: NaiveDate) -> io::Result<()> {
+        if day == self.day {
+            self.index += 1;
+        } else {
+            self.day = day;
+            self.index = 0;
+            prune(&self.folder, self.retention_days);
+        }
+        (self.file, self.size) = open_log_file(&log_file_path(&self.folder, self.day, self.index))?;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let day = today();
+        if day != self.day || (self.size > 0 && self.size + buf.len() as u64 > LOG_FILE_MAX_SIZE) {
+            self.rotate(day)?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn today() -> NaiveDate {
+    chrono::offset::Local::now().date_naive()
+}
+
+// `taiwu-2022-10-01.log`, then `taiwu-2022-10-01.1.log` and so on
+fn log_file_path(folder: &Path, day: NaiveDate, index: u32) -> PathBuf {
+    let name = match index {
+        0 => format!("{}{}.log", LOG_FILE_PREFIX, day),
+        _ => format!("{}{}.{}.log", LOG_FILE_PREFIX, day, index),
+    };
+    folder.join(name)
+}
+
+fn open_log_file(path: &Path) -> io::Result<(File, u64)> {
+    let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+// only our own log files, by their last write
+fn prune(folder: &Path, retention_days: u32) {
+    if retention_days == 0 {
+        return;
+    }
+    let Some(oldest) = SystemTime::now().checked_sub(Duration::from_secs(u64::from(retention_days) * 24 * 3600)) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !name.starts_with(LOG_FILE_PREFIX) || !name.ends_with(".log") {
+            continue;
+        }
+        let modified = path.metadata().and_then(|meta| meta.modified());
+        if modified.is_ok_and(|modified| modified < oldest) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
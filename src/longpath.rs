@@ -0,0 +1,131 @@
+// Paths over MAX_PATH (260) on windows, e.g. a Steam library deep in a folder with a Chinese
+// name. `std::fs` adds the `\\?\` prefix by itself when needed, only the paths given to other
+// APIs (the watcher) need it, and the paths they give back need it removed again.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+const VERBATIM_PREFIX: &str = r"\\?\";
+#[cfg(windows)]
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+// `C:\a` to `\\?\C:\a` and `\\server\share` to `\\?\UNC\server\share`, left as it is
+// if relative or not normalized (`..` is not resolved in verbatim paths)
+#[cfg(windows)]
+pub fn extended(path: &Path) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) => prefix.kind(),
+        _ => return path.to_owned(),
+    };
+    if components.clone().any(|component| matches!(component, Component::CurDir | Component::ParentDir)) {
+        return path.to_owned();
+    }
+    let text = path.as_os_str().to_string_lossy().replace('/', r"\");
+    let text = match prefix {
+        Prefix::Disk(_) => format!("{}{}", VERBATIM_PREFIX, text),
+        Prefix::UNC(..) => format!("{}{}", VERBATIM_UNC_PREFIX, text.trim_start_matches('\\')),
+        _ => return path.to_owned(),
+    };
+    // lossy only for names that are not valid unicode, keep those as they are
+    if text.contains(char::REPLACEMENT_CHARACTER) {
+        return path.to_owned();
+    }
+    PathBuf::from(text)
+}
+
+#[cfg(not(windows))]
+pub fn extended(path: &Path) -> PathBuf {
+    path.to_owned()
+}
+
+// the other way around, so the paths of events compare equal to the configured ones
+#[cfg(windows)]
+pub fn simplified(path: PathBuf) -> PathBuf {
+    let text = path.as_os_str().to_string_lossy();
+    if let Some(rest) = text.strip_prefix(VERBATIM_UNC_PREFIX) {
+        return PathBuf::from(format!(r"\\{}", rest));
+    }
+    match text.strip_prefix(VERBATIM_PREFIX) {
+        Some(rest) if !text.contains(char::REPLACEMENT_CHARACTER) => PathBuf::from(rest),
+        _ => path,
+    }
+}
+
+#[cfg(not(windows))]
+pub fn simplified(path: PathBuf) -> PathBuf {
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{synthetic_save, wait_for_backups, wait_for_watching, MockGame};
+    use crate::Result;
+
+    use std::thread;
+    use std::time::Duration;
+
+    #[cfg(windows)]
+    #[test]
+    fn extended_and_back() {
+        for (path, verbatim) in [
+            (r"C:\Steam\太吾绘卷", r"\\?\C:\Steam\太吾绘卷"),
+            ("C:/Steam/太吾绘卷", r"\\?\C:\Steam\太吾绘卷"),
+            (r"\\server\share\太吾绘卷", r"\\?\UNC\server\share\太吾绘卷"),
+        ] {
+            assert_eq!(extended(Path::new(path)), Path::new(verbatim));
+            assert_eq!(simplified(PathBuf::from(verbatim)), Path::new(&path.replace('/', r"\")));
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn extended_leaves_prefixed_and_relative() {
+        for path in [r"\\?\C:\a", r"\\?\UNC\server\share", r"Save\world_1", r"..\Save", r"C:\a\..\b", r"\a"] {
+            assert_eq!(extended(Path::new(path)), Path::new(path));
+        }
+        for path in [r"C:\a", r"Save\world_1", r"\\server\share"] {
+            assert_eq!(simplified(PathBuf::from(path)), Path::new(path));
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unchanged_elsewhere() {
+        for path in ["/home/taiwu/太吾绘卷", "Save/world_1", "../Save"] {
+            assert_eq!(extended(Path::new(path)), Path::new(path));
+            assert_eq!(simplified(PathBuf::from(path)), Path::new(path));
+        }
+    }
+
+    // over MAX_PATH with the world folder and the save, in folders of a few hundred bytes each
+    #[test]
+    fn backup_and_restore_in_a_long_chinese_path() -> Result<()> {
+        let folder: PathBuf = (0..8).map(|_| "太吾绘卷存档自动备份测试".repeat(3)).collect();
+        let game = MockGame::in_folder(&folder)?;
+        game.write_save(1, &synthetic_save(1, 0, 4096))?;
+        assert!(game.save_path(1).as_os_str().to_string_lossy().chars().count() > 260);
+
+        let tw = game.taiwu()?;
+        tw.backup_once().into_result()?;
+        let first = game.read_save(1)?;
+        thread::scope(|s| {
+            let watch = s.spawn(|| tw.watch());
+            assert!(wait_for_watching(&tw, Duration::from_secs(20)));
+            game.replace_save(1, &synthetic_save(1, 1, 4096))?;
+            let backed_up = wait_for_backups(&tw, 1, 2, Duration::from_secs(20));
+            tw.stop();
+            watch.join().unwrap()?;
+            assert!(backed_up, "the watcher sees a save in a long path");
+            Result::Ok(())
+        })?;
+
+        let backups = tw.list_backups(1)?;
+        tw.restore(1, &backups[0].id)?;
+        assert_eq!(game.read_save(1)?, first);
+        Ok(())
+    }
+}
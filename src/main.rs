@@ -1,127 +1,327 @@
 // Prevent showing the console window
 #![windows_subsystem = "windows"]
 
-use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use log::{debug, error};
-use simplelog::{Config, LevelFilter, WriteLogger};
-use tray_item::TrayItem;
+use clap::Parser;
+use log::{debug, error, info};
+use simplelog::{LevelFilter, WriteLogger};
 
-use taiwu::Taiwu;
+use taiwu::heartbeat;
+use taiwu::notifier::{self, LogNotifier, Subscription};
+use taiwu::{ipc, Config, ConfigOverrides, InstanceGuard, LogFormat, NotifierBackend, NotifierConfig, Taiwu, TaiwuError, WatchEnd};
+
+use i18n::tr;
+
+#[cfg(feature = "tui")]
+mod browse;
+mod cli;
+#[cfg(feature = "tray")]
+mod hotkey;
+mod i18n;
+mod jsonlog;
+// log files are only for the tray and the service, the daemon logs to stdout
+#[cfg(any(windows, feature = "tray"))]
+mod logfile;
+#[cfg(feature = "tray")]
+mod notification;
+#[cfg(windows)]
+mod service;
+#[cfg(windows)]
+mod session;
+#[cfg(unix)]
+mod signals;
+#[cfg(feature = "tray")]
+mod setup;
+#[cfg(feature = "tray")]
+mod tray;
+#[cfg(feature = "update")]
+mod update;
 
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+#[cfg(any(feature = "tray", feature = "update"))]
 const APP_REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
- 
-// Instead of env!("CARGO_PKG_AUTHORS")
-const APP_AUTHOR: &'static str = "owtotwo";
- 
-const LOG_TEMP_FOLDER_NAME: &'static str = "TaiwuBackupLogs";
 
 
 fn main() {
-    let log_folder = temp_log_folder();
-    let log_file = temp_log_file(&log_folder).unwrap();
-    let _ = WriteLogger::init(LevelFilter::Info, Config::default(), log_file);
+    if std::env::args_os().len() > 1 {
+        cli::attach_console();
+    }
 
-    let title = format!("Taiwu Backup (v{}) by {}", APP_VERSION, APP_AUTHOR);
-    let mut tray = TrayItem::new(&title, "TAIWU_ICON_1").unwrap();
+    let args = cli::Cli::parse();
+    taiwu::set_portable(args.portable);
+    taiwu::set_break_lock(args.force);
+    taiwu::set_config_overrides(ConfigOverrides {
+        config_file: args.config.clone(),
+        profile: args.profile.clone(),
+        game_root: args.game_root.clone(),
+        backup_root: args.backup_root.clone(),
+        log_level: args.log_level,
+    });
+    i18n::init(early_config().language.as_deref());
+    #[cfg(feature = "update")]
+    update::remove_replaced();
 
-    let tw = match Taiwu::new() {
+    // the subcommands only do something and quit, no watcher is started
+    let _guard = match args.command {
+        Some(_) => None,
+        // started again by the tray, which is still quitting
+        None if args.restarted => match InstanceGuard::wait(taiwu::SHUTDOWN_TIMEOUT * 2) {
+            Ok(guard) => Some(guard),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return;
+            },
+        },
+        None => match InstanceGuard::acquire() {
+            Ok(guard) => Some(guard),
+            Err(TaiwuError::AlreadyRunning) => {
+                alert(&tr!("太吾绘卷存档自动备份已经在运行中", "Taiwu Backup is already running"));
+                return;
+            },
+            Err(e) => {
+                eprintln!("error: {}", e);
+                None
+            }
+        },
+    };
+
+    #[cfg(windows)]
+    if args.service {
+        logfile::init();
+        if let Err(e) = service::run() {
+            error!("[service] error: {:?}", e);
+        }
+        return;
+    }
+
+    match args.command {
+        Some(command) => {
+            // the commands only log when asked to, or in a dry run
+            if let Some(level) = args.log_level {
+                let _ = WriteLogger::init(level.into(), simplelog::Config::default(), io::stderr());
+            }
+            if let Err(e) = cli::run(command, args.dry_run, args.json) {
+                eprintln!("{}", tr!("错误: {}", "error: {}", e));
+                let mut source = e.source();
+                while let Some(e) = source {
+                    eprintln!("{}", tr!("  原因: {}", "  caused by: {}", e));
+                    source = e.source();
+                }
+                std::process::exit(1);
+            }
+        },
+        None if args.daemon => run_daemon(args.dry_run),
+        #[cfg(feature = "tray")]
+        None => tray::run(args.dry_run),
+        #[cfg(not(feature = "tray"))]
+        None => run_daemon(args.dry_run),
+    }
+}
+
+// tell the user even if there is no console, e.g. started by double clicking
+fn alert(msg: &str) {
+    eprintln!("{}", msg);
+
+    #[cfg(windows)]
+    {
+        use std::iter::once;
+        use windows_sys::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONINFORMATION, MB_OK};
+
+        let text: Vec<u16> = msg.encode_utf16().chain(once(0)).collect();
+        let caption: Vec<u16> = "Taiwu Backup".encode_utf16().chain(once(0)).collect();
+        unsafe {
+            MessageBoxW(0, text.as_ptr(), caption.as_ptr(), MB_OK | MB_ICONINFORMATION);
+        }
+    }
+}
+
+// read before the logger is set, the config is loaded again after it so that loading it is logged
+fn early_config() -> Config {
+    Config::load().unwrap_or_default()
+}
+
+// at the configured level, which can be changed later with `LogLevel::apply`
+fn init_logger(text_config: simplelog::Config, writer: impl io::Write + Send + 'static) {
+    let config = early_config();
+    let result = match config.log_format {
+        LogFormat::Text => WriteLogger::init(LevelFilter::Trace, text_config, writer),
+        LogFormat::Json => jsonlog::init(LevelFilter::Trace, writer),
+    };
+    if result.is_ok() {
+        log::set_max_level(LevelFilter::from(config.log_level));
+    }
+}
+
+fn load_taiwu(dry_run: bool) -> taiwu::Result<(Config, Taiwu)> {
+    let config = match cli::load_config(dry_run) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("[config] error: {:?}", e);
+            return Err(e);
+        }
+    };
+
+    let tw = match Taiwu::with_config(&config) {
         Ok(tw) => tw,
         Err(e) => {
             error!("[new] error: {:?}", e);
-            return;
+            return Err(e);
         }
     };
 
     debug!("{:?}", tw);
 
-    let tw = Arc::new(tw);
-
-    tray.add_label("[*正在运行中]").unwrap();
-
-    let game_folder = tw.game_root();
-    tray.add_menu_item("打开游戏目录", move || {
-        debug!("Open game folder occurred!");
-        open_folder_in_explorer(&game_folder);
-    })
-    .unwrap();
-
-    let backup_folder = tw.backup_root();
-    tray.add_menu_item("打开备份目录", move || {
-        debug!("Open backup folder occurred!");
-        open_folder_in_explorer(&backup_folder);
-    })
-    .unwrap();
-
-    tray.add_menu_item("打开日志目录", move || {
-        debug!("Open log folder occurred!");
-        open_folder_in_explorer(&log_folder);
-    })
-    .unwrap();
+    Ok((config, tw))
+}
 
-    tray.add_menu_item("打开GitHub项目", move || {
-        debug!("Open github repository of this program occurred!");
-        open_url_in_browser(APP_REPOSITORY);
-    })
-    .unwrap();
+#[cfg(feature = "http")]
+fn serve_http(tw: &Arc<Taiwu>, config: &Config) {
+    if let Some(port) = config.http_port {
+        if let Err(e) = taiwu::http::serve(Arc::clone(tw), port) {
+            error!("[http] error: {:?}", e);
+        }
+    }
+}
 
-    let tw1 = Arc::clone(&tw);
-    tray.add_menu_item("退出", move || {
-        debug!("Quit occurred!");
-        tw1.unwatch(); // tricky, then watch will return, so handle.join() finish
-    })
-    .unwrap();
-
-    // do backup once on every boot if it has not been backed up
-    if let Err(e) = tw.backup_once_for_new_save() {
-        error!("[backup_once] error: {:?}", e);
-        return;
+#[cfg(not(feature = "http"))]
+fn serve_http(_tw: &Arc<Taiwu>, config: &Config) {
+    if config.http_port.is_some() {
+        log::warn!("`http_port` is set but this build has no `http` feature, ignored");
     }
+}
 
-    let handle = thread::spawn(move || {
-        if let Err(e) = tw.watch() {
-            error!("[watch] error: {:?}", e);
-            return;
+// join it after shutting down, so the last write says it stopped
+fn start_heartbeat(tw: &Arc<Taiwu>, config: &Config) -> Option<JoinHandle<()>> {
+    (config.heartbeat_interval_secs > 0)
+        .then(|| heartbeat::spawn(Arc::clone(tw), Duration::from_secs(config.heartbeat_interval_secs)))
+}
+
+// toasts only where there is a desktop to show them, the tray app
+fn start_notifiers(tw: &Arc<Taiwu>, config: &Config, toasts: bool) {
+    let mut subscriptions = Vec::new();
+    for notifier in config.notifiers() {
+        let events = notifier.events.clone();
+        match notifier.backend {
+            NotifierBackend::Toast => match toast(tw, toasts, Duration::from_secs(config.notification_cooldown_secs)) {
+                Some(toast) => subscriptions.push(Subscription { notifier: toast, events }),
+                None => debug!("no toasts without the tray app, ignored"),
+            },
+            NotifierBackend::Log => subscriptions.push(Subscription::new(LogNotifier, events)),
+            NotifierBackend::Webhook => match webhook(&notifier) {
+                Some(Ok(webhook)) => subscriptions.push(Subscription { notifier: webhook, events }),
+                Some(Err(e)) => error!("[webhook] error: {:?}", e),
+                None => {},
+            },
+            // it picks what to mail about itself, from every notification
+            NotifierBackend::Email => match email(tw, &notifier) {
+                Some(Ok(email)) => subscriptions.push(Subscription { notifier: email, events: Vec::new() }),
+                Some(Err(e)) => error!("[email] error: {:?}", e),
+                None => {},
+            },
         }
-    });
+    }
+    notifier::spawn(tw, subscriptions);
+}
 
-    handle.join().unwrap();
+#[cfg(feature = "tray")]
+fn toast(tw: &Arc<Taiwu>, toasts: bool, cooldown: Duration) -> Option<Box<dyn taiwu::Notifier>> {
+    toasts.then(|| Box::new(notification::Toast::new(Arc::clone(tw), cooldown)) as Box<dyn taiwu::Notifier>)
 }
 
-fn temp_log_file(folder: &Path) -> io::Result<fs::File> {
-    fs::create_dir_all(folder)?;
+#[cfg(not(feature = "tray"))]
+fn toast(_tw: &Arc<Taiwu>, _toasts: bool, _cooldown: Duration) -> Option<Box<dyn taiwu::Notifier>> {
+    None
+}
 
-    let now = chrono::offset::Local::now();
-    let timestamp = now.timestamp_nanos();
-    let name = format!("{}.log", timestamp);
+#[cfg(feature = "webhook")]
+fn webhook(notifier: &NotifierConfig) -> Option<taiwu::Result<Box<dyn taiwu::Notifier>>> {
+    let Some(url) = &notifier.url else {
+        log::warn!("a `webhook` notifier without `url`, ignored");
+        return None;
+    };
+    Some(taiwu::webhook::Webhook::new(url, notifier.format).map(|webhook| Box::new(webhook) as Box<dyn taiwu::Notifier>))
+}
 
-    let file_path = folder.join(&name);
+#[cfg(not(feature = "webhook"))]
+fn webhook(_notifier: &NotifierConfig) -> Option<taiwu::Result<Box<dyn taiwu::Notifier>>> {
+    log::warn!("a webhook is configured but this build has no `webhook` feature, ignored");
+    None
+}
 
-    fs::File::create(file_path)
+#[cfg(feature = "email")]
+fn email(tw: &Arc<Taiwu>, notifier: &NotifierConfig) -> Option<taiwu::Result<Box<dyn taiwu::Notifier>>> {
+    Some(taiwu::email::Email::new(tw, &notifier.email).map(|email| Box::new(email) as Box<dyn taiwu::Notifier>))
 }
 
-fn temp_log_folder() -> PathBuf {
-    let temp = std::env::temp_dir();
-    temp.join(LOG_TEMP_FOLDER_NAME)
+#[cfg(not(feature = "email"))]
+fn email(_tw: &Arc<Taiwu>, _notifier: &NotifierConfig) -> Option<taiwu::Result<Box<dyn taiwu::Notifier>>> {
+    log::warn!("an email notifier is configured but this build has no `email` feature, ignored");
+    None
 }
 
-fn open_folder_in_explorer(folder: &Path) {
-    use std::process::Command;
+// foreground without tray, logs to stdout for systemd and the like
+fn run_daemon(dry_run: bool) {
+    #[cfg(unix)]
+    signals::block();
+    let config = simplelog::ConfigBuilder::new()
+        .set_time_format_rfc3339()
+        .set_target_level(LevelFilter::Error)
+        .build();
+    init_logger(config, io::stdout());
+
+    let (config, tw) = match load_taiwu(dry_run) {
+        Ok((config, tw)) => (config, Arc::new(tw)),
+        Err(_) => std::process::exit(1),
+    };
+
+    info!("[daemon] started, v{}", APP_VERSION);
 
-    match Command::new("explorer").arg(folder).spawn() {
-        Ok(_) => debug!("Opened folder `{}` in explorer", folder.display()),
-        Err(e) => error!("An error occurred when opening folder `{}` in explorer: \n{}", folder.display(), e),
+    #[cfg(windows)]
+    {
+        session::spawn(&tw);
+        session::handle_console_events();
     }
-}
+    #[cfg(unix)]
+    signals::spawn(&tw);
+
+    let tw1 = Arc::clone(&tw);
+    if let Err(e) = ipc::serve(Arc::clone(&tw), move || tw1.shutdown()) {
+        error!("[ipc] error: {:?}", e);
+    }
+    serve_http(&tw, &config);
+    start_notifiers(&tw, &config, false);
+    let heartbeat = start_heartbeat(&tw, &config);
 
-fn open_url_in_browser(url: &str) {
-    match open::that(url) {
-        Ok(()) => debug!("Open url `{}` in default browser", url),
-        Err(e) => error!("An error occurred when opening url `{}` in default browser: \n{}", url, e),
+    // only logged, there is no one to notify
+    #[cfg(feature = "update")]
+    if config.check_updates {
+        update::spawn_checker(|_| {});
+    }
+
+    // the failures are logged one by one, the saves are still protected from their next change
+    let report = tw.backup_once_for_new_save();
+    if !report.failed.is_empty() {
+        error!("[backup_once] {} files not backed up", report.failed.len());
+    }
+
+    let end = match tw.watch() {
+        Ok(end) => end,
+        Err(e) => {
+            error!("[watch] error: {:?}", e);
+            std::process::exit(1);
+        },
+    };
+    if let Some(heartbeat) = heartbeat.filter(|_| tw.is_shutting_down()) {
+        let _ = heartbeat.join();
+    }
+    // so a service manager sees the backups cut short
+    if let WatchEnd::Unfinished { in_flight } = end {
+        error!("[daemon] stopped with {} backups or restores unfinished", in_flight);
+        std::process::exit(1);
     }
+    info!("[daemon] stopped");
 }
\ No newline at end of file
@@ -0,0 +1,148 @@
+// What is known about the backups of a world besides their files, `manifest.json` in its
+// folder of the backup root.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::game_root::GameSetup;
+use crate::game::game;
+use crate::{chunks, pack, store};
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    // by backup id, older backups only have their name until the next backup numbers them
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub backups: BTreeMap<String, BackupRecord>,
+    // the number of the next backup, never given twice even when backups are removed
+    pub next_seq: u64,
+    // backups the integrity scan moved to `quarantine`, oldest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub quarantined: Vec<QuarantineRecord>,
+    // oldest first
+    pub restores: Vec<RestoreRecord>,
+    // id of the backup put back whenever the game writes the save, see `Taiwu::freeze`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frozen: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRecord {
+    // RFC 3339 in UTC
+    pub time: DateTime<Utc>,
+    // counted up in the world (folder), still in order when the clock is turned back
+    pub seq: u64,
+    // of its content when it was copied, none for backups numbered afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // of the game when it was backed up, none for backups from before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setup: Option<GameSetup>,
+    // what the game profile read from the save, see `GameProfile::parse_metadata`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    // of copying it into this store, none for backups from before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+// what is recorded of a backup just made
+pub struct NewBackup {
+    pub sha256: String,
+    pub setup: GameSetup,
+    pub metadata: BTreeMap<String, String>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineRecord {
+    pub time: DateTime<Utc>,
+    // where it is now
+    pub file: PathBuf,
+    pub reason: String,
+}
+
+// one restore into the world, with the copy of the save it replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreRecord {
+    // nanoseconds since the unix epoch, like backup ids
+    pub time: i64,
+    // the id of the backup restored, of `from_world`
+    pub backup: String,
+    pub from_world: usize,
+    // in the pre-restore folder, none if the slot had no save
+    pub snapshot: Option<PathBuf>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl Manifest {
+    pub fn load(folder: &Path) -> io::Result<Manifest> {
+        let path = folder.join(MANIFEST_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Manifest::default());
+        }
+        let text = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    // the records of backups that are gone are dropped with it, backups without one are
+    // numbered first in the order of their names
+    pub fn record_backup(folder: &Path, timestamp: i64, time: DateTime<Utc>, backup: NewBackup) -> io::Result<()> {
+        let mut manifest = Manifest::load(folder)?;
+        let packed: Vec<String> = pack::list(folder)?.into_iter().map(|(_, file)| file.name).collect();
+        manifest.backups.retain(|id, _| {
+            let name = format!("{}.{}", game().save_file_name(), id);
+            let path = folder.join(&name);
+            path.is_file() || chunks::list_path(&path).is_file() || packed.contains(&name)
+        });
+
+        let mut unrecorded = Vec::new();
+        for entry in fs::read_dir(folder)? {
+            let path = entry?.path();
+            if let Some(old) = store::backup_file_timestamp(&path).or_else(|| Some(store::chunked_backup(&path)?.1)) {
+                if old != timestamp && !manifest.backups.contains_key(&old.to_string()) {
+                    unrecorded.push(old);
+                }
+            }
+        }
+        unrecorded.sort_unstable();
+        for old in unrecorded {
+            let seq = manifest.take_seq();
+            manifest.backups.insert(old.to_string(), BackupRecord { time: store::name_time(old), seq, sha256: None, setup: None, metadata: BTreeMap::new(), duration_ms: None });
+        }
+        let seq = manifest.take_seq();
+        manifest.backups.insert(timestamp.to_string(), BackupRecord {
+            time,
+            seq,
+            sha256: Some(backup.sha256),
+            setup: Some(backup.setup),
+            metadata: backup.metadata,
+            duration_ms: Some(backup.duration.as_millis() as u64),
+        });
+        manifest.save(folder)
+    }
+
+    fn take_seq(&mut self) -> u64 {
+        let seq = self.next_seq.max(1);
+        self.next_seq = seq + 1;
+        seq
+    }
+
+    // never left half written, it is the only record of the snapshots
+    pub fn save(&self, folder: &Path) -> io::Result<()> {
+        fs::create_dir_all(folder)?;
+        let path = folder.join(MANIFEST_FILE_NAME);
+        let tmp = store::temp_path(&path);
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp, path)
+    }
+}
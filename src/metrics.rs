@@ -0,0 +1,95 @@
+// Counters of the backups since the start, in the Prometheus text format for `GET /metrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// seconds, the upper bounds of the duration histogram
+const DURATION_BUCKETS: [f64; 8] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0, 30.0];
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    // by world, `extra` for the extra files
+    backups: BTreeMap<String, u64>,
+    bytes: BTreeMap<String, u64>,
+    duration_buckets: [u64; DURATION_BUCKETS.len()],
+    duration_sum: f64,
+    duration_count: u64,
+    errors: u64,
+}
+
+impl Metrics {
+    pub fn backup(&self, world: Option<usize>, bytes: u64, duration: Duration) {
+        let label = world_label(world);
+        let seconds = duration.as_secs_f64();
+        let mut counters = self.counters.lock().unwrap();
+        *counters.backups.entry(label.clone()).or_default() += 1;
+        *counters.bytes.entry(label).or_default() += bytes;
+        for (count, bound) in counters.duration_buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= bound {
+                *count += 1;
+            }
+        }
+        counters.duration_sum += seconds;
+        counters.duration_count += 1;
+    }
+
+    pub fn error(&self) {
+        self.counters.lock().unwrap().errors += 1;
+    }
+
+    // `last_backups` are the unix seconds of the newest backup of each world in the store,
+    // so they are right after a restart too
+    pub fn render(&self, last_backups: &[(usize, i64)]) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut text = String::new();
+
+        header(&mut text, "taiwu_backups_total", "counter", "Backups made since the start");
+        for (world, count) in &counters.backups {
+            let _ = writeln!(text, "taiwu_backups_total{{world=\"{}\"}} {}", world, count);
+        }
+
+        header(&mut text, "taiwu_backup_bytes_total", "counter", "Bytes backed up since the start");
+        for (world, bytes) in &counters.bytes {
+            let _ = writeln!(text, "taiwu_backup_bytes_total{{world=\"{}\"}} {}", world, bytes);
+        }
+
+        header(&mut text, "taiwu_backup_duration_seconds", "histogram", "Time to copy one file to all stores");
+        for (count, bound) in counters.duration_buckets.iter().zip(DURATION_BUCKETS) {
+            let _ = writeln!(text, "taiwu_backup_duration_seconds_bucket{{le=\"{}\"}} {}", bound, count);
+        }
+        let _ = writeln!(text, "taiwu_backup_duration_seconds_bucket{{le=\"+Inf\"}} {}", counters.duration_count);
+        let _ = writeln!(text, "taiwu_backup_duration_seconds_sum {}", counters.duration_sum);
+        let _ = writeln!(text, "taiwu_backup_duration_seconds_count {}", counters.duration_count);
+
+        header(&mut text, "taiwu_errors_total", "counter", "Backups and store copies that failed since the start");
+        let _ = writeln!(text, "taiwu_errors_total {}", counters.errors);
+
+        header(&mut text, "taiwu_last_backup_timestamp_seconds", "gauge", "Unix time of the newest backup of a world");
+        for (world, time) in last_backups {
+            let _ = writeln!(text, "taiwu_last_backup_timestamp_seconds{{world=\"{}\"}} {}", world, time);
+        }
+
+        text
+    }
+}
+
+// of copying `bytes` in `duration`
+pub fn bytes_per_sec(bytes: u64, duration: Duration) -> u64 {
+    (bytes as f64 / duration.as_secs_f64().max(0.001)) as u64
+}
+
+fn world_label(world: Option<usize>) -> String {
+    world.map_or_else(|| "extra".to_owned(), |world| world.to_string())
+}
+
+fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+}
@@ -0,0 +1,149 @@
+// Moving the store to another backup root (`backup_root` of the config, with the folders of every
+// profile in it). Every file is copied and read back before anything else changes, the config is
+// pointed at the new root once all of them are there, and only then are the old files removed
+// (unless kept). Failed copies waiting to be retried follow it, the mirrors stay where they are.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::bus::CopyProgress;
+use crate::config::config_override;
+use crate::ipc::{self, Request};
+use crate::outbox::Outbox;
+use crate::storelock::{StoreLock, STORE_LOCK_FILE_NAME};
+use crate::{get_appdata_root_default, get_backup_root_default, longpath, set_config_value, store, Config, Result, TaiwuError, OUTBOX_FOLDER_NAME};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrateReport {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+    // the old files are still there
+    pub kept: bool,
+    // failed copies now retried to the new root
+    pub retries_moved: usize,
+}
+
+// nothing is copied in a dry run, the report says what would be
+pub fn migrate_store(to: &Path, keep: bool, dry_run: bool, mut progress: impl FnMut(&CopyProgress)) -> Result<MigrateReport> {
+    // it would go on writing to the old root
+    if ipc::send(Request::Status).is_ok() {
+        return Err(TaiwuError::AlreadyRunning);
+    }
+    // only the config file is changed, the next start would use the emptied old root
+    if let Some(by) = config_override("backup_root") {
+        return Err(TaiwuError::MigrateRootOverridden { by });
+    }
+    let config = Config::load()?;
+    let from = match &config.backup_root {
+        Some(root) => root.clone(),
+        None => get_backup_root_default()?,
+    };
+    let invalid = |message: &str| TaiwuError::MigrateTargetInvalid { path: to.to_owned(), message: message.to_owned() };
+    if !from.is_dir() {
+        return Err(TaiwuError::MigrateTargetInvalid { path: from, message: "there is no store to move".to_owned() });
+    }
+    let (from, to) = (absolute(&from)?, absolute(to)?);
+    if to.starts_with(&from) || from.starts_with(&to) {
+        return Err(invalid("it is the store, or a folder in it or around it"));
+    }
+    if to.exists() && (!to.is_dir() || fs::read_dir(&to)?.next().is_some()) {
+        return Err(invalid("it is not an empty folder"));
+    }
+
+    let store_lock = StoreLock::new(&from, !dry_run);
+    let _lock = store_lock.acquire()?;
+    let files = list_files(&from)?;
+    let bytes = files.iter().map(|(_, len)| len).sum();
+    let mut report = MigrateReport { from: from.clone(), to: to.clone(), files: files.len(), bytes, kept: keep, retries_moved: 0 };
+    if let Some(existing) = to.ancestors().find(|path| path.exists()) {
+        let available = store::available_space(existing)?;
+        if available < bytes {
+            return Err(TaiwuError::InsufficientSpace { root: to, available, required: bytes });
+        }
+    }
+    if dry_run {
+        info!("[Dry Run] move {} files ({} bytes) from `{}` to `{}`", files.len(), bytes, from.display(), to.display());
+        return Ok(report);
+    }
+
+    info!("Move the store from `{}` to `{}`: {} files, {} bytes", from.display(), to.display(), files.len(), bytes);
+    fs::create_dir_all(&to)?;
+    let mut copied = 0;
+    for (src, _) in &files {
+        let dst = to.join(src.strip_prefix(&from).unwrap());
+        let failed = |source| TaiwuError::BackupCopyFailed { world: None, src: src.clone(), dst: dst.clone(), source };
+        fs::create_dir_all(dst.parent().unwrap()).map_err(failed)?;
+        let before = copied;
+        store::clone_or_copy_with_progress(src, &dst, |done, _| {
+            progress(&CopyProgress { src: src.clone(), root: to.clone(), copied: before + done, total: bytes });
+        })
+        .map_err(failed)?;
+        if store::sha256(src).map_err(failed)? != store::sha256(&dst).map_err(failed)? {
+            return Err(TaiwuError::MigrateVerifyFailed { src: src.clone(), dst });
+        }
+        copied = before + fs::metadata(src)?.len();
+    }
+    info!("Copied and verified {} files", files.len());
+
+    set_config_value("backup_root", &to.to_string_lossy())?;
+    let outbox = Outbox::open(get_appdata_root_default()?.join(OUTBOX_FOLDER_NAME));
+    report.retries_moved = outbox.move_root(&from, &to)?;
+
+    if !keep {
+        for (src, _) in &files {
+            if let Err(e) = fs::remove_file(src) {
+                warn!("could not remove `{}`: {}", src.display(), e);
+            }
+        }
+        remove_empty_folders(&from);
+        info!("Removed the old files in `{}`", from.display());
+    }
+    Ok(report)
+}
+
+// also of a folder not made yet, without the `\\?\` prefix canonical paths get on windows
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let path = std::env::current_dir()?.join(path);
+    let existing = path.ancestors().find(|path| path.exists()).unwrap();
+    let canonical = longpath::simplified(existing.canonicalize()?);
+    match path.strip_prefix(existing).unwrap() {
+        // a join would add a separator at the end
+        rest if rest.as_os_str().is_empty() => Ok(canonical),
+        rest => Ok(canonical.join(rest)),
+    }
+}
+
+// with their size, not the lock, which goes away with the last change
+fn list_files(folder: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            files.extend(list_files(&path)?);
+        } else if path.file_name().is_some_and(|name| name != STORE_LOCK_FILE_NAME) {
+            files.push((path, entry.metadata()?.len()));
+        }
+    }
+    Ok(files)
+}
+
+// the folder itself stays, it may be a drive or hold things of its own
+fn remove_empty_folders(folder: &Path) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            remove_empty_folders(&path);
+            let _ = fs::remove_dir(&path);
+        }
+    }
+}
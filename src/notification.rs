@@ -0,0 +1,185 @@
+// Desktop notifications of the tray app, failures are only logged.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, warn};
+
+use taiwu::{Notification, Notifier, Taiwu, TaiwuEvent};
+
+use crate::i18n::tr;
+
+const NOTIFICATION_APP_NAME: &str = "Taiwu Backup";
+
+// the `toast` notifier, in the language of the app
+pub struct Toast {
+    tw: Arc<Taiwu>,
+    cooldown: Duration,
+    burst: Arc<Mutex<Burst>>,
+}
+
+// the backups done since the toast of one, shown together once `cooldown` has passed
+#[derive(Default)]
+struct Burst {
+    until: Option<Instant>,
+    count: usize,
+}
+
+impl Toast {
+    pub fn new(tw: Arc<Taiwu>, cooldown: Duration) -> Toast {
+        Toast { tw, cooldown, burst: Arc::default() }
+    }
+
+    fn backup_done(&self, src: &Path) {
+        let mut burst = self.burst.lock().unwrap();
+        if burst.until.is_some_and(|until| Instant::now() < until) {
+            burst.count += 1;
+            return;
+        }
+        show(&tr!("备份完成", "Backup done"), &src.display().to_string());
+        if self.cooldown.is_zero() {
+            return;
+        }
+        *burst = Burst { until: Some(Instant::now() + self.cooldown), count: 0 };
+
+        let (cooldown, burst) = (self.cooldown, Arc::clone(&self.burst));
+        thread::spawn(move || {
+            thread::sleep(cooldown);
+            let count = std::mem::take(&mut *burst.lock().unwrap()).count;
+            if count > 0 {
+                let body = tr!(
+                    "最近 {} 内共完成 {} 次备份",
+                    "{1} backups in the last {0}",
+                    format_duration(cooldown),
+                    count + 1,
+                );
+                show(&tr!("备份完成", "Backup done"), &body);
+            }
+        });
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        60 => tr!("1 分钟", "minute"),
+        secs if secs % 60 == 0 => tr!("{} 分钟", "{} minutes", secs / 60),
+        secs => tr!("{} 秒", "{} seconds", secs),
+    }
+}
+
+impl Notifier for Toast {
+    fn notify(&self, notification: &Notification) {
+        let (summary, body) = match &notification.event {
+            TaiwuEvent::SaveTruncated { world, size, .. } => {
+                let body = tr!(
+                    "存档位 {} 的存档只有 {} 字节，游戏可能在写入时崩溃了，未备份。可在托盘菜单中恢复最新的完好备份",
+                    "The save of world {} is only {} bytes, the game may have crashed while writing it, it was not backed up. The latest good backup can be restored from the tray menu",
+                    world,
+                    size,
+                );
+                let (tw, world) = (Arc::clone(&self.tw), *world);
+                show_with_action(&tr!("存档损坏", "Save damaged"), &body, &tr!("恢复最新备份", "Restore latest backup"), move || {
+                    restore_latest_good(&tw, world);
+                });
+                return;
+            },
+            TaiwuEvent::GameStarted => (tr!("游戏已启动", "Game started"), tr!("正在监控存档变化", "Watching the saves")),
+            TaiwuEvent::GameStopped => (tr!("游戏已退出", "Game stopped"), tr!("正在备份所有存档位", "Backing up all worlds")),
+            TaiwuEvent::WatcherRestarted { running, .. } => {
+                let body = if *running {
+                    tr!("存档监控出现问题，已自动重启", "Watching the saves failed, it was restarted")
+                } else {
+                    tr!("存档监控出现问题且无法重启，稍后会再试", "Watching the saves failed and could not restart, will retry")
+                };
+                (tr!("存档监控", "Save watcher"), body)
+            },
+            TaiwuEvent::InsufficientSpace { root, available, .. } => {
+                let body = tr!(
+                    "`{}` 仅剩 {} MB 可用空间，已跳过备份",
+                    "Only {1} MB free on `{0}`, the backup was skipped",
+                    root.display(),
+                    available / 1024 / 1024,
+                );
+                (tr!("磁盘空间不足", "Not enough disk space"), body)
+            },
+            TaiwuEvent::BackupDone { src, .. } => return self.backup_done(src),
+            TaiwuEvent::BackupFailed { src, error } => {
+                (tr!("备份失败", "Backup failed"), format!("{}: {}", src.display(), error))
+            },
+            TaiwuEvent::CloudConflict { src, .. } => {
+                let body = tr!(
+                    "`{}` 比最新的备份还旧，可能被云存档覆盖了",
+                    "`{}` is older than its latest backup, the cloud may have replaced it",
+                    src.display(),
+                );
+                (tr!("存档冲突", "Save conflict"), body)
+            },
+            TaiwuEvent::SaveShrank { world, size, average, .. } => {
+                let body = tr!(
+                    "存档位 {} 的存档变小了很多({} KB，最近的备份平均 {} KB)，可能已损坏或被新游戏覆盖，仍已备份",
+                    "The save of world {} got much smaller ({} KB, its recent backups {} KB on average), it may be damaged or a new game, it was backed up anyway",
+                    world,
+                    size / 1024,
+                    average / 1024,
+                );
+                (tr!("存档大小异常", "Unusual save size"), body)
+            },
+            _ => (NOTIFICATION_APP_NAME.to_owned(), notification.text.clone()),
+        };
+        show(&summary, &body);
+    }
+}
+
+pub fn restore_latest_good(tw: &Taiwu, world: usize) {
+    match tw.restore_latest_good(world) {
+        Ok(entry) => show(&tr!("恢复完成", "Restored"), &tr!("存档位 {} 已恢复到 {} 的备份", "World {} is restored to the backup {}", world, entry.id)),
+        Err(e) => {
+            error!("[restore_latest_good] error: {:?}", e);
+            show(&tr!("恢复失败", "Restore failed"), &e.to_string());
+        },
+    }
+}
+
+// clicking the action (where the desktop supports actions, elsewhere only shown) calls `on_action`
+pub fn show_with_action(summary: &str, body: &str, action: &str, on_action: impl FnOnce() + Send + 'static) {
+    let mut notification = notify_rust::Notification::new();
+    notification.appname(NOTIFICATION_APP_NAME).summary(summary).body(body);
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        notification.action("default", action).action("open", action);
+        match notification.show() {
+            Ok(handle) => {
+                std::thread::spawn(move || {
+                    handle.wait_for_action(|id| {
+                        if id == "default" || id == "open" {
+                            on_action();
+                        }
+                    })
+                });
+            },
+            Err(e) => warn!("[notification] error: {}", e),
+        }
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    {
+        let _ = (action, on_action);
+        if let Err(e) = notification.show() {
+            warn!("[notification] error: {}", e);
+        }
+    }
+}
+
+pub fn show(summary: &str, body: &str) {
+    let result = notify_rust::Notification::new()
+        .appname(NOTIFICATION_APP_NAME)
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = result {
+        warn!("[notification] error: {}", e);
+    }
+}
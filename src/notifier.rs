@@ -0,0 +1,181 @@
+// Notifications of what happened to the backups, sent to every configured backend
+// (desktop toasts, webhooks, the log) whose `events` include them.
+
+use std::fmt;
+use std::thread::{self, JoinHandle};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{Taiwu, TaiwuEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationKind {
+    BackupComplete,
+    // a store was skipped for lack of free space
+    BackupSkipped,
+    // a backup failed, a save was truncated, or the watcher could not start again
+    Error,
+    // the watcher failed or missed changes and was restarted
+    WatcherRestarted,
+    CloudConflict,
+    // a save much smaller than the recent backups of its world
+    SaveShrank,
+    GameStarted,
+    GameStopped,
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            NotificationKind::BackupComplete => "backup-complete",
+            NotificationKind::BackupSkipped => "backup-skipped",
+            NotificationKind::Error => "error",
+            NotificationKind::WatcherRestarted => "watcher-restarted",
+            NotificationKind::CloudConflict => "cloud-conflict",
+            NotificationKind::SaveShrank => "save-shrank",
+            NotificationKind::GameStarted => "game-started",
+            NotificationKind::GameStopped => "game-stopped",
+        };
+        f.write_str(text)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    // in english, backends showing it to the user can build their own from `event`
+    pub text: String,
+    pub details: Value,
+    pub event: TaiwuEvent,
+}
+
+pub trait Notifier: Send {
+    fn notify(&self, notification: &Notification);
+}
+
+// one backend and the kinds sent to it, all of them if `events` is empty
+pub struct Subscription {
+    pub notifier: Box<dyn Notifier>,
+    pub events: Vec<NotificationKind>,
+}
+
+impl Subscription {
+    pub fn new(notifier: impl Notifier + 'static, events: Vec<NotificationKind>) -> Subscription {
+        Subscription { notifier: Box::new(notifier), events }
+    }
+
+    fn wants(&self, kind: NotificationKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+// only writes them to the log, e.g. for a daemon whose log is collected anyway
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn notify(&self, notification: &Notification) {
+        let kind = notification.kind.to_string();
+        if notification.kind == NotificationKind::Error {
+            warn!(kind = "notification", event = kind; "[Notify] {}", notification.text);
+        } else {
+            info!(kind = "notification", event = kind; "[Notify] {}", notification.text);
+        }
+    }
+}
+
+// one thread sends the notifications of `tw` to all of them, in order, until its events end
+pub fn spawn(tw: &Taiwu, subscriptions: Vec<Subscription>) -> Option<JoinHandle<()>> {
+    if subscriptions.is_empty() {
+        return None;
+    }
+    let events = tw.subscribe();
+    Some(thread::spawn(move || {
+        for event in events {
+            let Some(notification) = Notification::of(event) else { continue };
+            for subscription in subscriptions.iter().filter(|subscription| subscription.wants(notification.kind)) {
+                subscription.notifier.notify(&notification);
+            }
+        }
+    }))
+}
+
+impl Notification {
+    pub fn of(event: TaiwuEvent) -> Option<Notification> {
+        let (kind, text, details) = match &event {
+            TaiwuEvent::BackupDone { src, stores, bytes, duration } => {
+                let failed: Vec<_> = stores.iter()
+                    .filter_map(|store| store.result.as_ref().err().map(|e| json!({ "root": store.root, "error": e })))
+                    .collect();
+                let backups: Vec<_> = stores.iter().filter_map(|store| store.result.as_ref().ok()).collect();
+                let text = if failed.is_empty() {
+                    format!("Backed up `{}`", src.display())
+                } else {
+                    format!("Backed up `{}`, {} of {} stores failed", src.display(), failed.len(), stores.len())
+                };
+                (NotificationKind::BackupComplete, text, json!({
+                    "src": src,
+                    "backups": backups,
+                    "failed": failed,
+                    "bytes": bytes,
+                    "duration_ms": duration.as_millis() as u64,
+                    "bytes_per_sec": crate::metrics::bytes_per_sec(*bytes, *duration),
+                }))
+            },
+            TaiwuEvent::InsufficientSpace { root, available, required } => (
+                NotificationKind::BackupSkipped,
+                format!("Backup to `{}` skipped, only {} MB free", root.display(), available / 1024 / 1024),
+                json!({ "root": root, "available": available, "required": required }),
+            ),
+            TaiwuEvent::BackupFailed { src, error } => (
+                NotificationKind::Error,
+                format!("Backup of `{}` failed: {}", src.display(), error),
+                json!({ "src": src, "error": error }),
+            ),
+            TaiwuEvent::SaveTruncated { world, src, size } => (
+                NotificationKind::Error,
+                format!(
+                    "The save of world {} `{}` is only {} bytes and was not backed up, restore the latest good backup with `taiwu restore {}`",
+                    world, src.display(), size, world,
+                ),
+                json!({ "world": world, "src": src, "size": size }),
+            ),
+            TaiwuEvent::WatcherRestarted { reason, polling, running: false } => (
+                NotificationKind::Error,
+                format!("Watching the saves failed and could not restart: {}", reason),
+                json!({ "reason": reason, "polling": polling }),
+            ),
+            TaiwuEvent::WatcherRestarted { reason, polling, running: true } => (
+                NotificationKind::WatcherRestarted,
+                format!("Watching the saves was restarted: {}", reason),
+                json!({ "reason": reason, "polling": polling }),
+            ),
+            TaiwuEvent::CloudConflict { src, latest_backup, .. } => (
+                NotificationKind::CloudConflict,
+                format!("`{}` is older than its latest backup, it may have been replaced by the cloud", src.display()),
+                json!({ "src": src, "latest_backup": latest_backup }),
+            ),
+            TaiwuEvent::SaveShrank { world, src, size, average } => (
+                NotificationKind::SaveShrank,
+                format!(
+                    "The save of world {} `{}` shrank to {} bytes, its recent backups have {} on average, it may be damaged or a new game",
+                    world, src.display(), size, average,
+                ),
+                json!({ "world": world, "src": src, "size": size, "average": average }),
+            ),
+            TaiwuEvent::GameStarted => (NotificationKind::GameStarted, "Game started".to_owned(), Value::Null),
+            TaiwuEvent::GameStopped => (NotificationKind::GameStopped, "Game stopped, backing up all worlds".to_owned(), Value::Null),
+            TaiwuEvent::VerifyDone { checked, problems } if !problems.is_empty() => (
+                NotificationKind::Error,
+                format!("The integrity scan of {} backups found {} problems: {}", checked, problems.len(), problems.join(", ")),
+                json!({ "checked": checked, "problems": problems }),
+            ),
+            TaiwuEvent::VerifyDone { .. } | TaiwuEvent::Pruned { .. } | TaiwuEvent::RetryDone { .. } | TaiwuEvent::CopyProgress(_) => {
+                return None
+            },
+        };
+        Some(Notification { kind, text, details, event })
+    }
+}
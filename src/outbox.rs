@@ -0,0 +1,147 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+const OUTBOX_FILE_NAME: &str = "outbox.json";
+const OUTBOX_SPOOL_FOLDER_NAME: &str = "spool";
+
+const RETRY_DELAY_MIN_SECS: i64 = 30;
+const RETRY_DELAY_MAX_SECS: i64 = 60 * 60;
+
+// a backup copy that failed and is waiting to be written to `root` again
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxJob {
+    pub src: PathBuf,
+    pub root: PathBuf,
+    pub relative: PathBuf,
+    pub attempts: u32,
+    pub next_try: i64,
+    // `src` is our own copy in the spool folder, remove it once no job needs it
+    pub spooled: bool,
+}
+
+impl OutboxJob {
+    pub fn new(src: PathBuf, root: PathBuf, relative: PathBuf, spooled: bool) -> OutboxJob {
+        let next_try = now() + RETRY_DELAY_MIN_SECS;
+        OutboxJob { src, root, relative, attempts: 0, next_try, spooled }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.next_try <= now()
+    }
+
+    pub fn postpone(&mut self) {
+        self.attempts += 1;
+        let delay = RETRY_DELAY_MIN_SECS.saturating_mul(1 << self.attempts.min(16));
+        self.next_try = now() + delay.min(RETRY_DELAY_MAX_SECS);
+    }
+}
+
+// failed backup jobs persisted to disk, so they survive a restart
+#[derive(Debug)]
+pub struct Outbox {
+    folder: PathBuf,
+    jobs: Mutex<Vec<OutboxJob>>,
+}
+
+impl Outbox {
+    pub fn open(folder: impl AsRef<Path>) -> Outbox {
+        let folder = folder.as_ref().to_owned();
+        let jobs = match fs::read_to_string(folder.join(OUTBOX_FILE_NAME)) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("outbox file is broken, ignore it: {}", e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        debug!("outbox has {} pending job(s)", jobs.len());
+        Outbox { folder, jobs: Mutex::new(jobs) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    // keep a private copy of `src`, the save file will be overwritten by the game before retrying
+    pub fn spool(&self, src: &Path, relative: &Path) -> io::Result<PathBuf> {
+        let dst = self.folder.join(OUTBOX_SPOOL_FOLDER_NAME).join(relative);
+        fs::create_dir_all(dst.parent().unwrap())?;
+        crate::store::clone_or_copy(src, &dst)?;
+        Ok(dst)
+    }
+
+    pub fn push(&self, job: OutboxJob) -> io::Result<()> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.push(job);
+        self.save(&jobs)
+    }
+
+    // the jobs to a store that was moved, how many
+    #[cfg(feature = "config_edit")]
+    pub fn move_root(&self, from: &Path, to: &Path) -> io::Result<usize> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut moved = 0;
+        for job in jobs.iter_mut() {
+            if let Ok(rest) = job.root.strip_prefix(from) {
+                job.root = to.join(rest);
+                moved += 1;
+            }
+        }
+        if moved > 0 {
+            self.save(&jobs)?;
+        }
+        Ok(moved)
+    }
+
+    // run `f` on every due job, a job is done when `f` returns true, or dropped once its `src` is
+    // gone (an older job may copy from a backup of another store, which prune may have removed);
+    // the jobs are not locked while `f` runs, a backup failing meanwhile pushes one of its own
+    pub fn retry(&self, mut f: impl FnMut(&OutboxJob) -> bool) -> io::Result<()> {
+        let due: Vec<OutboxJob> = self.jobs.lock().unwrap().iter().filter(|job| job.is_due()).cloned().collect();
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        let (mut done, mut failed) = (Vec::new(), Vec::new());
+        for job in due {
+            if !job.src.exists() {
+                warn!("`{}` to retry to `{}` is gone, drop the job", job.src.display(), job.root.display());
+                done.push(job);
+                continue;
+            }
+            if f(&job) {
+                done.push(job);
+            } else {
+                failed.push(job);
+            }
+        }
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| !done.contains(job));
+        for job in jobs.iter_mut().filter(|job| failed.contains(job)) {
+            job.postpone();
+        }
+
+        for job in done.iter().filter(|job| job.spooled) {
+            if !jobs.iter().any(|other| other.src == job.src) {
+                let _ = fs::remove_file(&job.src);
+            }
+        }
+
+        self.save(&jobs)
+    }
+
+    fn save(&self, jobs: &[OutboxJob]) -> io::Result<()> {
+        fs::create_dir_all(&self.folder)?;
+        let text = serde_json::to_string_pretty(jobs).map_err(io::Error::from)?;
+        fs::write(self.folder.join(OUTBOX_FILE_NAME), text)
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
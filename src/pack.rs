@@ -0,0 +1,163 @@
+// Old backups of a world bundled into one `packs/<month>.tar.zst` per month, so the store is a
+// few large files instead of thousands of small ones. `packs/<month>.json` beside it is the
+// index, the backups are listed from it without opening the pack, and read from the pack only
+// when restored or compared (`pack` feature).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "pack")]
+use crate::store;
+
+const PACK_FOLDER_NAME: &str = "packs";
+const PACK_EXTENSION: &str = "tar.zst";
+#[cfg(feature = "pack")]
+const ZSTD_LEVEL: i32 = 9;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PackReport {
+    // nothing was packed, `packed` is what would have been
+    pub dry_run: bool,
+    pub packed: usize,
+    // of the backups packed, before compression
+    pub bytes: u64,
+    pub packs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PackIndex {
+    backups: Vec<PackedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackedFile {
+    // `local.sav.<timestamp>`, as it was in the world folder
+    pub name: String,
+    pub size: u64,
+}
+
+// month -> pack file
+pub fn pack_path(folder: &Path, month: &str) -> PathBuf {
+    folder.join(PACK_FOLDER_NAME).join(format!("{}.{}", month, PACK_EXTENSION))
+}
+
+fn index_path(pack: &Path) -> PathBuf {
+    let name = pack.file_name().unwrap().to_string_lossy();
+    pack.with_file_name(format!("{}.json", name.trim_end_matches(PACK_EXTENSION).trim_end_matches('.')))
+}
+
+// every packed backup of a world folder with its pack
+pub fn list(folder: &Path) -> io::Result<Vec<(PathBuf, PackedFile)>> {
+    let packs = folder.join(PACK_FOLDER_NAME);
+    if !packs.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut packed = Vec::new();
+    for entry in fs::read_dir(packs)? {
+        let pack = entry?.path();
+        if !pack.to_string_lossy().ends_with(PACK_EXTENSION) {
+            continue;
+        }
+        for file in read_index(&pack)?.backups {
+            packed.push((pack.clone(), file));
+        }
+    }
+    Ok(packed)
+}
+
+fn read_index(pack: &Path) -> io::Result<PackIndex> {
+    let path = index_path(pack);
+    if !path.is_file() {
+        return Ok(PackIndex::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+#[cfg(feature = "pack")]
+fn write_index(pack: &Path, index: &PackIndex) -> io::Result<()> {
+    let path = index_path(pack);
+    let tmp = store::temp_path(&path);
+    fs::write(&tmp, serde_json::to_string_pretty(index)?)?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(feature = "pack")]
+pub fn read(pack: &Path, name: &str) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut archive = tar::Archive::new(zstd::Decoder::new(fs::File::open(pack)?)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.to_string_lossy() == name {
+            let mut bytes = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut bytes)?;
+            return Ok(bytes);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("`{}` is not in `{}`", name, pack.display())))
+}
+
+#[cfg(not(feature = "pack"))]
+pub fn read(pack: &Path, _name: &str) -> io::Result<Vec<u8>> {
+    Err(unsupported(pack))
+}
+
+// the pack is written again with `add` (files of the world folder) and without `remove` (names),
+// it is only replaced once complete; the added files are left to the caller to remove
+#[cfg(feature = "pack")]
+pub fn rewrite(pack: &Path, add: &[PathBuf], remove: &[String]) -> io::Result<()> {
+    let mut index = PackIndex::default();
+    let tmp = store::temp_path(pack);
+    fs::create_dir_all(pack.parent().unwrap())?;
+    let result = (|| {
+        let encoder = zstd::Encoder::new(fs::File::create(&tmp)?, ZSTD_LEVEL)?;
+        let mut builder = tar::Builder::new(encoder);
+        if pack.is_file() {
+            let mut archive = tar::Archive::new(zstd::Decoder::new(fs::File::open(pack)?)?);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+                if remove.contains(&name) || add.iter().any(|path| path.file_name() == Some(name.as_ref())) {
+                    continue;
+                }
+                let header = entry.header().clone();
+                builder.append(&header, &mut entry)?;
+                index.backups.push(PackedFile { name, size: header.size()? });
+            }
+        }
+        for path in add {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            builder.append_path_with_name(path, &name)?;
+            index.backups.push(PackedFile { name, size: path.metadata()?.len() });
+        }
+        builder.into_inner()?.finish()?.sync_all()
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    if index.backups.is_empty() {
+        fs::remove_file(&tmp)?;
+        let _ = fs::remove_file(pack);
+        return match fs::remove_file(index_path(pack)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    fs::rename(&tmp, pack)?;
+    write_index(pack, &index)
+}
+
+#[cfg(not(feature = "pack"))]
+pub fn rewrite(pack: &Path, _add: &[PathBuf], _remove: &[String]) -> io::Result<()> {
+    Err(unsupported(pack))
+}
+
+#[cfg(not(feature = "pack"))]
+fn unsupported(pack: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("`{}` can only be read with the `pack` feature", pack.display()))
+}
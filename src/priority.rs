@@ -0,0 +1,86 @@
+// The copies of the backups (and the compression of packs) at the lowest CPU and disk priority,
+// so a large one does not make the game stutter: the background mode of the thread on windows,
+// nice and the lowest best-effort I/O priority on linux, the background band on macos. Only the
+// thread doing it is lowered, and only until the guard is dropped.
+
+use log::debug;
+
+#[cfg(target_os = "linux")]
+const NICE: libc::c_int = 10;
+// of the calling thread
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+#[cfg(target_os = "linux")]
+const IOPRIO_LOWEST_BEST_EFFORT: libc::c_long = 2 << 13 | 7;
+
+pub struct Background {
+    #[cfg(target_os = "linux")]
+    ioprio: libc::c_long,
+}
+
+// none if it is not `enabled` or could not be set (e.g. the thread is in the background already)
+pub fn background(enabled: bool) -> Option<Background> {
+    if !enabled {
+        return None;
+    }
+    let background = begin();
+    if background.is_none() {
+        debug!("could not lower the priority of the thread: {}", std::io::Error::last_os_error());
+    }
+    background
+}
+
+#[cfg(windows)]
+fn begin() -> Option<Background> {
+    use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN};
+
+    (unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) } != 0).then_some(Background {})
+}
+
+#[cfg(windows)]
+impl Drop for Background {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_END};
+
+        unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_END) };
+    }
+}
+
+// raising the nice value again needs privileges, it stays on the thread (which only backs up
+// anyway); the I/O priority is put back
+#[cfg(target_os = "linux")]
+fn begin() -> Option<Background> {
+    let tid = unsafe { libc::gettid() } as libc::id_t;
+    unsafe {
+        if libc::getpriority(libc::PRIO_PROCESS, tid) < NICE {
+            libc::setpriority(libc::PRIO_PROCESS, tid, NICE);
+        }
+    }
+    let ioprio = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+    let set = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_LOWEST_BEST_EFFORT) };
+    (ioprio >= 0 && set == 0).then_some(Background { ioprio })
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Background {
+    fn drop(&mut self) {
+        unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, self.ioprio) };
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn begin() -> Option<Background> {
+    (unsafe { libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG) } == 0).then_some(Background {})
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for Background {
+    fn drop(&mut self) {
+        unsafe { libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, 0) };
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn begin() -> Option<Background> {
+    None
+}
@@ -0,0 +1,86 @@
+// Whether the game is running, found by the name of its process. Without the `process` feature
+// it is never seen running.
+
+#[cfg(feature = "process")]
+use sysinfo::{Pid, Process, ProcessRefreshKind, RefreshKind, System, UpdateKind};
+
+#[cfg(feature = "process")]
+use crate::game::game;
+
+#[cfg(feature = "process")]
+pub struct GameMonitor {
+    system: System,
+    running: bool,
+}
+
+#[cfg(not(feature = "process"))]
+pub struct GameMonitor;
+
+#[cfg(feature = "process")]
+impl GameMonitor {
+    pub fn new() -> GameMonitor {
+        let system = System::new_with_specifics(RefreshKind::new().with_processes(refresh_kind()));
+        let running = system.processes().values().any(is_game_process);
+        GameMonitor { system, running }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    // Some(running) if the game started or stopped since the last poll
+    pub fn poll(&mut self) -> Option<bool> {
+        self.system.refresh_processes_specifics(refresh_kind());
+        let running = self.system.processes().values().any(is_game_process);
+        if running == self.running {
+            return None;
+        }
+        self.running = running;
+        Some(running)
+    }
+}
+
+#[cfg(not(feature = "process"))]
+impl GameMonitor {
+    pub fn new() -> GameMonitor {
+        GameMonitor
+    }
+
+    pub fn is_running(&self) -> bool {
+        false
+    }
+
+    pub fn poll(&mut self) -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(feature = "process")]
+fn refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::new().with_exe(UpdateKind::OnlyIfNotSet).with_cmd(UpdateKind::OnlyIfNotSet)
+}
+
+// under proton the process is wine, which keeps the windows path of the exe as the first argument
+#[cfg(feature = "process")]
+fn is_game_process(process: &Process) -> bool {
+    let is_exe = |name: &str| {
+        let name = name.rsplit(['\\', '/']).next().unwrap_or(name);
+        name.eq_ignore_ascii_case(game().exe_name())
+    };
+    is_exe(process.name())
+        || process.exe().and_then(|exe| exe.to_str()).is_some_and(is_exe)
+        || process.cmd().first().is_some_and(|arg| is_exe(arg))
+}
+
+// whether a process with this id still runs on this machine
+#[cfg(feature = "process")]
+pub fn is_process_running(pid: u32) -> bool {
+    let mut system = System::new();
+    system.refresh_process_specifics(Pid::from_u32(pid), ProcessRefreshKind::new())
+}
+
+// it can not be told, so as if it did
+#[cfg(not(feature = "process"))]
+pub fn is_process_running(_pid: u32) -> bool {
+    true
+}
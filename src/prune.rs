@@ -0,0 +1,112 @@
+// Which backups `Taiwu::prune` removes. The latest backup of a world and labeled backups
+// are always kept, like with the store quota.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::store::BackupEntry;
+
+#[derive(Debug, Clone, Default)]
+pub struct PrunePolicy {
+    // per world, the newest backups kept
+    pub keep_last: Option<usize>,
+    // only backups older than this are removed
+    pub older_than: Option<Duration>,
+    // then the oldest left are removed until all worlds take no more than this many bytes
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    // nothing was removed, `deleted` is what would have been
+    pub dry_run: bool,
+    pub deleted: Vec<BackupEntry>,
+    pub retained: Vec<BackupEntry>,
+    pub freed: u64,
+}
+
+impl PrunePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.keep_last.is_none() && self.older_than.is_none() && self.max_size.is_none()
+    }
+
+    // with what it does not set taken from `base`
+    pub fn or(&self, base: &PrunePolicy) -> PrunePolicy {
+        PrunePolicy {
+            keep_last: self.keep_last.or(base.keep_last),
+            older_than: self.older_than.or(base.older_than),
+            max_size: self.max_size.or(base.max_size),
+        }
+    }
+
+    // split the backups of every world (oldest first) into the removed and the kept ones
+    pub fn plan(&self, worlds: Vec<Vec<BackupEntry>>, now: chrono::DateTime<chrono::Utc>) -> (Vec<BackupEntry>, Vec<BackupEntry>) {
+        let by_age = self.keep_last.is_some() || self.older_than.is_some();
+        let mut deleted = Vec::new();
+        // (protected, entry), protected ones are never removed for the size limit
+        let mut kept = Vec::new();
+        for backups in worlds {
+            let count = backups.len();
+            for (i, entry) in backups.into_iter().enumerate() {
+                let protected = i + 1 == count || entry.label.is_some();
+                let beyond_last = self.keep_last.is_none_or(|keep| i + keep < count);
+                let old = self.older_than.is_none_or(|age| {
+                    chrono::Duration::from_std(age).is_ok_and(|age| entry.utc < now - age)
+                });
+                if by_age && !protected && beyond_last && old {
+                    deleted.push(entry);
+                } else {
+                    kept.push((protected, entry));
+                }
+            }
+        }
+
+        if let Some(max_size) = self.max_size {
+            kept.sort_by_key(|(_, entry)| (entry.utc, entry.timestamp));
+            let mut size: u64 = kept.iter().map(|(_, entry)| entry.size).sum();
+            let mut left = Vec::new();
+            for (protected, entry) in kept {
+                if size > max_size && !protected {
+                    size -= entry.size;
+                    deleted.push(entry);
+                } else {
+                    left.push((protected, entry));
+                }
+            }
+            kept = left;
+        }
+
+        (deleted, kept.into_iter().map(|(_, entry)| entry).collect())
+    }
+}
+
+// `30d`, `12h`, `2w` or `45m`
+pub fn parse_age(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = text.split_at(split);
+    let seconds = match unit.trim() {
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 24 * 3600,
+        "w" => 7 * 24 * 3600,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.parse::<u64>().ok()? * seconds))
+}
+
+// `2GB`, `500MB`, `800KB` or bytes, powers of 1024
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_ascii_uppercase();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let factor = match unit.trim().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    Some(number.parse::<u64>().ok()? * factor)
+}
@@ -0,0 +1,183 @@
+// Backups found by the watcher are copied on a worker thread, so a slow disk does not hold up
+// the events. They are done in the order the files changed; a file changed again while its job
+// waits gets its newer job at the end of the queue instead, so it is not copied twice. A manual
+// backup does not wait in the queue, it only waits for the backup of the same world in progress
+// (see `FolderLocks`) and drops the jobs it made unnecessary.
+
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use log::{trace, warn};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Job {
+    Backup(PathBuf),
+    BackupExtra(PathBuf),
+    // the save of a new world folder, unless it was backed up already
+    BackupIfNew(PathBuf),
+}
+
+impl Job {
+    // a rename or create may be reported more than once for the same save
+    pub fn if_new(self) -> Job {
+        match self {
+            Job::Backup(save) => Job::BackupIfNew(save),
+            job => job,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Job::Backup(path) | Job::BackupExtra(path) | Job::BackupIfNew(path) => path,
+        }
+    }
+
+    // of the same file, a change that must be backed up stays one
+    fn supersede(self, older: &Job) -> Job {
+        match (self, older) {
+            (Job::BackupIfNew(save), Job::Backup(_)) => Job::Backup(save),
+            (job, _) => job,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    jobs: VecDeque<Job>,
+    capacity: usize,
+    // `JobQueue`s not dropped yet, the worker stops once there are none and it took every job
+    senders: usize,
+    // the `Jobs` was not dropped
+    receiving: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct JobQueue {
+    shared: Arc<Shared>,
+}
+
+pub(crate) struct Jobs {
+    shared: Arc<Shared>,
+}
+
+pub(crate) fn bounded(capacity: usize) -> (JobQueue, Jobs) {
+    let state = State { jobs: VecDeque::new(), capacity, senders: 1, receiving: true };
+    let shared = Arc::new(Shared { state: Mutex::new(state), changed: Condvar::new() });
+    (JobQueue { shared: Arc::clone(&shared) }, Jobs { shared })
+}
+
+impl JobQueue {
+    // waits for the worker only if the queue is full
+    pub fn push(&self, job: Job) {
+        let mut state = self.shared.lock();
+        let job = match state.jobs.iter().position(|queued| queued.path() == job.path()) {
+            Some(i) => {
+                let older = state.jobs.remove(i).unwrap();
+                trace!("{:?} supersedes the queued {:?}", job, older);
+                job.supersede(&older)
+            },
+            None => job,
+        };
+        if state.jobs.len() >= state.capacity && state.receiving {
+            warn!("the backup queue is full, wait for the worker");
+            state = self.shared.changed.wait_while(state, |state| state.jobs.len() >= state.capacity && state.receiving).unwrap();
+        }
+        if !state.receiving {
+            return;
+        }
+        state.jobs.push_back(job);
+        self.shared.changed.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.lock().jobs.len()
+    }
+
+    // the queued job of `path`, e.g. backed up by hand meanwhile
+    pub fn discard(&self, path: &Path) {
+        let mut state = self.shared.lock();
+        if let Some(i) = state.jobs.iter().position(|queued| queued.path() == path) {
+            let job = state.jobs.remove(i).unwrap();
+            trace!("{:?} is not needed anymore", job);
+            self.shared.changed.notify_all();
+        }
+    }
+}
+
+impl Clone for JobQueue {
+    fn clone(&self) -> JobQueue {
+        self.shared.lock().senders += 1;
+        JobQueue { shared: Arc::clone(&self.shared) }
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        self.shared.lock().senders -= 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+// ends once every `JobQueue` is dropped and every job was taken
+impl Iterator for Jobs {
+    type Item = Job;
+
+    fn next(&mut self) -> Option<Job> {
+        let state = self.shared.lock();
+        let mut state = self.shared.changed.wait_while(state, |state| state.jobs.is_empty() && state.senders > 0).unwrap();
+        let job = state.jobs.pop_front()?;
+        self.shared.changed.notify_all();
+        Some(job)
+    }
+}
+
+impl Drop for Jobs {
+    fn drop(&mut self) {
+        self.shared.lock().receiving = false;
+        self.shared.changed.notify_all();
+    }
+}
+
+// one backup at a time of the files in a folder (the save of a world), so the worker and a manual
+// backup do not copy the same save at once and the backups of a world are in the order started
+#[derive(Debug, Default)]
+pub(crate) struct FolderLocks {
+    busy: Mutex<HashSet<PathBuf>>,
+    freed: Condvar,
+}
+
+pub(crate) struct FolderLock<'a> {
+    locks: &'a FolderLocks,
+    folder: PathBuf,
+}
+
+impl FolderLocks {
+    // waits for the backup of the folder of `file` in progress
+    pub fn lock(&self, file: &Path) -> FolderLock<'_> {
+        let folder = file.parent().unwrap_or(file).to_owned();
+        let busy = self.busy.lock().unwrap();
+        let mut busy = self.freed.wait_while(busy, |busy| busy.contains(&folder)).unwrap();
+        busy.insert(folder.clone());
+        FolderLock { locks: self, folder }
+    }
+}
+
+impl Drop for FolderLock<'_> {
+    fn drop(&mut self) {
+        self.locks.busy.lock().unwrap().remove(&self.folder);
+        self.locks.freed.notify_all();
+    }
+}
@@ -0,0 +1,193 @@
+// Run the watcher as a windows service, independent of user login.
+//
+// The service manager starts the exe with `--service`, see `install`.
+
+use std::ffi::{OsStr, OsString};
+use std::sync::mpsc;
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::{error, info};
+use windows_service::service::{
+    PowerEventParam, ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl,
+    ServiceExitCode, ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::service_dispatcher;
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::define_windows_service;
+
+use taiwu::{Config, Taiwu};
+
+use crate::session;
+
+const SERVICE_NAME: &str = "TaiwuBackup";
+const SERVICE_DISPLAY_NAME: &str = "Taiwu Backup";
+const SERVICE_DESCRIPTION: &str = "太吾绘卷存档自动备份 (The Scroll Of Taiwu save backup)";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+define_windows_service!(ffi_service_main, service_main);
+
+// blocks until the service is stopped, only works when started by the service manager
+pub fn run() -> windows_service::Result<()> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        error!("[service] error: {:?}", e);
+    }
+}
+
+fn run_service() -> windows_service::Result<()> {
+    let (control_tx, control_rx) = mpsc::channel();
+    // set once loaded, the power events are handled right in the handler so the system waits for them
+    let loaded: Arc<OnceLock<Arc<Taiwu>>> = Arc::default();
+
+    let loaded1 = Arc::clone(&loaded);
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown | ServiceControl::Pause | ServiceControl::Continue => {
+            let _ = control_tx.send(control);
+            ServiceControlHandlerResult::NoError
+        },
+        ServiceControl::PowerEvent(event) => {
+            match (event, loaded1.get()) {
+                (PowerEventParam::Suspend, Some(tw)) => session::before_sleep(tw),
+                (PowerEventParam::ResumeAutomatic | PowerEventParam::ResumeSuspend, Some(tw)) => session::after_resume(tw),
+                _ => {},
+            }
+            ServiceControlHandlerResult::NoError
+        },
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    let (config, tw) = match Config::load().and_then(|config| Taiwu::with_config(&config).map(|tw| (config, tw))) {
+        Ok((config, tw)) => (config, Arc::new(tw)),
+        Err(e) => {
+            error!("[service] error: {:?}", e);
+            set_status(&status_handle, ServiceState::Stopped, ServiceExitCode::ServiceSpecific(1))?;
+            return Ok(());
+        }
+    };
+
+    let _ = loaded.set(Arc::clone(&tw));
+    crate::start_notifiers(&tw, &config, false);
+    let heartbeat = crate::start_heartbeat(&tw, &config);
+
+    if let Err(e) = tw.backup_once_for_new_save().into_result() {
+        error!("[backup_once] error: {:?}", e);
+    }
+
+    let mut worker = Some(spawn_watch(&tw));
+    set_status(&status_handle, ServiceState::Running, ServiceExitCode::Win32(0))?;
+    info!("[service] running");
+
+    for control in control_rx {
+        match control {
+            ServiceControl::Pause => {
+                if let Some(worker) = worker.take() {
+                    tw.stop();
+                    let _ = worker.join();
+                }
+                set_status(&status_handle, ServiceState::Paused, ServiceExitCode::Win32(0))?;
+                info!("[service] paused");
+            },
+            ServiceControl::Continue => {
+                if worker.is_none() {
+                    worker = Some(spawn_watch(&tw));
+                }
+                set_status(&status_handle, ServiceState::Running, ServiceExitCode::Win32(0))?;
+                info!("[service] continued");
+            },
+            _ => break,
+        }
+    }
+
+    // a paused service does not back up anymore
+    if worker.is_some() && !tw.flush(session::SESSION_FLUSH_TIMEOUT) {
+        error!("[service] stopping with backups unfinished");
+    }
+    tw.shutdown();
+    if let Some(worker) = worker.take() {
+        let _ = worker.join();
+    } else {
+        tw.wait_idle(taiwu::SHUTDOWN_TIMEOUT);
+    }
+    if let Some(heartbeat) = heartbeat {
+        let _ = heartbeat.join();
+    }
+    set_status(&status_handle, ServiceState::Stopped, ServiceExitCode::Win32(0))?;
+    info!("[service] stopped");
+
+    Ok(())
+}
+
+fn spawn_watch(tw: &Arc<Taiwu>) -> JoinHandle<()> {
+    let tw = Arc::clone(tw);
+    thread::spawn(move || {
+        if let Err(e) = tw.watch() {
+            error!("[watch] error: {:?}", e);
+        }
+    })
+}
+
+fn set_status(handle: &ServiceStatusHandle, state: ServiceState, exit_code: ServiceExitCode) -> windows_service::Result<()> {
+    let controls_accepted = match state {
+        ServiceState::Stopped => ServiceControlAccept::empty(),
+        _ => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN | ServiceControlAccept::PAUSE_CONTINUE | ServiceControlAccept::POWER_EVENT,
+    };
+    handle.set_service_status(ServiceStatus {
+        service_type: SERVICE_TYPE,
+        current_state: state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })
+}
+
+pub fn install() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)?;
+    let executable_path = std::env::current_exe().map_err(windows_service::Error::Winapi)?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(SERVICE_DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path,
+        launch_arguments: vec![OsString::from("--service")],
+        dependencies: vec![],
+        account_name: None, // LocalSystem
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description(SERVICE_DESCRIPTION)?;
+    Ok(())
+}
+
+pub fn uninstall() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let access = ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE;
+    let service = manager.open_service(SERVICE_NAME, access)?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    service.delete()
+}
+
+pub fn start() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::START)?;
+    service.start::<&OsStr>(&[])
+}
+
+pub fn stop() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::STOP)?;
+    service.stop()?;
+    Ok(())
+}
@@ -0,0 +1,141 @@
+// Windows going to sleep, shutting down or logging off: the saves changed since their last backup
+// are backed up and the queued jobs done first, so the last minutes of play are not lost. After
+// waking up the watcher is restarted, the native one sometimes stops reporting changes.
+//
+// The broadcasts only reach top-level windows, so there is a hidden one on a thread of its own.
+// The service gets the power events from the service manager instead, see `service`.
+
+use std::iter::once;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use log::{error, info, warn};
+use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::System::Console::{
+    SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT, CTRL_C_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::System::Shutdown::{ShutdownBlockReasonCreate, ShutdownBlockReasonDestroy};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, TranslateMessage, MSG,
+    PBT_APMRESUMEAUTOMATIC, PBT_APMRESUMESUSPEND, PBT_APMSUSPEND, WM_ENDSESSION, WM_POWERBROADCAST,
+    WM_QUERYENDSESSION, WNDCLASSW, WS_OVERLAPPED,
+};
+
+use taiwu::Taiwu;
+
+use crate::i18n::tr;
+
+// windows gives a sleeping system about two seconds, a shutdown about five before it asks the user
+pub const SESSION_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+const WINDOW_CLASS: &str = "TaiwuBackupSession";
+
+// for the window procedure and the console handler, which get no data of their own
+static TAIWU: OnceLock<Arc<Taiwu>> = OnceLock::new();
+
+pub fn before_sleep(tw: &Taiwu) {
+    info!("[session] the system goes to sleep, back up first");
+    if !tw.flush(SESSION_FLUSH_TIMEOUT) {
+        warn!("[session] going to sleep with backups unfinished");
+    }
+}
+
+pub fn after_resume(tw: &Taiwu) {
+    info!("[session] the system woke up, restart watching");
+    tw.resumed();
+}
+
+// the process is killed soon afterwards
+fn before_end(tw: &Taiwu) {
+    info!("[session] the session ends, back up first");
+    if !tw.flush(SESSION_FLUSH_TIMEOUT) {
+        warn!("[session] the session ends with backups unfinished");
+    }
+    tw.shutdown();
+    tw.wait_idle(SESSION_FLUSH_TIMEOUT);
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(once(0)).collect()
+}
+
+// for the tray and the daemon, once
+pub fn spawn(tw: &Arc<Taiwu>) {
+    if TAIWU.set(Arc::clone(tw)).is_err() {
+        return;
+    }
+    std::thread::spawn(|| {
+        let class = wide(WINDOW_CLASS);
+        let hwnd = unsafe {
+            let instance = GetModuleHandleW(std::ptr::null());
+            let mut wndclass: WNDCLASSW = std::mem::zeroed();
+            wndclass.lpfnWndProc = Some(window_proc);
+            wndclass.hInstance = instance;
+            wndclass.lpszClassName = class.as_ptr();
+            RegisterClassW(&wndclass);
+            // never shown
+            CreateWindowExW(0, class.as_ptr(), class.as_ptr(), WS_OVERLAPPED, 0, 0, 0, 0, 0, 0, instance, std::ptr::null())
+        };
+        if hwnd == 0 {
+            error!("[session] could not create the window: {}", std::io::Error::last_os_error());
+            return;
+        }
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while GetMessageW(&mut msg, 0, 0, 0) > 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    });
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let Some(tw) = TAIWU.get() else {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    };
+    match msg {
+        WM_POWERBROADCAST => {
+            match wparam as u32 {
+                PBT_APMSUSPEND => before_sleep(tw),
+                PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND => after_resume(tw),
+                _ => {},
+            }
+            1
+        },
+        WM_QUERYENDSESSION => 1,
+        // not 0 if the session really ends, windows waits while the reason is shown
+        WM_ENDSESSION if wparam != 0 => {
+            let reason = wide(&tr!("正在备份太吾绘卷存档", "Backing up the Taiwu saves"));
+            ShutdownBlockReasonCreate(hwnd, reason.as_ptr());
+            before_end(tw);
+            ShutdownBlockReasonDestroy(hwnd);
+            0
+        },
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+// the console the daemon was started from is closed, or the session of a console only process
+// ends; ctrl-c quits it like `taiwu ctl quit`
+pub fn handle_console_events() {
+    unsafe {
+        SetConsoleCtrlHandler(Some(console_handler), 1);
+    }
+}
+
+unsafe extern "system" fn console_handler(ctrl_type: u32) -> BOOL {
+    match (ctrl_type, TAIWU.get()) {
+        (CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT, Some(tw)) => {
+            before_end(tw);
+            1
+        },
+        // the process goes on, until `watch` returns
+        (CTRL_C_EVENT | CTRL_BREAK_EVENT, Some(tw)) => {
+            info!("[session] ctrl-c, quit");
+            tw.shutdown();
+            1
+        },
+        _ => 0,
+    }
+}
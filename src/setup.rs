@@ -0,0 +1,103 @@
+// When the game is not found on start (or the configured folder is not it), the tray asks for the
+// game folder and the backup folder with folder pickers instead of quitting without a word, the
+// choices are saved to the config file.
+
+use std::path::PathBuf;
+
+use log::{error, info};
+use rfd::{FileDialog, MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+
+use taiwu::{GameRootDiagnosis, TaiwuError};
+
+use crate::i18n::tr;
+
+// true if the config file was changed for `error`, so loading is worth another try
+pub fn run(error: &TaiwuError) -> bool {
+    let question = match error {
+        TaiwuError::GameRootNotFound => Some(tr!(
+            "没有找到太吾绘卷的游戏目录，是否手动选择游戏所在的文件夹?",
+            "The Scroll of Taiwu was not found, choose the folder of the game?",
+        )),
+        TaiwuError::GameRootInvalid(diagnosis) => Some(tr!(
+            "{}\n是否重新选择游戏所在的文件夹?",
+            "{}\nChoose the folder of the game again?",
+            diagnosis,
+        )),
+        TaiwuError::BackupRootDefaultNotAvailable => None,
+        _ => return false,
+    };
+
+    let game_root = match question {
+        Some(question) => match confirm(&question).then(pick_game_root).flatten() {
+            Some(path) => Some(path),
+            None => return false,
+        },
+        None => None,
+    };
+    // the default one can not be used then
+    let must_pick = matches!(error, TaiwuError::BackupRootDefaultNotAvailable);
+    let backup_root = if must_pick || confirm(&tr!(
+        "是否选择备份存放的文件夹? 选择\"否\"则使用默认位置",
+        "Choose the folder the backups go to? With \"No\" the default one is used",
+    )) {
+        match pick_folder(&tr!("选择备份目录", "Choose the backup folder")) {
+            Some(path) => Some(path),
+            None if must_pick => return false,
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    for (key, path) in [("game_root", game_root), ("backup_root", backup_root)] {
+        let Some(path) = path else { continue };
+        if let Err(e) = taiwu::set_config_value(key, &path.to_string_lossy()) {
+            error!("[setup] error: {:?}", e);
+            show_error(&tr!("无法保存配置: {}", "Could not save the config: {}", e));
+            return false;
+        }
+    }
+    info!("[setup] done");
+    true
+}
+
+// again until it is the game folder, or there is one in it or above it to take instead
+fn pick_game_root() -> Option<PathBuf> {
+    loop {
+        let path = pick_folder(&tr!("选择游戏目录", "Choose the game folder"))?;
+        let diagnosis = GameRootDiagnosis::of(&path);
+        if diagnosis.is_valid() {
+            return Some(path);
+        }
+        if let Some(suggestion) = &diagnosis.suggestion {
+            if confirm(&tr!("{}\n是否使用 `{}`?", "{}\nUse `{}`?", diagnosis, suggestion.display())) {
+                return Some(suggestion.clone());
+            }
+        } else if !confirm(&tr!("{}\n是否重新选择?", "{}\nChoose again?", diagnosis)) {
+            return None;
+        }
+    }
+}
+
+fn pick_folder(title: &str) -> Option<PathBuf> {
+    FileDialog::new().set_title(title).pick_folder()
+}
+
+fn confirm(msg: &str) -> bool {
+    let result = MessageDialog::new()
+        .set_title("Taiwu Backup")
+        .set_description(msg)
+        .set_level(MessageLevel::Info)
+        .set_buttons(MessageButtons::YesNo)
+        .show();
+    result == MessageDialogResult::Yes
+}
+
+fn show_error(msg: &str) {
+    MessageDialog::new()
+        .set_title("Taiwu Backup")
+        .set_description(msg)
+        .set_level(MessageLevel::Error)
+        .set_buttons(MessageButtons::Ok)
+        .show();
+}
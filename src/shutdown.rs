@@ -0,0 +1,67 @@
+// Quitting waits for the backups and restores still copying, and no new ones start after.
+
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+pub(crate) struct Shutdown {
+    state: Mutex<State>,
+    idle: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    cancelled: bool,
+    in_flight: usize,
+}
+
+// held while an operation runs, see `Shutdown::begin`
+pub(crate) struct InFlight<'a>(&'a Shutdown);
+
+impl Shutdown {
+    // `None` once cancelled
+    pub fn begin(&self) -> Option<InFlight<'_>> {
+        let mut state = self.state.lock().unwrap();
+        if state.cancelled {
+            return None;
+        }
+        state.in_flight += 1;
+        Some(InFlight(self))
+    }
+
+    pub fn cancel(&self) {
+        self.state.lock().unwrap().cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.lock().unwrap().cancelled
+    }
+
+    // false if some are still running after `timeout`
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        while state.in_flight > 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            state = self.idle.wait_timeout(state, left).unwrap().0;
+        }
+        true
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// SIGTERM (systemd stopping the daemon) and SIGINT (ctrl-c) quit the daemon like `taiwu ctl quit`:
+// the running backups and restores are finished first, a second signal quits at once.
+//
+// They are blocked before any other thread is started, so every thread keeps them blocked, and
+// taken by a thread of their own with `sigwait` instead of a handler interrupting a copy.
+
+use std::sync::Arc;
+use std::{mem, ptr, thread};
+
+use log::{error, info, warn};
+
+use taiwu::Taiwu;
+
+// before any other thread is started
+pub fn block() {
+    let set = quit_signals();
+    unsafe {
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut());
+    }
+}
+
+pub fn spawn(tw: &Arc<Taiwu>) {
+    let tw = Arc::clone(tw);
+    thread::spawn(move || {
+        let set = quit_signals();
+        loop {
+            let mut signal = 0;
+            let code = unsafe { libc::sigwait(&set, &mut signal) };
+            if code != 0 {
+                error!("[signal] could not wait for signals: {}", std::io::Error::from_raw_os_error(code));
+                return;
+            }
+            let name = if signal == libc::SIGINT { "SIGINT" } else { "SIGTERM" };
+            if tw.is_shutting_down() {
+                warn!("[signal] {} again, quit without waiting", name);
+                std::process::exit(1);
+            }
+            info!("[signal] {}, quit", name);
+            tw.shutdown();
+        }
+    });
+}
+
+fn quit_signals() -> libc::sigset_t {
+    unsafe {
+        let mut set = mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        set
+    }
+}
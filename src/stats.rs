@@ -0,0 +1,102 @@
+// How many backups there are and how much space they take.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::store::{self, BackupEntry};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stats {
+    pub worlds: Vec<WorldStats>,
+    pub count: usize,
+    pub size: u64,
+    // bytes of backups identical to an older one, saved if the store deduplicated them
+    pub duplicate_size: u64,
+    // none if no backup has its copy time recorded yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_times: Option<CopyTimes>,
+}
+
+// how long copying the backups into the primary store took, percentiles of those it was recorded for
+#[derive(Debug, Clone, Serialize)]
+pub struct CopyTimes {
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    // bytes per second, the typical one and the one the slowest tenth get at most
+    pub p50_bytes_per_sec: u64,
+    pub p10_bytes_per_sec: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorldStats {
+    pub world: usize,
+    pub count: usize,
+    pub size: u64,
+    pub oldest: Option<chrono::DateTime<chrono::Utc>>,
+    pub newest: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// `copies` are the size and the milliseconds of the copy of each backup it is known for
+pub fn collect(worlds: Vec<(usize, Vec<BackupEntry>)>, copies: Vec<(u64, u64)>) -> io::Result<Stats> {
+    let mut stats = Stats::default();
+    let mut by_size: HashMap<u64, Vec<&BackupEntry>> = HashMap::new();
+
+    for (world, backups) in &worlds {
+        let size = backups.iter().map(|entry| entry.size).sum();
+        stats.worlds.push(WorldStats {
+            world: *world,
+            count: backups.len(),
+            size,
+            oldest: backups.iter().map(|entry| entry.utc).min(),
+            newest: backups.iter().map(|entry| entry.utc).max(),
+        });
+        stats.count += backups.len();
+        stats.size += size;
+        for entry in backups {
+            by_size.entry(entry.size).or_default().push(entry);
+        }
+    }
+
+    // only files of the same size can be the same, so most are never read
+    for entries in by_size.values().filter(|entries| entries.len() > 1) {
+        let mut seen = HashSet::new();
+        for entry in entries {
+            if !seen.insert(store::sha256_bytes(&store::read_backup(entry)?)) {
+                stats.duplicate_size += entry.size;
+            }
+        }
+    }
+
+    stats.copy_times = copy_times(&copies);
+    Ok(stats)
+}
+
+fn copy_times(copies: &[(u64, u64)]) -> Option<CopyTimes> {
+    let mut durations: Vec<u64> = copies.iter().map(|(_, ms)| *ms).collect();
+    let mut throughputs: Vec<u64> = copies.iter()
+        .map(|(size, ms)| crate::metrics::bytes_per_sec(*size, Duration::from_millis(*ms)))
+        .collect();
+    durations.sort_unstable();
+    throughputs.sort_unstable();
+    Some(CopyTimes {
+        count: copies.len(),
+        p50_ms: percentile(&durations, 50)?,
+        p90_ms: percentile(&durations, 90)?,
+        p99_ms: percentile(&durations, 99)?,
+        max_ms: *durations.last()?,
+        p50_bytes_per_sec: percentile(&throughputs, 50)?,
+        p10_bytes_per_sec: percentile(&throughputs, 10)?,
+    })
+}
+
+// nearest rank of the sorted `values`
+fn percentile(values: &[u64], percent: usize) -> Option<u64> {
+    let rank = (values.len() * percent).div_ceil(100).max(1);
+    values.get(rank - 1).copied()
+}
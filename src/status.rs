@@ -0,0 +1,48 @@
+// What a `Taiwu` is doing right now, see `Taiwu::status`: shown in the tray tooltip and by
+// `taiwu ctl status`, and written to the heartbeat file.
+
+use serde::{Deserialize, Serialize};
+
+use crate::CopyProgress;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaiwuStatus {
+    pub watching: bool,
+    // polling instead of the native watcher
+    #[serde(default)]
+    pub polling: bool,
+    pub paused: bool,
+    #[serde(default)]
+    pub game_running: bool,
+    // unix seconds of the last change the watcher saw
+    #[serde(default)]
+    pub last_event: Option<i64>,
+    // of each world with backups
+    #[serde(default)]
+    pub last_backups: Vec<LastBackup>,
+    // changes waiting for the worker
+    #[serde(default)]
+    pub queued_jobs: usize,
+    // backups to a store that failed, retried later
+    pub pending_retries: usize,
+    // the saves being copied that took a while so far
+    #[serde(default)]
+    pub copying: Vec<CopyProgress>,
+    #[serde(default)]
+    pub last_error: Option<LastError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastBackup {
+    pub world: usize,
+    // unix seconds of its newest backup
+    pub time: i64,
+}
+
+// of a backup or of the watcher
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastError {
+    // unix seconds
+    pub time: i64,
+    pub message: String,
+}
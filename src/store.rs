@@ -0,0 +1,462 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+#[cfg(feature = "reflink")]
+use log::debug;
+use serde::Serialize;
+
+use crate::manifest::Manifest;
+use crate::{chunks, pack};
+use crate::game::{game, CHARACTER_KEY, GAME_DATE_KEY};
+
+// user given names of backups in a world folder, by backup id
+const LABELS_FILE_NAME: &str = "labels.json";
+// a backup still being copied, renamed to its name once complete
+const TEMP_EXTENSION: &str = "tmp";
+// read and written at a time when copying, see `clone_or_copy_with_progress`
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+// one backup file `local.sav.<timestamp>` in a world folder of the backup root
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupEntry {
+    pub id: String,
+    pub path: PathBuf,
+    // nanoseconds since the unix epoch in its name, only an id, see `utc`
+    pub timestamp: i64,
+    // when it was backed up, from the manifest, what backups are ordered and pruned by
+    pub utc: DateTime<Utc>,
+    // its number in the world, `#143`, none until the manifest has one
+    pub seq: Option<u64>,
+    pub size: u64,
+    pub label: Option<String>,
+    // the pack it was moved into, `path` is then where it was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<PathBuf>,
+    // its chunk list in the chunked format, `path` is then the backup it stands for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<PathBuf>,
+    // its sha256 in lowercase hex, only filled when asked for: the one recorded in the manifest, the
+    // backups from before it are read, which takes a while
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    // what the game profile read from the save, from the manifest
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl BackupEntry {
+    // in a world, the number goes first for when the clock was turned back
+    pub fn order(&self) -> (Option<u64>, DateTime<Utc>, i64) {
+        (self.seq, self.utc, self.timestamp)
+    }
+
+    // local wall clock time of the backup, for display
+    pub fn time(&self) -> chrono::NaiveDateTime {
+        self.utc.with_timezone(&Local).naive_local()
+    }
+
+    // the in-game year and month (0 if only the year is known) of the save
+    pub fn game_date(&self) -> Option<(u32, u32)> {
+        let text = self.metadata.get(GAME_DATE_KEY)?.trim();
+        match text.split_once('-') {
+            Some((year, month)) => Some((year.trim().parse().ok()?, month.trim().parse().ok()?)),
+            None => Some((text.parse().ok()?, 0)),
+        }
+    }
+
+    // the name of the character played in the save
+    pub fn character(&self) -> Option<&str> {
+        self.metadata.get(CHARACTER_KEY).map(String::as_str)
+    }
+}
+
+// which backups of a world `Taiwu::query_backups` returns, and how
+#[derive(Debug, Clone, Default)]
+pub struct BackupQuery {
+    // local wall clock times like `BackupEntry::time()`, `since` is included and `until` is not
+    pub since: Option<chrono::NaiveDateTime>,
+    pub until: Option<chrono::NaiveDateTime>,
+    pub newest_first: bool,
+    pub limit: Option<usize>,
+    // fill `BackupEntry::hash`
+    pub hash: bool,
+    // of the save, see `BackupEntry::game_date`, backups without one do not match
+    pub game_year: Option<u32>,
+    // a part of the character's name, in any case
+    pub character: Option<String>,
+    // by the in-game date instead of the backup time, those without one first
+    pub by_game_date: bool,
+}
+
+impl BackupQuery {
+    pub fn matches(&self, entry: &BackupEntry) -> bool {
+        let since = self.since.and_then(local_to_utc);
+        let until = self.until.and_then(local_to_utc);
+        let character = self.character.as_deref().map(str::to_lowercase);
+        since.is_none_or(|since| entry.utc >= since)
+            && until.is_none_or(|until| entry.utc < until)
+            && self.game_year.is_none_or(|year| entry.game_date().is_some_and(|(other, _)| other == year))
+            && character.is_none_or(|name| entry.character().is_some_and(|other| other.to_lowercase().contains(&name)))
+    }
+
+    // `2022-10-01 12:00:00`, `2022-10-01T12:00:00` or just the day `2022-10-01`
+    pub fn parse_time(text: &str) -> Option<chrono::NaiveDateTime> {
+        let text = text.trim();
+        ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|format| chrono::NaiveDateTime::parse_from_str(text, format).ok())
+            .or_else(|| chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+    }
+
+    pub fn apply(&self, entries: Vec<BackupEntry>) -> io::Result<Vec<BackupEntry>> {
+        let mut entries: Vec<_> = entries.into_iter().filter(|entry| self.matches(entry)).collect();
+        if self.by_game_date {
+            entries.sort_by_key(BackupEntry::game_date);
+        }
+        if self.newest_first {
+            entries.reverse();
+        }
+        if let Some(limit) = self.limit {
+            entries.truncate(limit);
+        }
+        if self.hash {
+            let mut manifests = HashMap::new();
+            for entry in &mut entries {
+                let folder = entry.path.parent().unwrap().to_owned();
+                let manifest = manifests.entry(folder).or_insert_with_key(|folder| Manifest::load(folder).unwrap_or_default());
+                let recorded = manifest.backups.get(&entry.id).and_then(|record| record.sha256.clone());
+                entry.hash = Some(match recorded {
+                    Some(sha256) => sha256,
+                    None => sha256_bytes(&read_backup(entry)?),
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// a backup of a world picked without its id, for restoring
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupSelector {
+    // or its number `#143`
+    Id(String),
+    // the backup nearest to this local wall clock time, see `parse_at`
+    At(chrono::NaiveDateTime),
+    // the one right before the newest backup with this label, e.g. the save before a fight lost
+    BeforeLabel(String),
+}
+
+impl BackupSelector {
+    // a time like `BackupQuery::parse_time`, or `21:00` (today), `yesterday 21:00`, `today`,
+    // `2h ago`, `3d ago` or `now`; also `昨天 21:00`, `前天`, `2h前`
+    pub fn parse_at(text: &str) -> Option<chrono::NaiveDateTime> {
+        parse_at_from(text, Local::now().naive_local())
+    }
+
+    // of a world's backups oldest first, none if there is no such backup
+    pub fn select(&self, entries: Vec<BackupEntry>) -> Option<BackupEntry> {
+        match self {
+            BackupSelector::Id(id) => entries.into_iter().find(|entry| entry.id == *id),
+            BackupSelector::At(time) => {
+                let at = local_to_utc(*time)?;
+                // the earlier one of two as near
+                entries.into_iter().rev().min_by_key(|entry| (entry.utc - at).num_milliseconds().unsigned_abs())
+            },
+            BackupSelector::BeforeLabel(label) => {
+                let label = label.trim().to_lowercase();
+                let labeled = |exact: bool| {
+                    entries.iter().rposition(|entry| {
+                        let Some(other) = entry.label.as_deref().map(str::to_lowercase) else {
+                            return false;
+                        };
+                        if exact { other == label } else { other.contains(&label) }
+                    })
+                };
+                let i = labeled(true).or_else(|| labeled(false))?;
+                entries.into_iter().nth(i.checked_sub(1)?)
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for BackupSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BackupSelector::Id(id) => f.write_str(id),
+            BackupSelector::At(time) => write!(f, "at {}", time.format("%Y-%m-%d %H:%M:%S")),
+            BackupSelector::BeforeLabel(label) => write!(f, "before {}", label),
+        }
+    }
+}
+
+fn parse_at_from(text: &str, now: chrono::NaiveDateTime) -> Option<chrono::NaiveDateTime> {
+    let text = text.trim().to_lowercase();
+    if let Some(time) = BackupQuery::parse_time(&text) {
+        return Some(time);
+    }
+    if text == "now" || text == "现在" {
+        return Some(now);
+    }
+    if let Some(age) = text.strip_suffix("ago").or_else(|| text.strip_suffix('前')) {
+        return now.checked_sub_signed(chrono::Duration::from_std(crate::parse_age(age)?).ok()?);
+    }
+    let days = [("today", 0), ("yesterday", 1), ("今天", 0), ("昨天", 1), ("前天", 2)];
+    let (days_ago, clock) = match days.iter().find_map(|(word, days_ago)| Some((*days_ago, text.strip_prefix(word)?.trim()))) {
+        Some((days_ago, "")) => (days_ago, chrono::NaiveTime::from_hms_opt(0, 0, 0)?),
+        Some((days_ago, clock)) => (days_ago, parse_clock(clock)?),
+        None => (0, parse_clock(&text)?),
+    };
+    Some((now.date() - chrono::Duration::days(days_ago)).and_time(clock))
+}
+
+// `21:00` or `21:00:30`
+fn parse_clock(text: &str) -> Option<chrono::NaiveTime> {
+    ["%H:%M:%S", "%H:%M"].iter().find_map(|format| chrono::NaiveTime::parse_from_str(text, format).ok())
+}
+
+pub fn backup_file_timestamp(path: &Path) -> Option<i64> {
+    let name = path.file_name()?.to_str()?;
+    let timestamp = name.strip_prefix(game().save_file_name())?.strip_prefix('.')?;
+    timestamp.parse().ok()
+}
+
+// the time a backup name stands for, for backups the manifest has no time of
+pub fn name_time(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_nanos(timestamp)
+}
+
+fn recorded(manifest: &Manifest, id: &str, timestamp: i64) -> (DateTime<Utc>, Option<u64>) {
+    match manifest.backups.get(id) {
+        Some(record) => (record.time, Some(record.seq)),
+        None => (name_time(timestamp), None),
+    }
+}
+
+fn recorded_metadata(manifest: &Manifest, id: &str) -> BTreeMap<String, String> {
+    manifest.backups.get(id).map(|record| record.metadata.clone()).unwrap_or_default()
+}
+
+// the earliest, when the clock was turned back it is there twice
+pub fn local_to_utc(time: chrono::NaiveDateTime) -> Option<DateTime<Utc>> {
+    Some(time.and_local_timezone(Local).earliest()?.with_timezone(&Utc))
+}
+
+// without its label
+pub fn backup_entry(path: &Path) -> Option<BackupEntry> {
+    let timestamp = backup_file_timestamp(path)?;
+    let size = path.metadata().ok()?.len();
+    let id = timestamp.to_string();
+    let manifest = Manifest::load(path.parent()?).unwrap_or_default();
+    let (utc, seq) = recorded(&manifest, &id, timestamp);
+    let metadata = recorded_metadata(&manifest, &id);
+    Some(BackupEntry { id, path: path.to_owned(), timestamp, utc, seq, size, label: None, pack: None, chunks: None, hash: None, metadata })
+}
+
+pub fn list_backups(folder: &Path) -> io::Result<Vec<BackupEntry>> {
+    let labels = read_labels(folder)?;
+    let manifest = Manifest::load(folder)?;
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if let Some(timestamp) = backup_file_timestamp(&path) {
+            let size = path.metadata()?.len();
+            let id = timestamp.to_string();
+            let label = labels.get(&id).cloned();
+            let (utc, seq) = recorded(&manifest, &id, timestamp);
+            let metadata = recorded_metadata(&manifest, &id);
+            entries.push(BackupEntry { id, path, timestamp, utc, seq, size, label, pack: None, chunks: None, hash: None, metadata });
+        } else if let Some((backup, timestamp)) = chunked_backup(&path) {
+            let size = chunks::read_list(&path)?.size;
+            let id = timestamp.to_string();
+            let label = labels.get(&id).cloned();
+            let (utc, seq) = recorded(&manifest, &id, timestamp);
+            let metadata = recorded_metadata(&manifest, &id);
+            entries.push(BackupEntry { id, path: backup, timestamp, utc, seq, size, label, pack: None, chunks: Some(path), hash: None, metadata });
+        }
+    }
+    // a backup still there besides its pack was not removed yet after packing
+    for (pack, file) in pack::list(folder)? {
+        let path = folder.join(&file.name);
+        let Some(timestamp) = backup_file_timestamp(&path) else { continue };
+        let id = timestamp.to_string();
+        if entries.iter().any(|entry| entry.id == id) {
+            continue;
+        }
+        let label = labels.get(&id).cloned();
+        let (utc, seq) = recorded(&manifest, &id, timestamp);
+        let metadata = recorded_metadata(&manifest, &id);
+        entries.push(BackupEntry { id, path, timestamp, utc, seq, size: file.size, label, pack: Some(pack), chunks: None, hash: None, metadata });
+    }
+    entries.sort_by_key(BackupEntry::order);
+    Ok(entries)
+}
+
+// a chunk list with the backup it stands for
+pub fn chunked_backup(list: &Path) -> Option<(PathBuf, i64)> {
+    let backup = chunks::backup_path(list)?;
+    let timestamp = backup_file_timestamp(&backup)?;
+    Some((backup, timestamp))
+}
+
+pub fn read_backup(entry: &BackupEntry) -> io::Result<Vec<u8>> {
+    match (&entry.pack, &entry.chunks) {
+        (Some(pack), _) => pack::read(pack, &entry.path.file_name().unwrap().to_string_lossy()),
+        (_, Some(list)) => chunks::read(list),
+        _ => fs::read(&entry.path),
+    }
+}
+
+// a backup file or chunk list, by path, the chunks are checked against their list
+pub fn read_stored(path: &Path) -> io::Result<Vec<u8>> {
+    match chunks::is_chunk_list(path) {
+        true => chunks::read(path),
+        false => fs::read(path),
+    }
+}
+
+// a copy-on-write clone sharing the blocks of `src` (btrfs, xfs, apfs, refs), instant and taking no
+// space until one of them is written; a regular copy where the file system can not, or `dst`
+// exists already
+pub fn clone_or_copy(src: &Path, dst: &Path) -> io::Result<()> {
+    clone_or_copy_with_progress(src, dst, |_, _| {})
+}
+
+// `progress` gets the bytes copied so far and the size of `src` after every chunk, only once
+// if it was cloned
+pub fn clone_or_copy_with_progress(src: &Path, dst: &Path, progress: impl FnMut(u64, u64)) -> io::Result<()> {
+    copy_reading(src, dst, progress, |_| {}).map(|_| ())
+}
+
+// and the sha256 of what was read from `src` (of the clone, which shares the blocks `src` had), so
+// a copy can be checked without opening `src` again
+pub fn copy_hashed_with_progress(src: &Path, dst: &Path, progress: impl FnMut(u64, u64)) -> io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    match copy_reading(src, dst, progress, |bytes| hasher.update(bytes))? {
+        true => sha256(dst),
+        false => Ok(format!("{:x}", hasher.finalize())),
+    }
+}
+
+// true if cloned, `read` gets every chunk read from `src` otherwise
+fn copy_reading(src: &Path, dst: &Path, mut progress: impl FnMut(u64, u64), mut read_chunk: impl FnMut(&[u8])) -> io::Result<bool> {
+    #[cfg(feature = "reflink")]
+    if !dst.exists() {
+        match reflink_copy::reflink(src, dst) {
+            Ok(()) => {
+                debug!("cloned `{}` to `{}`", src.display(), dst.display());
+                let len = fs::metadata(dst)?.len();
+                progress(len, len);
+                return Ok(true);
+            },
+            Err(e) => debug!("could not clone `{}`, copy it: {}", src.display(), e),
+        }
+    }
+
+    let mut reader = fs::File::open(src)?;
+    let meta = reader.metadata()?;
+    let mut writer = fs::File::create(dst)?;
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+    let mut copied = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        read_chunk(&buffer[..read]);
+        copied += read as u64;
+        progress(copied, meta.len());
+    }
+    writer.set_permissions(meta.permissions())?;
+    Ok(false)
+}
+
+// on the disk of `path`, for the free space checks
+#[cfg(feature = "free_space")]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    fs2::available_space(path)
+}
+
+// not known, so nothing is refused for lack of it
+#[cfg(not(feature = "free_space"))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Ok(u64::MAX)
+}
+
+// the backup file or chunk list
+pub fn latest_backup_file(folder: &Path) -> io::Result<Option<PathBuf>> {
+    let manifest = Manifest::load(folder).unwrap_or_default();
+    let mut latest = None;
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        let timestamp = backup_file_timestamp(&path).or_else(|| Some(chunked_backup(&path)?.1));
+        if let Some(timestamp) = timestamp {
+            let (utc, seq) = recorded(&manifest, &timestamp.to_string(), timestamp);
+            if latest.as_ref().is_none_or(|(order, _)| *order < (seq, utc, timestamp)) {
+                latest = Some(((seq, utc, timestamp), path));
+            }
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+pub fn read_labels(folder: &Path) -> io::Result<BTreeMap<String, String>> {
+    let path = folder.join(LABELS_FILE_NAME);
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    let text = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+pub fn write_labels(folder: &Path, labels: &BTreeMap<String, String>) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(labels)?;
+    fs::write(folder.join(LABELS_FILE_NAME), text)
+}
+
+pub fn temp_path(dst: &Path) -> PathBuf {
+    let mut name = dst.as_os_str().to_owned();
+    name.push(".");
+    name.push(TEMP_EXTENSION);
+    PathBuf::from(name)
+}
+
+pub fn is_temp_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == TEMP_EXTENSION)
+}
+
+// left by copies cut short (a crash, power loss), anywhere under `root`
+pub fn remove_temp_files(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    if !root.is_dir() {
+        return Ok(removed);
+    }
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            removed.extend(remove_temp_files(&path)?);
+        } else if is_temp_file(&path) {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+// lowercase hex, the same with every build, it is kept in the manifest and shown
+pub fn sha256(path: &Path) -> io::Result<String> {
+    Ok(sha256_bytes(&fs::read(path)?))
+}
+
+pub fn sha256_bytes(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:x}", Sha256::digest(bytes))
+}
@@ -0,0 +1,182 @@
+// A lock file in the backup root, held while the store is changed, so that two programs on it
+// (e.g. the tray app and `taiwu prune`) do not write the labels or manifests at the same time.
+//
+// In one process it is held by one thread at a time, the others wait for it as long as for
+// another program (e.g. the worker recording a backup while the HTTP API labels one), and a thread holding it may take it again
+// (`prune` deleting backups), the file goes away when that thread is done. A lock left by a
+// crashed process of this machine is broken by itself, any other only with `--force`.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::marker::PhantomData;
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{process, Result, TaiwuError};
+
+pub(crate) const STORE_LOCK_FILE_NAME: &str = "store.lock";
+// how long a change waits for another program to release the store
+const STORE_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const STORE_LOCK_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+// set by `--force`, the first lock found is broken whoever holds it
+static BREAK_LOCK: AtomicBool = AtomicBool::new(false);
+
+pub fn set_break_lock(force: bool) {
+    BREAK_LOCK.store(force, Ordering::SeqCst);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Holder {
+    pid: u32,
+    host: String,
+    // unix seconds
+    since: i64,
+}
+
+impl Holder {
+    fn this_process() -> Holder {
+        Holder { pid: std::process::id(), host: host_name(), since: chrono::offset::Local::now().timestamp() }
+    }
+
+    fn is_gone(&self) -> bool {
+        self.host == host_name() && !process::is_process_running(self.pid)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Holding {
+    thread: Option<ThreadId>,
+    // times it was taken by that thread
+    count: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct StoreLock {
+    path: PathBuf,
+    // nothing is written in a dry run, not even the lock
+    enabled: bool,
+    holding: Mutex<Holding>,
+    released: Condvar,
+}
+
+// released by the thread that took it
+pub(crate) struct StoreLockGuard<'a> {
+    lock: &'a StoreLock,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl StoreLock {
+    pub fn new(root: &Path, enabled: bool) -> StoreLock {
+        StoreLock { path: root.join(STORE_LOCK_FILE_NAME), enabled, holding: Mutex::new(Holding::default()), released: Condvar::new() }
+    }
+
+    pub fn acquire(&self) -> Result<StoreLockGuard<'_>> {
+        let this = thread::current().id();
+        let started = Instant::now();
+        let mut holding = self.holding.lock().unwrap();
+        while holding.thread.is_some_and(|thread| thread != this) {
+            let Some(left) = STORE_LOCK_TIMEOUT.checked_sub(started.elapsed()) else {
+                return Err(TaiwuError::StoreLocked { path: self.path.clone(), holder: describe(Some(&Holder::this_process())) });
+            };
+            holding = self.released.wait_timeout(holding, left).unwrap().0;
+        }
+        holding.thread = Some(this);
+        holding.count += 1;
+        let first = holding.count == 1;
+        // the others wait on `released` meanwhile, not on `holding`
+        drop(holding);
+        if first && self.enabled {
+            if let Err(e) = self.create(started) {
+                self.release(false);
+                return Err(e);
+            }
+        }
+        Ok(StoreLockGuard { lock: self, _not_send: PhantomData })
+    }
+
+    // once by every guard, the file is only removed when it was made
+    fn release(&self, remove_file: bool) {
+        let mut holding = self.holding.lock().unwrap();
+        holding.count -= 1;
+        if holding.count > 0 {
+            return;
+        }
+        if remove_file && self.enabled {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("could not unlock the store `{}`: {}", self.path.display(), e);
+            }
+        }
+        holding.thread = None;
+        self.released.notify_all();
+    }
+
+    fn create(&self, started: Instant) -> Result<()> {
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&self.path) {
+                Ok(mut file) => {
+                    let holder = serde_json::to_string(&Holder::this_process()).map_err(io::Error::from)?;
+                    file.write_all(holder.as_bytes())?;
+                    debug!("locked the store `{}`", self.path.display());
+                    return Ok(());
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let text = fs::read_to_string(&self.path).unwrap_or_default();
+                    let holder = serde_json::from_str::<Holder>(&text).ok();
+                    let gone = holder.as_ref().is_some_and(Holder::is_gone);
+                    if gone || BREAK_LOCK.swap(false, Ordering::SeqCst) {
+                        warn!("broke the lock `{}` of {}", self.path.display(), describe(holder.as_ref()));
+                        let _ = fs::remove_file(&self.path);
+                        continue;
+                    }
+                    if started.elapsed() >= STORE_LOCK_TIMEOUT {
+                        return Err(TaiwuError::StoreLocked { path: self.path.clone(), holder: describe(holder.as_ref()) });
+                    }
+                    thread::sleep(STORE_LOCK_RETRY_DELAY);
+                },
+                // the backup root is made by the first backup
+                Err(e) if e.kind() == io::ErrorKind::NotFound => fs::create_dir_all(self.path.parent().unwrap())?,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for StoreLockGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.release(true);
+    }
+}
+
+fn describe(holder: Option<&Holder>) -> String {
+    match holder {
+        Some(holder) => format!("process {} on `{}`", holder.pid, holder.host),
+        None => "an unknown process".to_owned(),
+    }
+}
+
+#[cfg(feature = "process")]
+fn host_name() -> String {
+    sysinfo::System::host_name().unwrap_or_default()
+}
+
+#[cfg(all(not(feature = "process"), windows))]
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(all(not(feature = "process"), unix))]
+fn host_name() -> String {
+    let mut name = [0u8; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return String::new();
+    }
+    let len = name.iter().position(|&byte| byte == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
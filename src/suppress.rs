@@ -0,0 +1,50 @@
+// Files this program writes itself (restores), whose watcher events must not become backups.
+//
+// A write is registered before it starts and is suppressed for a short while after it is done,
+// as long as the file is still the one written, so a save the game writes meanwhile is backed up.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Default)]
+pub(crate) struct SelfWrites {
+    writes: Mutex<HashMap<PathBuf, SelfWrite>>,
+}
+
+#[derive(Debug)]
+struct SelfWrite {
+    until: Instant,
+    // size and modified time once written, none while writing
+    written: Option<(u64, SystemTime)>,
+}
+
+impl SelfWrites {
+    pub fn begin(&self, path: &Path, window: Duration) {
+        let write = SelfWrite { until: Instant::now() + window, written: None };
+        self.writes.lock().unwrap().insert(path.to_owned(), write);
+    }
+
+    // the window starts again, the events may come a while after the write
+    pub fn done(&self, path: &Path, window: Duration) {
+        let written = fs::metadata(path).ok().and_then(|meta| Some((meta.len(), meta.modified().ok()?)));
+        let write = SelfWrite { until: Instant::now() + window, written };
+        self.writes.lock().unwrap().insert(path.to_owned(), write);
+    }
+
+    pub fn is_own(&self, path: &Path) -> bool {
+        let mut writes = self.writes.lock().unwrap();
+        let now = Instant::now();
+        writes.retain(|_, write| write.until > now);
+        match writes.get(path) {
+            Some(SelfWrite { written: None, .. }) => true,
+            Some(SelfWrite { written: Some(written), .. }) => {
+                let current = fs::metadata(path).ok().and_then(|meta| Some((meta.len(), meta.modified().ok()?)));
+                current.as_ref() == Some(written)
+            },
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,459 @@
+// A fake game for tests, of this crate or of programs using it (`test_support` feature): a
+// temporary game root with the save root and world folders of the game profile, saves written the
+// ways the game (or a save editor) writes them, and a config going only by it. There is no
+// detection, no Steam userdata and no `status.json`, the backups are in the same temporary folder
+// and the history and outbox in one of the process (`set_appdata_root`), so backing up, watching
+// and restoring can be run anywhere, give the same result every time and leave the user's alone.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use crate::{game, world_folder_name, Config, Result, Taiwu};
+
+// how often `wait_for_backups` looks again
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// no such folder in Steam userdata, so its saves are never used
+const NO_STEAM_USER: &str = "taiwu-test-support";
+// in the temporary folder, the appdata folder (history, outbox) of all mock games of a process
+const APPDATA_FOLDER_NAME: &str = "taiwu-mock-appdata";
+
+// removed with everything in it when dropped
+pub struct MockGame {
+    dir: TempDir,
+    // of the game root in `dir`
+    game_folder: PathBuf,
+}
+
+impl MockGame {
+    // with an empty save root, as after the first start of the game
+    pub fn new() -> io::Result<MockGame> {
+        MockGame::in_folder("game")
+    }
+
+    // the game root at `folder` in the temporary folder, e.g. a long one with Chinese names
+    pub fn in_folder(folder: impl AsRef<Path>) -> io::Result<MockGame> {
+        // one for all of them, it is of the whole process, so tests running at once share it
+        crate::set_appdata_root(Some(std::env::temp_dir().join(APPDATA_FOLDER_NAME)));
+        let dir = tempfile::Builder::new().prefix("taiwu-mock-").tempdir()?;
+        let game = MockGame { dir, game_folder: folder.as_ref().to_owned() };
+        fs::create_dir_all(game.save_root())?;
+        fs::create_dir_all(game.backup_root())?;
+        Ok(game)
+    }
+
+    // and a save in each of `worlds`, see `synthetic_save`
+    pub fn with_worlds(worlds: &[usize]) -> io::Result<MockGame> {
+        let game = MockGame::new()?;
+        for &world in worlds {
+            game.write_save(world, &synthetic_save(world, 0, 4096))?;
+        }
+        Ok(game)
+    }
+
+    pub fn game_root(&self) -> PathBuf {
+        self.dir.path().join(&self.game_folder)
+    }
+
+    pub fn save_root(&self) -> PathBuf {
+        self.game_root().join(game().save_root_name())
+    }
+
+    pub fn backup_root(&self) -> PathBuf {
+        self.dir.path().join("backups")
+    }
+
+    // whether it exists or not
+    pub fn save_path(&self, world: usize) -> PathBuf {
+        self.save_root().join(world_folder_name(world)).join(game().save_file_name())
+    }
+
+    // changes (e.g. `only_while_game_running`) are made on it before `Taiwu::with_config`
+    pub fn config(&self) -> Config {
+        Config {
+            game_root: Some(self.game_root()),
+            backup_root: Some(self.backup_root()),
+            steam_user: Some(NO_STEAM_USER.to_owned()),
+            lock_retry_attempts: 1,
+            lock_retry_delay_ms: 10,
+            min_free_space_mb: 0,
+            background_priority: false,
+            notification_cooldown_secs: 0,
+            heartbeat_interval_secs: 0,
+            ..Config::default()
+        }
+    }
+
+    pub fn taiwu(&self) -> Result<Taiwu> {
+        Taiwu::with_config(&self.config())
+    }
+
+    // at once and in place, like the game does; the world folder is made if missing
+    pub fn write_save(&self, world: usize, contents: &[u8]) -> io::Result<PathBuf> {
+        let mut file = self.open_save(world)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        Ok(self.save_path(world))
+    }
+
+    // in place in `chunks` writes `pause` apart, so a watcher sees the save change more than once
+    // (and a copy made meanwhile may find it cut short)
+    pub fn write_save_slowly(&self, world: usize, contents: &[u8], chunks: usize, pause: Duration) -> io::Result<PathBuf> {
+        let mut file = self.open_save(world)?;
+        for (i, chunk) in contents.chunks(contents.len().div_ceil(chunks.max(1)).max(1)).enumerate() {
+            if i > 0 {
+                thread::sleep(pause);
+            }
+            file.write_all(chunk)?;
+            file.sync_data()?;
+        }
+        Ok(self.save_path(world))
+    }
+
+    // written next to it and renamed over it, like save editors do
+    pub fn replace_save(&self, world: usize, contents: &[u8]) -> io::Result<PathBuf> {
+        let save = self.save_path(world);
+        fs::create_dir_all(save.parent().unwrap())?;
+        let temp = save.with_extension("tmp");
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp, &save)?;
+        Ok(save)
+    }
+
+    // cut short to `len` bytes, like the game leaves a save it crashed while writing
+    pub fn truncate_save(&self, world: usize, len: u64) -> io::Result<PathBuf> {
+        OpenOptions::new().write(true).open(self.save_path(world))?.set_len(len)?;
+        Ok(self.save_path(world))
+    }
+
+    // the world deleted in the game
+    pub fn remove_world(&self, world: usize) -> io::Result<()> {
+        fs::remove_dir_all(self.save_root().join(world_folder_name(world)))
+    }
+
+    pub fn read_save(&self, world: usize) -> io::Result<Vec<u8>> {
+        fs::read(self.save_path(world))
+    }
+
+    fn open_save(&self, world: usize) -> io::Result<File> {
+        let save = self.save_path(world);
+        fs::create_dir_all(save.parent().unwrap())?;
+        OpenOptions::new().create(true).write(true).truncate(true).open(save)
+    }
+}
+
+// `len` bytes, the same for the same world and generation and different for any other, so each
+// write of a test makes a backup of its own
+pub fn synthetic_save(world: usize, generation: u64, len: usize) -> Vec<u8> {
+    let mut state = (world as u64) << 32 ^ generation ^ 0x9e37_79b9_7f4a_7c15;
+    (0..len)
+        .map(|_| {
+            // xorshift
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+// until `Taiwu::watch` running on another thread watches the saves, false if it does not within
+// `timeout`; what is written before is only backed up if it is new when it starts
+pub fn wait_for_watching(tw: &Taiwu, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !tw.is_watching() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+    true
+}
+
+// until `world` has at least `count` backups, false if it does not within `timeout`, e.g. after a
+// write while `Taiwu::watch` runs on another thread
+pub fn wait_for_backups(tw: &Taiwu, world: usize, count: usize, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if tw.list_backups(world).is_ok_and(|backups| backups.len() >= count) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc};
+
+    use super::*;
+    use crate::game::{CHARACTER_KEY, GAME_DATE_KEY};
+    use crate::manifest::Manifest;
+    use crate::outbox::{Outbox, OutboxJob};
+    use crate::{store, BackupQuery, HistoryFilter, HistoryKind, TaiwuError, WorldConfig};
+
+    #[test]
+    fn synthetic_saves_differ_by_world_and_generation() {
+        assert_eq!(synthetic_save(1, 0, 4096), synthetic_save(1, 0, 4096));
+        assert_ne!(synthetic_save(1, 0, 4096), synthetic_save(2, 0, 4096));
+        assert_ne!(synthetic_save(1, 0, 4096), synthetic_save(1, 1, 4096));
+        assert_eq!(synthetic_save(3, 7, 100).len(), 100);
+    }
+
+    #[test]
+    fn backup_and_restore() -> Result<()> {
+        let game = MockGame::with_worlds(&[1, 2])?;
+        let tw = game.taiwu()?;
+        tw.backup_once().into_result()?;
+        assert_eq!(tw.list_backups(1)?.len(), 1);
+        assert_eq!(tw.list_backups(2)?.len(), 1);
+
+        let first = game.read_save(1)?;
+        game.write_save(1, &synthetic_save(1, 1, 4096))?;
+        tw.backup_once_for_new_save().into_result()?;
+        let backups = tw.list_backups(1)?;
+        assert_eq!(backups.len(), 2);
+        assert_eq!(tw.list_backups(2)?.len(), 1, "an unchanged save is not backed up again");
+
+        tw.restore(1, &backups[0].id)?;
+        assert_eq!(game.read_save(1)?, first);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_restore_puts_back_the_replaced_save() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        let tw = game.taiwu()?;
+        tw.backup_once().into_result()?;
+        let backup = tw.list_backups(1)?.remove(0);
+        let played = synthetic_save(1, 1, 4096);
+        game.write_save(1, &played)?;
+
+        tw.restore(1, &backup.id)?;
+        assert_ne!(game.read_save(1)?, played);
+        tw.undo_restore(1)?;
+        assert_eq!(game.read_save(1)?, played);
+        assert!(!store::temp_path(&game.save_path(1)).exists());
+        Ok(())
+    }
+
+    #[cfg(feature = "pack")]
+    #[test]
+    fn pruned_packed_backups_are_gone() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        let tw = game.taiwu()?;
+        for generation in 1..5 {
+            tw.backup_once().into_result()?;
+            game.write_save(1, &synthetic_save(1, generation, 4096))?;
+        }
+        assert_eq!(tw.pack(Some(Duration::ZERO))?.packed, 3);
+        let report = tw.prune(&crate::PrunePolicy { keep_last: Some(2), ..Default::default() })?;
+        assert_eq!(report.deleted.len(), 2);
+
+        let backups = tw.list_backups(1)?;
+        assert_eq!(backups.len(), 2);
+        for pruned in &report.deleted {
+            assert!(backups.iter().all(|entry| entry.id != pruned.id));
+        }
+        for entry in &backups {
+            tw.restore(1, &entry.id)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn backups_over_the_quota_are_gone_from_the_list() -> Result<()> {
+        let game = MockGame::with_worlds(&[1, 2])?;
+        let mut config = Config { store_quota_mb: Some(1), ..game.config() };
+        // whatever the quota
+        config.worlds.insert("2".to_owned(), WorldConfig { keep_last: Some(3), ..Default::default() });
+        let tw = Taiwu::with_config(&config)?;
+        for generation in 0..6 {
+            for world in [1, 2] {
+                game.write_save(world, &synthetic_save(world, generation, 256 * 1024))?;
+            }
+            tw.backup_once().into_result()?;
+            if generation == 0 {
+                tw.set_label(1, &tw.list_backups(1)?[0].id, "first")?;
+            }
+        }
+
+        let backups = tw.list_backups(1)?;
+        assert_eq!(backups.len(), 2, "the labeled and the latest backups are left");
+        assert_eq!(backups[0].label.as_deref(), Some("first"));
+        assert_eq!(tw.list_backups(2)?.len(), 3);
+        let folder = game.backup_root().join(world_folder_name(1));
+        assert_eq!(Manifest::load(&folder)?.backups.len(), 2);
+        assert!(tw.verify()?.problems.is_empty());
+        for entry in &backups {
+            tw.restore(1, &entry.id)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn backups_are_found_by_in_game_date_and_character() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        let tw = game.taiwu()?;
+        for generation in 0..4 {
+            game.write_save(1, &synthetic_save(1, generation, 4096))?;
+            tw.backup_once().into_result()?;
+        }
+        // as a game profile reading them from the saves records them, a character loaded from an older save last
+        let folder = game.backup_root().join(world_folder_name(1));
+        let mut manifest = Manifest::load(&folder)?;
+        let read = [("3-7", "太吾 李"), ("4", "太吾 李"), ("4-2", "王"), ("3-1", "王")];
+        for (record, (date, character)) in manifest.backups.values_mut().zip(read) {
+            record.metadata.insert(GAME_DATE_KEY.to_owned(), date.to_owned());
+            record.metadata.insert(CHARACTER_KEY.to_owned(), character.to_owned());
+        }
+        manifest.save(&folder)?;
+
+        let dates = |query: BackupQuery| -> Result<Vec<_>> {
+            Ok(tw.query_backups(1, &query)?.iter().map(|entry| entry.game_date().unwrap()).collect())
+        };
+        assert_eq!(dates(BackupQuery::default())?, vec![(3, 7), (4, 0), (4, 2), (3, 1)]);
+        assert_eq!(dates(BackupQuery { by_game_date: true, ..Default::default() })?, vec![(3, 1), (3, 7), (4, 0), (4, 2)]);
+        assert_eq!(dates(BackupQuery { game_year: Some(3), newest_first: true, ..Default::default() })?, vec![(3, 1), (3, 7)]);
+        let query = BackupQuery { character: Some("李".to_owned()), by_game_date: true, newest_first: true, ..Default::default() };
+        assert_eq!(dates(query)?, vec![(4, 0), (3, 7)]);
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_save_is_not_backed_up() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        let tw = game.taiwu()?;
+        game.truncate_save(1, 10)?;
+        assert!(matches!(tw.backup_once().into_result(), Err(TaiwuError::SaveTruncated { world: 1, .. })));
+        assert!(tw.list_backups(1)?.is_empty());
+        assert_eq!(tw.truncated_worlds(), vec![1]);
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_save_at_startup_is_a_skip() -> Result<()> {
+        let game = MockGame::with_worlds(&[1, 2])?;
+        let tw = game.taiwu()?;
+        game.truncate_save(1, 0)?;
+        let report = tw.backup_once_for_new_save();
+        assert_eq!(report.done, vec![game.save_path(2)]);
+        assert!(matches!(report.failed[..], [(_, TaiwuError::SaveTruncated { world: 1, .. })]));
+        // the history is shared by the tests running at the same time
+        let filter = HistoryFilter { kinds: vec![HistoryKind::Error], ..Default::default() };
+        let save = game.save_path(1).display().to_string();
+        assert!(!tw.history(&filter)?.iter().any(|entry| entry.message.contains(&save)));
+        Ok(())
+    }
+
+    #[test]
+    fn labels_set_at_once_are_all_kept() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        let tw = game.taiwu()?;
+        for generation in 0..8 {
+            game.write_save(1, &synthetic_save(1, generation, 4096))?;
+            tw.backup_once().into_result()?;
+        }
+        let backups = tw.list_backups(1)?;
+        thread::scope(|s| {
+            for (i, entry) in backups.iter().enumerate() {
+                let tw = &tw;
+                s.spawn(move || tw.set_label(1, &entry.id, &format!("label {}", i)).unwrap());
+            }
+        });
+        let labeled = tw.list_backups(1)?.iter().filter(|entry| entry.label.is_some()).count();
+        assert_eq!(labeled, backups.len());
+        Ok(())
+    }
+
+    #[test]
+    fn failed_backups_are_queued_while_others_are_retried() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        // a file where the mirror should be, so every copy to it fails and is queued
+        let mirror = game.backup_root().with_file_name("mirror");
+        fs::write(&mirror, "")?;
+        let mut tw = Taiwu::with_config(&Config { mirror_roots: vec![mirror.clone()], ..game.config() })?;
+        // not the one of the process, the jobs left would be retried by the next test run
+        tw.outbox = Outbox::open(game.backup_root().with_file_name("outbox"));
+        let tw = Arc::new(tw);
+        let save = game.save_path(1);
+
+        let (sender, finished) = mpsc::channel();
+        let (backing_up, retrying) = (Arc::clone(&tw), Arc::clone(&tw));
+        let (src, done) = (save.clone(), sender.clone());
+        thread::spawn(move || {
+            let result = (1..20).try_for_each(|generation| {
+                fs::write(&src, synthetic_save(1, generation, 4096))?;
+                backing_up.backup(&src)
+            });
+            done.send(result).unwrap();
+        });
+        thread::spawn(move || {
+            let result = (0..20).try_for_each(|_| {
+                let job = OutboxJob { next_try: 0, ..OutboxJob::new(save.clone(), mirror.clone(), PathBuf::from("retried"), false) };
+                retrying.outbox.push(job)?;
+                retrying.retry_outbox()
+            });
+            sender.send(result).unwrap();
+        });
+        for _ in 0..2 {
+            finished.recv_timeout(Duration::from_secs(60)).expect("a backup and a retry wait for each other")?;
+        }
+        assert_eq!(tw.outbox.len(), 19 + 20);
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[test]
+    fn chunks_are_kept_while_backups_are_deleted() -> Result<()> {
+        let game = MockGame::with_worlds(&[1, 2])?;
+        let tw = Taiwu::with_config(&Config { store_format: crate::StoreFormat::Chunked, ..game.config() })?;
+        for generation in 0..8 {
+            game.write_save(2, &synthetic_save(2, generation, 64 * 1024))?;
+            tw.backup_once().into_result()?;
+        }
+        let before = tw.list_backups(1)?.len();
+        thread::scope(|s| -> Result<()> {
+            let deleting = s.spawn(|| -> Result<()> {
+                for entry in tw.list_backups(2)?.iter().skip(1) {
+                    tw.delete_backup(2, &entry.id)?;
+                }
+                Ok(())
+            });
+            for generation in 1..8 {
+                let save = game.write_save(1, &synthetic_save(1, generation, 64 * 1024))?;
+                tw.backup(&save)?;
+            }
+            deleting.join().unwrap()
+        })?;
+        assert_eq!(tw.list_backups(1)?.len(), before + 7);
+        assert!(tw.verify()?.problems.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn replaced_save_is_backed_up_while_watching() -> Result<()> {
+        let game = MockGame::with_worlds(&[1])?;
+        let tw = game.taiwu()?;
+        tw.backup_once().into_result()?;
+        thread::scope(|s| {
+            let watch = s.spawn(|| tw.watch());
+            assert!(wait_for_watching(&tw, Duration::from_secs(20)));
+            game.replace_save(1, &synthetic_save(1, 1, 4096))?;
+            let backed_up = wait_for_backups(&tw, 1, 2, Duration::from_secs(20));
+            tw.stop();
+            watch.join().unwrap()?;
+            assert!(backed_up);
+            Ok(())
+        })
+    }
+}
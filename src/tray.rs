@@ -0,0 +1,465 @@
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use log::{debug, error};
+use tray_item::{IconSource, TrayItem};
+
+use taiwu::{autostart, ipc, LogLevel, Taiwu};
+
+use crate::cli::format_size;
+use crate::i18n::tr;
+use crate::{hotkey, notification};
+
+// Instead of env!("CARGO_PKG_AUTHORS")
+const APP_AUTHOR: &str = "owtotwo";
+
+// the icon resource compiled in by build.rs, other platforms take a theme icon name
+#[cfg(windows)]
+const TRAY_ICON: &str = "TAIWU_ICON_1";
+#[cfg(not(windows))]
+const TRAY_ICON: &str = "document-save";
+
+// the "5 分钟前" of the status labels goes stale otherwise
+#[cfg(windows)]
+const STATUS_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub fn run(dry_run: bool) {
+    let log_folder = crate::logfile::init();
+
+    #[cfg(target_os = "linux")]
+    gtk::init().unwrap();
+
+    let title = format!("Taiwu Backup (v{}) by {}", crate::APP_VERSION, APP_AUTHOR);
+    let mut tray = TrayItem::new(&title, IconSource::Resource(TRAY_ICON)).unwrap();
+
+    // asked for what could not be found, then tried again
+    let loaded = match crate::load_taiwu(dry_run) {
+        Err(e) if crate::setup::run(&e) => crate::load_taiwu(dry_run),
+        loaded => loaded,
+    };
+    let (config, tw) = match loaded {
+        Ok((config, tw)) => (config, Arc::new(tw)),
+        Err(e) => {
+            crate::alert(&tr!("太吾绘卷存档自动备份无法启动: {}", "Taiwu Backup could not start: {}", e));
+            return;
+        },
+    };
+
+    #[cfg(windows)]
+    crate::session::spawn(&tw);
+
+    tray.add_label(&tr!("[*正在运行中]", "[*Running]")).unwrap();
+    // one per world with the time of its latest backup, only on windows, the tray of the other
+    // platforms can not change a label afterwards and it would show the time of the start forever
+    #[cfg(windows)]
+    let status_labels: Vec<u32> = world_status(&tw, &tw.status()).iter().map(|status| tray.inner_mut().add_label_with_id(status).unwrap()).collect();
+    tray.add_label(&tr!("[游戏目录识别: {}]", "[Game folder found by: {}]", tw.detect_method())).unwrap();
+    if let Some(profile) = tw.profile() {
+        tray.add_label(&tr!("[配置档: {}]", "[Profile: {}]", profile)).unwrap();
+    }
+    for name in config.profiles.keys().filter(|name| Some(name.as_str()) != tw.profile()) {
+        let (tw1, name) = (Arc::clone(&tw), name.clone());
+        tray.add_menu_item(&tr!("切换到配置档 {}", "Switch to profile {}", name), move || {
+            debug!("Switch profile occurred!");
+            match taiwu::save_active_profile(Some(&name)) {
+                Ok(()) => restart(&tw1),
+                Err(e) => error!("[profile] error: {:?}", e),
+            }
+        })
+        .unwrap();
+    }
+
+    let tw1 = Arc::clone(&tw);
+    tray.add_menu_item(&tr!("启动游戏", "Launch game"), move || {
+        debug!("Launch game occurred!");
+        if let Err(e) = tw1.launch_game() {
+            error!("[launch] error: {:?}", e);
+            notification::show(&tr!("启动游戏失败", "Could not launch the game"), &e.to_string());
+        }
+    })
+    .unwrap();
+
+    let game_folder = tw.game_root();
+    tray.add_menu_item(&tr!("打开游戏目录", "Open game folder"), move || {
+        debug!("Open game folder occurred!");
+        open_folder(&game_folder);
+    })
+    .unwrap();
+
+    let backup_folder = tw.backup_root();
+    tray.add_menu_item(&tr!("打开备份目录", "Open backup folder"), move || {
+        debug!("Open backup folder occurred!");
+        open_folder(&backup_folder);
+    })
+    .unwrap();
+
+    tray.add_menu_item(&tr!("打开日志目录", "Open log folder"), move || {
+        debug!("Open log folder occurred!");
+        open_folder(&log_folder);
+    })
+    .unwrap();
+
+    tray.add_menu_item(&tr!("切换日志级别", "Switch log level"), move || {
+        debug!("Switch log level occurred!");
+        let current = LogLevel::ALL.iter().position(|level| *level == LogLevel::current()).unwrap_or(0);
+        let level = LogLevel::ALL[(current + 1) % LogLevel::ALL.len()];
+        level.apply();
+        notification::show(&tr!("日志级别", "Log level"), &tr!("已切换为 {}", "Switched to {}", level));
+    })
+    .unwrap();
+
+    let tw1 = Arc::clone(&tw);
+    tray.add_menu_item(&tr!("统计信息", "Statistics"), move || {
+        debug!("Show stats occurred!");
+        match tw1.stats() {
+            Ok(stats) => {
+                let body = tr!(
+                    "共 {} 个备份，占用 {}，其中重复 {}",
+                    "{} backups taking {}, {} of them duplicates",
+                    stats.count,
+                    format_size(stats.size),
+                    format_size(stats.duplicate_size),
+                );
+                notification::show(&tr!("统计信息", "Statistics"), &body);
+            },
+            Err(e) => error!("[stats] error: {:?}", e),
+        }
+    })
+    .unwrap();
+
+    let tw1 = Arc::clone(&tw);
+    tray.add_menu_item(&tr!("撤销上次恢复", "Undo last restore"), move || {
+        debug!("Undo last restore occurred!");
+        let Some(world) = tw1.last_restored_world() else {
+            notification::show(&tr!("撤销上次恢复", "Undo last restore"), &tr!("没有可以撤销的恢复", "There is no restore to undo"));
+            return;
+        };
+        match tw1.undo_restore(world) {
+            Ok(_) => notification::show(&tr!("撤销上次恢复", "Undo last restore"), &tr!("存档位 {} 已还原到恢复之前", "World {} is back to before the restore", world)),
+            Err(e) => {
+                error!("[undo_restore] error: {:?}", e);
+                notification::show(&tr!("撤销失败", "Undo failed"), &e.to_string());
+            },
+        }
+    })
+    .unwrap();
+
+    let tw1 = Arc::clone(&tw);
+    tray.add_menu_item(&tr!("导出最新备份", "Export latest backup"), move || {
+        debug!("Export latest backup occurred!");
+        // of the world played last, into the downloads folder
+        let Some((world, _)) = tw1.last_backups().into_iter().max_by_key(|(_, time)| *time) else {
+            notification::show(&tr!("导出最新备份", "Export latest backup"), &tr!("还没有备份", "There is no backup yet"));
+            return;
+        };
+        let folder = directories::UserDirs::new()
+            .and_then(|dirs| dirs.download_dir().map(Path::to_owned))
+            .unwrap_or_else(|| tw1.backup_root());
+        match tw1.export(world, None, &folder) {
+            Ok(path) => {
+                notification::show(&tr!("导出最新备份", "Export latest backup"), &tr!("已导出到 {}", "Exported to {}", path.display()));
+                open_folder(&folder);
+            },
+            Err(e) => {
+                error!("[export] error: {:?}", e);
+                notification::show(&tr!("导出失败", "Export failed"), &e.to_string());
+            },
+        }
+    })
+    .unwrap();
+
+    let tw1 = Arc::clone(&tw);
+    tray.add_menu_item(&tr!("恢复损坏的存档", "Restore damaged saves"), move || {
+        let worlds = tw1.truncated_worlds();
+        if worlds.is_empty() {
+            notification::show(&tr!("恢复损坏的存档", "Restore damaged saves"), &tr!("没有发现损坏的存档", "No damaged save was found"));
+        }
+        for world in worlds {
+            notification::restore_latest_good(&tw1, world);
+        }
+    })
+    .unwrap();
+
+    let autostart_enabled = autostart::is_enabled().unwrap_or(false);
+    let autostart_label = if autostart_enabled {
+        tr!("[开机自启: 已开启]", "[Autostart: on]")
+    } else {
+        tr!("[开机自启: 已关闭]", "[Autostart: off]")
+    };
+    tray.add_label(&autostart_label).unwrap();
+    tray.add_menu_item(&tr!("开机自启", "Toggle autostart"), move || {
+        debug!("Toggle autostart occurred!");
+        let enabled = autostart::is_enabled().unwrap_or(false);
+        if let Err(e) = autostart::set_enabled(!enabled) {
+            error!("[autostart] error: {:?}", e);
+        }
+    })
+    .unwrap();
+
+    #[cfg(feature = "http")]
+    if let Some(port) = config.http_port {
+        tray.add_menu_item(&tr!("打开控制面板", "Open dashboard"), move || {
+            debug!("Open dashboard occurred!");
+            open_url_in_browser(&format!("http://127.0.0.1:{}/", port));
+        })
+        .unwrap();
+    }
+
+    #[cfg(feature = "update")]
+    {
+        tray.add_menu_item(&tr!("检查更新", "Check for updates"), move || {
+            debug!("Check for updates occurred!");
+            thread::spawn(|| match crate::update::check() {
+                Ok(Some(release)) => notify_update(release),
+                Ok(None) => notification::show(&tr!("检查更新", "Check for updates"), &tr!("已是最新版本", "This is the latest version")),
+                Err(e) => {
+                    error!("[update] error: {}", e);
+                    notification::show(&tr!("检查更新失败", "Update check failed"), &e.to_string());
+                },
+            });
+        })
+        .unwrap();
+
+        if config.check_updates {
+            crate::update::spawn_checker(notify_update);
+        }
+    }
+
+    tray.add_menu_item(&tr!("打开GitHub项目", "Open GitHub project"), move || {
+        debug!("Open github repository of this program occurred!");
+        open_url_in_browser(crate::APP_REPOSITORY);
+    })
+    .unwrap();
+
+    let tw1 = Arc::clone(&tw);
+    tray.add_menu_item(&tr!("退出", "Quit"), move || {
+        debug!("Quit occurred!");
+        quit(&tw1);
+    })
+    .unwrap();
+
+    let tw1 = Arc::clone(&tw);
+    if let Err(e) = ipc::serve(Arc::clone(&tw), move || quit(&tw1)) {
+        error!("[ipc] error: {:?}", e);
+    }
+    crate::serve_http(&tw, &config);
+
+    let _hotkey = config.hotkey.as_deref().and_then(|combo| register_hotkey(&tw, combo));
+    if let Some(combo) = &config.hotkey {
+        tray.add_label(&tr!("[快捷键备份: {}]", "[Backup hotkey: {}]", combo)).unwrap();
+    }
+
+    crate::start_notifiers(&tw, &config, true);
+    let heartbeat = crate::start_heartbeat(&tw, &config);
+
+    // do backup once on every boot if it has not been backed up, the failures are logged one by
+    // one and watching starts anyway, a truncated save is left to the restore item
+    let report = tw.backup_once_for_new_save();
+    if !report.failed.is_empty() {
+        error!("[backup_once] {} files not backed up", report.failed.len());
+    }
+
+    #[cfg(windows)]
+    let events = tw.subscribe();
+    let tw1 = Arc::clone(&tw);
+    let handle = thread::spawn(move || {
+        if let Err(e) = tw1.watch() {
+            error!("[watch] error: {:?}", e);
+            return;
+        }
+    });
+
+    // the tray on linux and macos only works while the main thread runs their event loop
+    #[cfg(target_os = "linux")]
+    gtk::main();
+    #[cfg(target_os = "macos")]
+    tray.inner_mut().display();
+
+    // refreshed on every backup and once a minute until the watcher returns, the tooltip shows
+    // how far a long copy got
+    #[cfg(windows)]
+    while !handle.is_finished() {
+        match events.recv_timeout(STATUS_REFRESH_INTERVAL) {
+            Ok(taiwu::TaiwuEvent::CopyProgress(copy)) => {
+                let name = copy.src.parent().and_then(|world| world.file_name()).unwrap_or(copy.src.as_os_str());
+                let percent = copy.copied * 100 / copy.total.max(1);
+                let _ = tray.inner_mut().set_tooltip(&tr!("正在备份 {} {}%", "Backing up {} {}%", name.to_string_lossy(), percent));
+                continue;
+            },
+            Ok(taiwu::TaiwuEvent::BackupDone { .. } | taiwu::TaiwuEvent::BackupFailed { .. }) | Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Ok(_) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        let status = tw.status();
+        let _ = tray.inner_mut().set_tooltip(&tooltip(&title, &status));
+        for (id, status) in status_labels.iter().zip(world_status(&tw, &status)) {
+            if let Err(e) = tray.inner_mut().set_label(&status, *id) {
+                error!("[tray] status error: {:?}", e);
+            }
+        }
+    }
+
+    handle.join().unwrap();
+    if let Some(heartbeat) = heartbeat {
+        let _ = heartbeat.join();
+    }
+}
+
+// the title, whether it watches, and the last error if it came after the last backup
+#[cfg(windows)]
+fn tooltip(title: &str, status: &taiwu::TaiwuStatus) -> String {
+    // windows cuts off a longer one
+    const TOOLTIP_MAX_CHARS: usize = 127;
+
+    let now = chrono::Utc::now().timestamp();
+    let state = match (status.paused, status.watching) {
+        (true, _) => tr!("已暂停", "paused"),
+        (false, true) => tr!("监控中", "watching"),
+        (false, false) => tr!("未监控", "not watching"),
+    };
+    let mut text = format!("{}\n{}", title, state);
+    let last_backup = status.last_backups.iter().map(|last| last.time).max().unwrap_or(0);
+    if let Some(error) = status.last_error.as_ref().filter(|error| error.time >= last_backup) {
+        text += &tr!("\n{}出错: {}", "\nfailed {}: {}", format_age(now - error.time), error.message);
+    }
+    text.chars().take(TOOLTIP_MAX_CHARS).collect()
+}
+
+// `world_1: 5 分钟前` for every world, in the order of `worlds()`
+#[cfg(windows)]
+fn world_status(tw: &Taiwu, status: &taiwu::TaiwuStatus) -> Vec<String> {
+    let now = chrono::Utc::now().timestamp();
+    tw.worlds()
+        .into_iter()
+        .map(|world| {
+            let name = format!("world_{}", world);
+            match status.last_backups.iter().find(|last| last.world == world) {
+                Some(last) => format!("{}: {}", name, format_age(now - last.time)),
+                None => tr!("{}: 还没有备份", "{}: no backup yet", name),
+            }
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn format_age(secs: i64) -> String {
+    match secs {
+        ..=59 => tr!("刚刚", "just now"),
+        60..=3599 => tr!("{} 分钟前", "{} minutes ago", secs / 60),
+        3600..=86399 => tr!("{} 小时前", "{} hours ago", secs / 3600),
+        _ => tr!("{} 天前", "{} days ago", secs / 86400),
+    }
+}
+
+// presses are queued to one thread, so a held down hotkey does not start many copies at once
+fn register_hotkey(tw: &Arc<Taiwu>, combo: &str) -> Option<hotkey::Hotkey> {
+    let (tx, rx) = mpsc::channel();
+
+    let tw = Arc::clone(tw);
+    thread::spawn(move || {
+        for () in rx {
+            match tw.backup_once().into_result() {
+                Ok(_) => notification::show(&tr!("备份完成", "Backup done"), &tr!("已备份所有存档位", "All worlds are backed up")),
+                Err(e) => {
+                    error!("[hotkey] backup error: {:?}", e);
+                    notification::show(&tr!("备份失败", "Backup failed"), &e.to_string());
+                },
+            }
+        }
+    });
+
+    let result = hotkey::register(combo, move || {
+        debug!("Hotkey backup occurred!");
+        let _ = tx.send(());
+    });
+    match result {
+        Ok(hotkey) => Some(hotkey),
+        Err(e) => {
+            error!("[hotkey] `{}` error: {}", combo, e);
+            None
+        },
+    }
+}
+
+#[cfg(feature = "update")]
+fn notify_update(release: crate::update::Release) {
+    let body = tr!(
+        "新版本 {} 已发布(当前 v{})",
+        "Version {} is out (this is v{})",
+        release.tag_name,
+        crate::APP_VERSION,
+    );
+    notification::show_with_action(&tr!("发现新版本", "Update available"), &body, &tr!("下载", "Download"), move || {
+        open_url_in_browser(&release.html_url);
+    });
+}
+
+fn quit(tw: &Taiwu) {
+    tw.shutdown(); // then watch() returns once the copies running are done, so handle.join() finishes
+
+    // the event loop of the tray is on the main thread and must end too
+    #[cfg(target_os = "linux")]
+    gtk::glib::idle_add(|| {
+        gtk::main_quit();
+        gtk::glib::ControlFlow::Break
+    });
+    #[cfg(target_os = "macos")]
+    {
+        tw.wait_idle(taiwu::SHUTDOWN_TIMEOUT);
+        std::process::exit(0);
+    }
+}
+
+// with the same options but `--profile`, the profile switched to is used then; the new instance
+// waits for this one to quit
+fn restart(tw: &Taiwu) {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return error!("[restart] error: {:?}", e),
+    };
+    let mut args = Vec::new();
+    let mut skip_value = false;
+    for arg in std::env::args_os().skip(1) {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if arg == "--profile" {
+            skip_value = true;
+        } else if arg != "--restarted" && !arg.to_string_lossy().starts_with("--profile=") {
+            args.push(arg);
+        }
+    }
+    let mut command = std::process::Command::new(exe);
+    command.arg("--restarted").args(&args);
+    if taiwu::is_portable() && !args.iter().any(|arg| arg == "--portable") {
+        command.arg("--portable");
+    }
+    match command.spawn() {
+        Ok(_) => quit(tw),
+        Err(e) => error!("[restart] error: {:?}", e),
+    }
+}
+
+fn open_folder(folder: &Path) {
+    use std::process::Command;
+
+    #[cfg(windows)]
+    let opener = "explorer";
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let opener = "xdg-open";
+
+    match Command::new(opener).arg(folder).spawn() {
+        Ok(_) => debug!("Opened folder `{}` in {}", folder.display(), opener),
+        Err(e) => error!("An error occurred when opening folder `{}` in {}: \n{}", folder.display(), opener, e),
+    }
+}
+
+fn open_url_in_browser(url: &str) {
+    match open::that(url) {
+        Ok(()) => debug!("Open url `{}` in default browser", url),
+        Err(e) => error!("An error occurred when opening url `{}` in default browser: \n{}", url, e),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,185 @@
+// Look for a newer release of this program on GitHub, and replace this executable with it.
+
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use log::{info, warn};
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const RELEASES_API_URL: &str = "https://api.github.com/repos";
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// found next to the asset as `<asset>.sha256`, or one line per asset in a sums file
+const CHECKSUM_FILE_NAMES: [&str; 3] = ["SHA256SUMS", "SHA256SUMS.txt", "sha256sums.txt"];
+// the executable that was replaced, removed on the next start (windows cannot while it runs)
+const REPLACED_SUFFIX: &str = "old";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    // the release page
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(self.tag_name.trim_start_matches('v')).ok()
+    }
+
+    // the executable for this platform, by os and arch in its name (a windows release may
+    // only have the one `.exe`)
+    fn executable(&self) -> Option<&Asset> {
+        let os_names: &[&str] = match std::env::consts::OS {
+            "windows" => &["windows", "win64", "win"],
+            "macos" => &["macos", "darwin", "apple"],
+            os => &[os],
+        };
+        let is_checksum = |asset: &&Asset| asset.name.ends_with(".sha256") || CHECKSUM_FILE_NAMES.contains(&asset.name.as_str());
+        let candidates: Vec<_> = self.assets.iter().filter(|asset| !is_checksum(asset)).collect();
+        let matches = |asset: &&&Asset| {
+            let name = asset.name.to_ascii_lowercase();
+            os_names.iter().any(|os| name.contains(os)) && name.contains(std::env::consts::ARCH)
+        };
+        candidates.iter().find(matches).copied().or_else(|| {
+            let exes: Vec<_> = candidates.iter().filter(|asset| asset.name.ends_with(".exe")).collect();
+            match exes.as_slice() {
+                [exe] if cfg!(windows) => Some(**exe),
+                _ => None,
+            }
+        })
+    }
+
+    // lowercase hex sha256 of `asset`, from its own checksum file or a sums file
+    fn checksum(&self, agent: &ureq::Agent, asset: &Asset) -> Result<String, Box<dyn Error>> {
+        let own = format!("{}.sha256", asset.name);
+        let file = self.assets.iter()
+            .find(|other| other.name == own)
+            .or_else(|| self.assets.iter().find(|other| CHECKSUM_FILE_NAMES.contains(&other.name.as_str())))
+            .ok_or_else(|| format!("release {} has no checksum for `{}`", self.tag_name, asset.name))?;
+        let text = agent.get(&file.browser_download_url).call()?.into_string()?;
+        // `<hex>  <name>` lines, or only the hex in a file of its own
+        text.lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let hex = parts.next()?;
+                match parts.next().map(|name| name.trim_start_matches('*')) {
+                    Some(name) if name != asset.name => None,
+                    _ => Some(hex.to_ascii_lowercase()),
+                }
+            })
+            .next()
+            .ok_or_else(|| format!("no checksum for `{}` in `{}`", asset.name, file.name).into())
+    }
+}
+
+pub fn agent() -> Result<ureq::Agent, Box<dyn Error>> {
+    let tls = native_tls::TlsConnector::new()?;
+    Ok(ureq::AgentBuilder::new()
+        .tls_connector(Arc::new(tls))
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(&format!("taiwu-backup/{}", crate::APP_VERSION))
+        .build())
+}
+
+pub fn latest_release() -> Result<Release, Box<dyn Error>> {
+    let repository = crate::APP_REPOSITORY.trim_start_matches("https://github.com/");
+    let url = format!("{}/{}/releases/latest", RELEASES_API_URL, repository);
+    let text = agent()?.get(&url).set("Accept", "application/vnd.github+json").call()?.into_string()?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+// the latest release, if it is newer than this program
+pub fn check() -> Result<Option<Release>, Box<dyn Error>> {
+    let release = latest_release()?;
+    let current = Version::parse(crate::APP_VERSION)?;
+    match release.version() {
+        Some(version) if version > current => Ok(Some(release)),
+        Some(_) => Ok(None),
+        None => Err(format!("release tag `{}` is not a version", release.tag_name).into()),
+    }
+}
+
+// now and then daily, `on_newer` is called once for every newer version found
+pub fn spawn_checker(on_newer: impl Fn(Release) + Send + 'static) {
+    thread::spawn(move || {
+        let mut notified = None;
+        loop {
+            match check() {
+                Ok(Some(release)) if notified.as_ref() != Some(&release.tag_name) => {
+                    info!("[update] {} is available", release.tag_name);
+                    notified = Some(release.tag_name.clone());
+                    on_newer(release);
+                },
+                Ok(_) => {},
+                Err(e) => warn!("[update] check error: {}", e),
+            }
+            thread::sleep(UPDATE_CHECK_INTERVAL);
+        }
+    });
+}
+
+// download the executable of `release`, check it against the published checksum and put it
+// in place of the running one, which is kept as `<exe>.old` until the next start
+pub fn install(release: &Release) -> Result<PathBuf, Box<dyn Error>> {
+    let asset = release.executable()
+        .ok_or_else(|| format!("release {} has no executable for {} {}", release.tag_name, std::env::consts::OS, std::env::consts::ARCH))?;
+    let agent = agent()?;
+    let expected = release.checksum(&agent, asset)?;
+
+    info!("[update] downloading `{}`", asset.browser_download_url);
+    let mut bytes = Vec::new();
+    agent.get(&asset.browser_download_url).call()?.into_reader().read_to_end(&mut bytes)?;
+    let actual = format!("{:x}", Sha256::digest(&bytes));
+    if actual != expected {
+        return Err(format!("checksum of `{}` does not match: {} expected, {} downloaded", asset.name, expected, actual).into());
+    }
+
+    let exe = std::env::current_exe()?;
+    let new = exe.with_extension("new");
+    fs::write(&new, &bytes)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // a running executable can be renamed on windows, but not overwritten
+    let replaced = replaced_path(&exe);
+    let _ = fs::remove_file(&replaced);
+    fs::rename(&exe, &replaced)?;
+    if let Err(e) = fs::rename(&new, &exe) {
+        let _ = fs::rename(&replaced, &exe);
+        return Err(e.into());
+    }
+    info!("[update] installed {} to `{}`", release.tag_name, exe.display());
+    Ok(exe)
+}
+
+// left by the last update
+pub fn remove_replaced() {
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = fs::remove_file(replaced_path(&exe));
+    }
+}
+
+fn replaced_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(REPLACED_SUFFIX);
+    exe.with_file_name(name)
+}
@@ -0,0 +1,149 @@
+// An integrity scan of the stores: every backup of the primary store is read through and must
+// still have the content recorded when it was copied, and its copy on a mirror (if it has one
+// yet) must have the same. Backups that do not are moved to `quarantine` in their folder, where
+// a restore does not pick them, with the reason in the manifest.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::manifest::{Manifest, QuarantineRecord};
+use crate::{chunks, store};
+
+const QUARANTINE_FOLDER_NAME: &str = "quarantine";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    // backups read
+    pub checked: usize,
+    pub bytes: u64,
+    pub problems: Vec<VerifyProblem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyProblem {
+    pub path: PathBuf,
+    pub problem: String,
+    // where it was moved, none if it could not be read (it may only be locked) or in a dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantined: Option<PathBuf>,
+}
+
+// nothing is moved unless `quarantine`
+pub fn verify(root: &Path, mirrors: &[PathBuf], quarantine: bool) -> io::Result<VerifyReport> {
+    let mut scan = Scan { root, mirrors, quarantine, report: VerifyReport::default() };
+    if root.is_dir() {
+        scan.folder(root)?;
+    }
+    Ok(scan.report)
+}
+
+struct Scan<'a> {
+    root: &'a Path,
+    mirrors: &'a [PathBuf],
+    quarantine: bool,
+    report: VerifyReport,
+}
+
+impl Scan<'_> {
+    fn folder(&mut self, folder: &Path) -> io::Result<()> {
+        let manifest = Manifest::load(folder).unwrap_or_default();
+        for entry in fs::read_dir(folder)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path.file_name() != Some(QUARANTINE_FOLDER_NAME.as_ref()) && !chunks::is_chunk_folder(&path) {
+                    self.folder(&path)?;
+                }
+                continue;
+            }
+            // a chunk list is checked by its chunks put together
+            let chunked = chunks::is_chunk_list(&path);
+            if !chunked && !is_backup_file(&path) {
+                continue;
+            }
+            let sha256 = match digest(&path) {
+                Ok(sha256) => sha256,
+                Err(e) => {
+                    self.problem(path, format!("could not be read: {}", e), false);
+                    continue;
+                },
+            };
+            self.report.checked += 1;
+            let size = match chunked {
+                true => chunks::read_list(&path).map(|list| list.size),
+                false => path.metadata().map(|meta| meta.len()),
+            };
+            self.report.bytes += size.unwrap_or_default();
+
+            let backup = chunks::backup_path(&path).unwrap_or_else(|| path.clone());
+            let recorded = store::backup_file_timestamp(&backup)
+                .and_then(|timestamp| manifest.backups.get(&timestamp.to_string()))
+                .and_then(|record| record.sha256.clone())
+                .or_else(|| chunked.then(|| chunks::read_list(&path).ok().map(|list| list.sha256)).flatten());
+            let relative = path.strip_prefix(self.root).unwrap().to_owned();
+            let expected = match recorded {
+                Some(recorded) if recorded != sha256 => {
+                    self.problem(path.clone(), "differs from what was backed up".to_owned(), true);
+                    recorded
+                },
+                _ => sha256,
+            };
+
+            let copies: Vec<_> = self.mirrors.iter().map(|mirror| mirror.join(&relative)).filter(|copy| copy.is_file()).collect();
+            for copy in copies {
+                match digest(&copy) {
+                    Ok(copy_sha256) if copy_sha256 == expected => {},
+                    Ok(_) => self.problem(copy, format!("differs from `{}`", path.display()), true),
+                    Err(e) => self.problem(copy, format!("could not be read: {}", e), false),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn problem(&mut self, path: PathBuf, problem: String, bad: bool) {
+        if bad && self.quarantine && chunks::is_chunk_list(&path) {
+            match chunks::remove_damaged(&path) {
+                Ok(removed) => removed.iter().for_each(|hash| log::warn!("removed the damaged chunk {}", hash)),
+                Err(e) => log::warn!("could not look for the damaged chunks of `{}`: {}", path.display(), e),
+            }
+        }
+        let quarantined = if bad && self.quarantine {
+            quarantine(&path, &problem)
+                .map_err(|e| log::warn!("could not quarantine `{}`: {}", path.display(), e))
+                .ok()
+        } else {
+            None
+        };
+        self.report.problems.push(VerifyProblem { path, problem, quarantined });
+    }
+}
+
+fn quarantine(path: &Path, reason: &str) -> io::Result<PathBuf> {
+    let folder = path.parent().unwrap();
+    let moved = folder.join(QUARANTINE_FOLDER_NAME).join(path.file_name().unwrap());
+    fs::create_dir_all(moved.parent().unwrap())?;
+    fs::rename(path, &moved)?;
+
+    let mut manifest = Manifest::load(folder)?;
+    let record = QuarantineRecord { time: chrono::Utc::now(), file: moved.clone(), reason: reason.to_owned() };
+    manifest.quarantined.push(record);
+    manifest.save(folder)?;
+    Ok(moved)
+}
+
+fn digest(path: &Path) -> io::Result<String> {
+    match chunks::is_chunk_list(path) {
+        true => chunks::sha256(path),
+        false => store::sha256(path),
+    }
+}
+
+// named `<file name>.<timestamp>`, not the labels, manifests or unfinished copies
+fn is_backup_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| !extension.is_empty() && extension.bytes().all(|b| b.is_ascii_digit()))
+}
@@ -0,0 +1,103 @@
+// Copy a locked file out of a Volume Shadow Copy snapshot (Windows, needs administrator).
+//
+// The snapshot is created and deleted through WMI `Win32_ShadowCopy` by powershell,
+// so no extra COM bindings are needed.
+
+use std::io;
+use std::path::Path;
+
+#[cfg(all(windows, feature = "vss"))]
+// the sha256 of what was read from the snapshot, the locked save itself is not opened
+pub fn copy_from_shadow(src: &Path, dst: &Path) -> io::Result<String> {
+    use std::fs;
+    use std::path::{Component, PathBuf, Prefix};
+
+    use log::{debug, info};
+
+    let src = fs::canonicalize(src)?;
+    let mut components = src.components();
+    let volume = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::VerbatimDisk(disk) | Prefix::Disk(disk) => format!("{}:\\", disk as char),
+            _ => return Err(unsupported("only local disk volumes have shadow copies")),
+        },
+        _ => return Err(unsupported("not an absolute path")),
+    };
+    // skip the root `\`
+    let relative: PathBuf = components.skip(1).collect();
+
+    let shadow = ShadowCopy::create(&volume)?;
+    let shadow_src = PathBuf::from(format!("{}\\", shadow.device)).join(relative);
+    debug!("copy `{}` from shadow copy `{}`", shadow_src.display(), shadow.id);
+
+    let sha256 = crate::store::copy_hashed_with_progress(&shadow_src, dst, |_, _| {})?;
+    info!("[Shadow] {}", shadow_src.display());
+
+    Ok(sha256)
+}
+
+#[cfg(not(all(windows, feature = "vss")))]
+pub fn copy_from_shadow(_src: &Path, _dst: &Path) -> io::Result<String> {
+    Err(unsupported("volume shadow copy needs the `vss` feature on Windows"))
+}
+
+fn unsupported(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg)
+}
+
+#[cfg(all(windows, feature = "vss"))]
+struct ShadowCopy {
+    id: String,
+    device: String,
+}
+
+#[cfg(all(windows, feature = "vss"))]
+impl ShadowCopy {
+    fn create(volume: &str) -> io::Result<ShadowCopy> {
+        let script = format!(
+            "$r = (Get-WmiObject -List Win32_ShadowCopy).Create('{}', 'ClientAccessible'); \
+             if ($r.ReturnValue -ne 0) {{ exit $r.ReturnValue }}; \
+             $s = Get-WmiObject Win32_ShadowCopy | Where-Object {{ $_.ID -eq $r.ShadowID }}; \
+             Write-Output $s.ID; Write-Output $s.DeviceObject",
+            volume
+        );
+        let output = powershell(&script)?;
+        let mut lines = output.lines().map(str::trim).filter(|line| !line.is_empty());
+        match (lines.next(), lines.next()) {
+            (Some(id), Some(device)) => Ok(ShadowCopy { id: id.to_owned(), device: device.to_owned() }),
+            _ => Err(io::Error::other(format!("unexpected shadow copy output: {}", output))),
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "vss"))]
+impl Drop for ShadowCopy {
+    fn drop(&mut self) {
+        let script = format!(
+            "Get-WmiObject Win32_ShadowCopy | Where-Object {{ $_.ID -eq '{}' }} | ForEach-Object {{ $_.Delete() }}",
+            self.id
+        );
+        if let Err(e) = powershell(&script) {
+            log::warn!("could not delete shadow copy `{}`: {}", self.id, e);
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "vss"))]
+fn powershell(script: &str) -> io::Result<String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let msg = format!("powershell exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr));
+        Err(io::Error::other(msg))
+    }
+}
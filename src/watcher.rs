@@ -0,0 +1,70 @@
+// The watcher of the saves: the native one of the platform, or polling the modified times for
+// folders that report no changes (network drives, FUSE mounts).
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use notify::{EventHandler, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::longpath;
+
+#[derive(Debug)]
+pub(crate) enum AnyWatcher {
+    Native(RecommendedWatcher),
+    Poll(PollWatcher),
+}
+
+impl AnyWatcher {
+    // polls every `poll` if set
+    pub fn new(poll: Option<Duration>, handler: impl EventHandler) -> notify::Result<AnyWatcher> {
+        Ok(match poll {
+            Some(interval) => AnyWatcher::Poll(PollWatcher::new(handler, notify::Config::default().with_poll_interval(interval))?),
+            None => AnyWatcher::Native(RecommendedWatcher::new(handler, notify::Config::default())?),
+        })
+    }
+
+    pub fn is_polling(&self) -> bool {
+        matches!(self, AnyWatcher::Poll(_))
+    }
+
+    pub fn watch(&mut self, path: &Path, mode: RecursiveMode) -> notify::Result<()> {
+        let path = &longpath::extended(path);
+        match self {
+            AnyWatcher::Native(watcher) => watcher.watch(path, mode),
+            AnyWatcher::Poll(watcher) => watcher.watch(path, mode),
+        }
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        let path = &longpath::extended(path);
+        match self {
+            AnyWatcher::Native(watcher) => watcher.unwatch(path),
+            AnyWatcher::Poll(watcher) => watcher.unwatch(path),
+        }
+    }
+}
+
+// whether the native watcher misses changes: a save was modified since the last check,
+// but no event came at all
+#[derive(Debug, Default)]
+pub(crate) struct MissedChanges {
+    modified: HashMap<PathBuf, SystemTime>,
+    events: u64,
+}
+
+impl MissedChanges {
+    // `events` is how many the watcher sent so far
+    pub fn check(&mut self, files: impl IntoIterator<Item = PathBuf>, events: u64) -> bool {
+        let mut changed = false;
+        for file in files {
+            if let Ok(modified) = fs::metadata(&file).and_then(|meta| meta.modified()) {
+                changed |= self.modified.insert(file, modified).is_some_and(|before| before != modified);
+            }
+        }
+        let silent = events == self.events;
+        self.events = events;
+        changed && silent
+    }
+}
@@ -0,0 +1,77 @@
+// POST a JSON message to a webhook (Discord, Slack or any other service) for a notification.
+// Failures to deliver it are only logged, the message is not retried.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+
+use crate::notifier::{Notification, Notifier};
+use crate::{Result, WebhookFormat};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const WEBHOOK_USERNAME: &str = "Taiwu Backup";
+
+pub struct Webhook {
+    agent: ureq::Agent,
+    url: String,
+    format: WebhookFormat,
+}
+
+impl Webhook {
+    pub fn new(url: &str, format: WebhookFormat) -> Result<Webhook> {
+        let tls = native_tls::TlsConnector::new().map_err(io::Error::other)?;
+        let agent = ureq::AgentBuilder::new()
+            .tls_connector(Arc::new(tls))
+            .timeout(WEBHOOK_TIMEOUT)
+            .user_agent(&format!("taiwu-backup/{}", env!("CARGO_PKG_VERSION")))
+            .build();
+        let format = match format {
+            WebhookFormat::Auto => detect_format(url),
+            format => format,
+        };
+        info!("Webhook notifications to `{}` ({:?})", redact(url), format);
+        Ok(Webhook { agent, url: url.to_owned(), format })
+    }
+}
+
+impl Notifier for Webhook {
+    fn notify(&self, notification: &Notification) {
+        let body = payload(self.format, notification).to_string();
+        match self.agent.post(&self.url).set("Content-Type", "application/json").send_string(&body) {
+            Ok(_) => debug!("webhook `{}` sent", notification.kind),
+            Err(e) => warn!("[webhook] `{}` not sent: {}", notification.kind, e),
+        }
+    }
+}
+
+fn detect_format(url: &str) -> WebhookFormat {
+    if url.contains("discord.com/api/webhooks") || url.contains("discordapp.com/api/webhooks") {
+        WebhookFormat::Discord
+    } else if url.contains("hooks.slack.com") {
+        WebhookFormat::Slack
+    } else {
+        WebhookFormat::Generic
+    }
+}
+
+// the path of a webhook url is its secret, only the host gets logged
+fn redact(url: &str) -> &str {
+    let start = url.find("://").map_or(0, |i| i + 3);
+    url[start..].find('/').map_or(url, |i| &url[..start + i])
+}
+
+fn payload(format: WebhookFormat, notification: &Notification) -> Value {
+    match format {
+        WebhookFormat::Discord => json!({ "username": WEBHOOK_USERNAME, "content": notification.text }),
+        WebhookFormat::Slack => json!({ "text": notification.text }),
+        WebhookFormat::Auto | WebhookFormat::Generic => json!({
+            "event": notification.kind,
+            "message": notification.text,
+            "time": chrono::offset::Local::now().to_rfc3339(),
+            "details": notification.details,
+        }),
+    }
+}